@@ -0,0 +1,77 @@
+// Time the shell takes to parse a line and run the builtin it names, with no
+// process started, run with `cargo bench`. What's measured is the work done
+// for every command before anything runs
+use std::{
+    ffi::OsString,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use ish::{
+    builtins::{Builtin, Io},
+    exec::ExitStatus,
+    options::Options,
+    parser,
+    shell::Shell,
+};
+
+const LINES: u32 = 100_000;
+const RUNS: usize = 5;
+
+// a builtin that does nothing, so only getting to it is timed
+struct Noop;
+
+impl Builtin for Noop {
+    fn name(&self) -> &str {
+        "noop"
+    }
+
+    fn help(&self) -> &str {
+        "noop: do nothing"
+    }
+
+    fn execute(&self, _: &mut Shell, _: &[OsString], _: &mut Io) -> ExitStatus {
+        ExitStatus::Done(0)
+    }
+}
+
+// best time per line over a few runs, the others mostly measure noise
+fn per_line(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..LINES {
+                run();
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+        / LINES
+}
+
+fn main() {
+    let mut shell = Shell::new(Options::default());
+    shell.register_builtin(Noop);
+
+    let benchmarks = [
+        ("simple", "noop"),
+        ("arguments", "noop a b c d e f g h"),
+        ("quoted", "noop 'a b' \"c $? d\" e\\ f"),
+    ];
+    println!("{:<16}{:>12}{:>12}", "", "parse", "dispatch");
+    for (name, line) in benchmarks {
+        let parse = per_line(|| {
+            black_box(parser::parse(black_box(line)).unwrap());
+        });
+        let dispatch = per_line(|| {
+            black_box(shell.run_line(black_box(line)).unwrap());
+        });
+        println!(
+            "{:<16}{:>12}{:>12}",
+            name,
+            format!("{:?}", parse),
+            format!("{:?}", dispatch)
+        );
+    }
+}
@@ -0,0 +1,68 @@
+// Time the shell takes per external command, run with `cargo bench`. The
+// same scripts are run with /bin/sh when it exists so there's something to
+// compare against
+use std::{
+    env, fs,
+    path::Path,
+    process::{self, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+const COMMANDS: usize = 1000;
+const RUNS: usize = 5;
+
+// best time per command over a few runs, the others mostly measure noise
+fn per_command(shell: &str, script: &Path) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let status = Command::new(shell)
+                .arg(script)
+                .stdout(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(
+                status.success(),
+                "{} failed running {}",
+                shell,
+                script.display()
+            );
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+        / COMMANDS as u32
+}
+
+fn main() {
+    let ish = env!("CARGO_BIN_EXE_ish");
+    let dir = env::temp_dir().join(format!("ish-bench-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let benchmarks = [
+        ("absolute path", "/bin/true\n"),
+        // not a builtin in any shell, so it has to be found on $PATH
+        ("path lookup", "printenv HOME\n"),
+        ("arguments", "/bin/true a b c d e f g h\n"),
+        ("pipeline", "/bin/true | /bin/true\n"),
+    ];
+    println!("{:<16}{:>12}{:>12}", "", "ish", "sh");
+    for (name, line) in benchmarks {
+        let script = dir.join(name.replace(' ', "_"));
+        fs::write(&script, line.repeat(COMMANDS)).unwrap();
+        let ish_time = per_command(ish, &script);
+        let sh_time = if Path::new("/bin/sh").exists() {
+            format!("{:?}", per_command("/bin/sh", &script))
+        } else {
+            "-".to_string()
+        };
+        println!(
+            "{:<16}{:>12}{:>12}",
+            name,
+            format!("{:?}", ish_time),
+            sh_time
+        );
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,1361 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs::File,
+    io::{self, stdout, Read, Write},
+    mem::ManuallyDrop,
+    os::{fd::FromRawFd, unix::ffi::OsStrExt},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    compspec::{self, Spec, Specs},
+    error::ShellError,
+    error_message,
+    exec::{ExecArgs, ExitStatus},
+    expand,
+    history::History,
+    jobs::{captured, parse_job_spec, wait_for_job, Job, JobState, Jobs, Waited},
+    jump,
+    keymap::{self, Binding, Keymaps},
+    named_dirs::{self, NamedDirs},
+    options::Options,
+    parser, printf, prompt,
+    shell::Shell,
+    suggest,
+    sys::{self, Signal},
+    trap::{self, Trap, Traps},
+};
+
+// The set builtin, only handles options for now:
+// `set -o` lists them, `set -o name` turns one on and `set +o name` off,
+// `set -x` and `set +x` do the same for the options with a letter
+pub fn set_builtin(args: &[String], options: &mut Options, location: &str) -> i32 {
+    match args {
+        [] => 0,
+        flags if flags.iter().all(|flag| Options::by_letter(flag).is_some()) => {
+            for flag in flags {
+                options.set(Options::by_letter(flag).unwrap(), flag.starts_with('-'));
+            }
+            0
+        }
+        [flag] if flag == "-o" || flag == "+o" => {
+            for name in Options::NAMES {
+                let state = if options.get(name).unwrap() {
+                    "on"
+                } else {
+                    "off"
+                };
+                println!("{:<16}{}", name, state);
+            }
+            0
+        }
+        [flag, names @ ..] if flag == "-o" || flag == "+o" => {
+            for name in names {
+                if Options::READ_ONLY.contains(&name.as_str()) {
+                    eprintln!("{}: set: {}: read-only option", location, name);
+                    return 1;
+                }
+                match options.set(name, flag == "-o") {
+                    Some(()) => {}
+                    None => {
+                        eprintln!("{}: set: {}: invalid option name", location, name);
+                        return 2;
+                    }
+                }
+            }
+            0
+        }
+        [flag, ..] => {
+            eprintln!("{}: set: {}: invalid option", location, flag);
+            2
+        }
+    }
+}
+
+// The shopt builtin: `shopt -s NAME...` and `shopt -u NAME...` turn options
+// on and off, `shopt NAME...` shows them and fails when one is off, -q
+// without printing anything. Without names it's every option, only the ones
+// on or off with -s or -u
+fn shopt_builtin(args: &[String], options: &mut Options, location: &str) -> i32 {
+    let (mut set, mut unset, mut quiet) = (false, false, false);
+    let mut names = args;
+    while let Some(flag) = names
+        .first()
+        .filter(|arg| arg.starts_with('-') && arg.len() > 1)
+    {
+        for letter in flag[1..].chars() {
+            match letter {
+                's' => set = true,
+                'u' => unset = true,
+                'q' => quiet = true,
+                _ => {
+                    eprintln!("{}: shopt: usage: shopt [-squ] [NAME...]", location);
+                    return 2;
+                }
+            }
+        }
+        names = &names[1..];
+    }
+    if set && unset {
+        eprintln!("{}: shopt: cannot set and unset options at once", location);
+        return 1;
+    }
+    if let Some(name) = names
+        .iter()
+        .find(|name| !Options::SHOPT_NAMES.contains(&name.as_str()))
+    {
+        eprintln!("{}: shopt: {}: invalid shell option name", location, name);
+        return 1;
+    }
+    if !names.is_empty() && (set || unset) {
+        for name in names {
+            options.set(name, set);
+        }
+        return 0;
+    }
+    let shown: Vec<&str> = if names.is_empty() {
+        (Options::SHOPT_NAMES.iter().copied())
+            .filter(|name| !(set || unset) || options.get(name) == Some(set))
+            .collect()
+    } else {
+        names.iter().map(String::as_str).collect()
+    };
+    let mut status = 0;
+    for name in shown {
+        let on = options.get(name).unwrap();
+        if !on {
+            status = 1;
+        }
+        if !quiet {
+            println!("{:<16}{}", name, if on { "on" } else { "off" });
+        }
+    }
+    status
+}
+
+// The printf builtin: the arguments formatted like printf(1) does, and %q
+// for an argument quoted to be read back in. It fails when an argument
+// wasn't what its conversion needs
+fn printf_builtin(args: &[OsString], location: &str) -> i32 {
+    let args = match args.first() {
+        Some(arg) if arg == "--" => &args[1..],
+        _ => args,
+    };
+    let Some((format, args)) = args.split_first() else {
+        eprintln!("{}: printf: usage: printf FORMAT [ARGUMENT...]", location);
+        return 2;
+    };
+    let formatted = printf::format(format.as_bytes(), args);
+    let _ = stdout().lock().write_all(&formatted.output);
+    for error in &formatted.errors {
+        eprintln!("{}: printf: {}", location, error);
+    }
+    i32::from(!formatted.errors.is_empty())
+}
+
+// The namedir builtin: `namedir NAME=DIR...` names directories so ~NAME
+// stands for them, `namedir -r NAME...` takes the names away and `namedir`
+// lists them. A ~ at the start of DIR is expanded, and a relative DIR is
+// taken from the current directory
+fn namedir_builtin(args: &[String], named_dirs: &mut NamedDirs, location: &str) -> i32 {
+    match args {
+        [] => {
+            for (name, dir) in named_dirs.iter() {
+                println!("namedir {}={}", name, parser::quote(&dir.to_string_lossy()));
+            }
+            0
+        }
+        [flag, names @ ..] if flag == "-r" => {
+            let mut status = 0;
+            for name in names {
+                if !named_dirs.remove(name) {
+                    eprintln!("{}: namedir: {}: no such named directory", location, name);
+                    status = 1;
+                }
+            }
+            status
+        }
+        definitions => {
+            for definition in definitions {
+                let Some((name, dir)) = definition
+                    .split_once('=')
+                    .filter(|(name, dir)| named_dirs::is_name(name) && !dir.is_empty())
+                else {
+                    eprintln!(
+                        "{}: namedir: usage: namedir [NAME=DIR...] | namedir -r NAME...",
+                        location
+                    );
+                    return 2;
+                };
+                let mut path = PathBuf::from(dir);
+                if let Some(rest) = dir.strip_prefix('~') {
+                    let (user, rest) = rest.split_once('/').unwrap_or((rest, ""));
+                    if let Some(home) = expand::tilde(user, named_dirs) {
+                        path = Path::new(&home).join(rest);
+                    }
+                }
+                if path.is_relative() {
+                    let cwd = env::current_dir().unwrap_or_default();
+                    path = cwd.join(path).components().collect();
+                }
+                named_dirs.set(name, path);
+            }
+            0
+        }
+    }
+}
+
+// The history builtin: list the entries, or only the last N with `history N`,
+// with the times they were entered with -t or when $HISTTIMEFORMAT is set.
+// `history -d N` deletes an entry and `history -d START-END` several,
+// `history -n` takes in what other sessions added to the history file
+fn history_builtin(args: &[String], history: &mut History, location: &str) -> i32 {
+    if args.first().is_some_and(|arg| arg == "-n") {
+        if let Err(e) = history.merge() {
+            let file = history.file().cloned().unwrap_or_default();
+            eprintln!(
+                "{}: history: {}: {}",
+                location,
+                file.display(),
+                error_message(&e)
+            );
+            return 1;
+        }
+        return 0;
+    }
+    if args.first().is_some_and(|arg| arg == "-d") {
+        let Some(which) = args.get(1) else {
+            eprintln!("{}: history: -d: option requires an argument", location);
+            return 2;
+        };
+        let (start, end) = which.split_once('-').unwrap_or((which, which));
+        let numbers = history.numbers();
+        let Some(range) = start.parse::<usize>().ok().zip(end.parse::<usize>().ok()) else {
+            eprintln!(
+                "{}: history: {}: numeric argument required",
+                location, which
+            );
+            return 2;
+        };
+        let in_range = |n: &usize| numbers.contains(n);
+        if !in_range(&range.0) || !in_range(&range.1) || range.0 > range.1 {
+            eprintln!(
+                "{}: history: {}: history position out of range",
+                location, which
+            );
+            return 1;
+        }
+        if let Err(e) = history.delete(range.0..=range.1) {
+            let file = history.file().cloned().unwrap_or_default();
+            eprintln!(
+                "{}: history: {}: {}",
+                location,
+                file.display(),
+                error_message(&e)
+            );
+            return 1;
+        }
+        return 0;
+    }
+    let (timestamps, args) = match args.split_first() {
+        Some((flag, rest)) if flag == "-t" => (true, rest),
+        _ => (false, args),
+    };
+    let format = env::var("HISTTIMEFORMAT")
+        .ok()
+        .filter(|format| !format.is_empty());
+    let format = format.or_else(|| timestamps.then(|| "%F %T ".to_string()));
+    let entries = history.numbered();
+    let count = match args.first() {
+        None => entries.len(),
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(n) => n.min(entries.len()),
+            Err(_) => {
+                eprintln!("{}: history: {}: numeric argument required", location, arg);
+                return 2;
+            }
+        },
+    };
+    let skip = entries.len() - count;
+    for (number, entry) in entries.skip(skip) {
+        // entries loaded from a file without times have none to show
+        let time = match (&format, history.time(number)) {
+            (Some(format), Some(time)) => sys::format_time(time, format),
+            (Some(_), None) => "?? ".to_string(),
+            (None, _) => String::new(),
+        };
+        println!("{:5}  {}{}", number, time, entry);
+    }
+    0
+}
+
+// The bindkey builtin: `bindkey KEYS ACTION` binds a key sequence to an action
+// of the line editor and `bindkey -s KEYS TEXT` to text it inserts, `bindkey
+// -r KEYS` takes the binding away. With no arguments it lists the bindings as
+// the commands that make them. `-M KEYMAP` first picks the keymap, it's the
+// one of the editing mode otherwise
+fn bindkey_builtin(args: &[String], keymaps: &mut Keymaps, vi: bool, location: &str) -> i32 {
+    let (name, args) = match args {
+        [flag, name, rest @ ..] if flag == "-M" => (name.as_str(), rest),
+        _ if vi => ("viins", args),
+        _ => ("emacs", args),
+    };
+    let Some(keymap) = keymaps.get_mut(name) else {
+        eprintln!("{}: bindkey: {}: no such keymap", location, name);
+        return 1;
+    };
+    let keys = |text: &str| match keymap::parse_keys(text) {
+        Some(keys) if !keys.is_empty() => Some(keys),
+        _ => {
+            eprintln!("{}: bindkey: {}: invalid key sequence", location, text);
+            None
+        }
+    };
+    match args {
+        [] => {
+            for (keys, binding) in keymap.bindings() {
+                let keys = keymap::show_keys(keys);
+                match binding {
+                    Binding::Action(action) => {
+                        println!("bindkey '{}' {}", keys, keymap::action_name(*action))
+                    }
+                    Binding::Text(text) => println!(
+                        "bindkey -s '{}' '{}'",
+                        keys,
+                        keymap::show_keys(text.as_bytes())
+                    ),
+                }
+            }
+            0
+        }
+        [flag, sequence, text] if flag == "-s" => {
+            let (Some(sequence), Some(text)) = (keys(sequence), keymap::parse_keys(text)) else {
+                return 1;
+            };
+            let text = String::from_utf8_lossy(&text).into_owned();
+            keymap.bind(sequence, Binding::Text(text));
+            0
+        }
+        [flag, sequence] if flag == "-r" => {
+            let Some(sequence) = keys(sequence) else {
+                return 1;
+            };
+            if !keymap.unbind(&sequence) {
+                eprintln!("{}: bindkey: {}: not bound", location, args[1]);
+                return 1;
+            }
+            0
+        }
+        [sequence, name] if !sequence.starts_with('-') => {
+            let Some(action) = keymap::action(name) else {
+                eprintln!("{}: bindkey: {}: unknown action", location, name);
+                return 1;
+            };
+            let Some(sequence) = keys(sequence) else {
+                return 1;
+            };
+            keymap.bind(sequence, Binding::Action(action));
+            0
+        }
+        _ => {
+            eprintln!(
+                "{}: bindkey: usage: bindkey [-M KEYMAP] [-s|-r] KEYS [ACTION|TEXT]",
+                location
+            );
+            2
+        }
+    }
+}
+
+// The complete builtin: `complete -W WORDS NAME...` completes the arguments of
+// commands with the words of a list and `complete -C COMMAND NAME...` with
+// the lines a command prints, `complete -r NAME...` takes that away. `complete
+// -p` lists them as the commands that set them up, only for NAMEs if given
+fn complete_builtin(args: &[String], specs: &mut Specs, location: &str) -> i32 {
+    let missing = |name: &str| {
+        eprintln!(
+            "{}: complete: {}: no completion specification",
+            location, name
+        );
+    };
+    let (flag, rest) = match args {
+        [] => ("-p", args),
+        [flag, rest @ ..] => (flag.as_str(), rest),
+    };
+    match (flag, rest) {
+        ("-p", names) => {
+            let mut status = 0;
+            for name in names.iter().filter(|name| specs.get(name).is_none()) {
+                missing(name);
+                status = 1;
+            }
+            for (name, spec) in specs.iter() {
+                if names.is_empty() || names.iter().any(|listed| listed == name) {
+                    println!("{}", compspec::show(name, spec));
+                }
+            }
+            status
+        }
+        ("-r", names) if !names.is_empty() => {
+            let mut status = 0;
+            for name in names.iter().filter(|name| !specs.remove(name)) {
+                missing(name);
+                status = 1;
+            }
+            status
+        }
+        ("-W" | "-C", [what, names @ ..]) if !names.is_empty() => {
+            for name in names {
+                let spec = match flag {
+                    "-W" => Spec::Words(what.split_whitespace().map(String::from).collect()),
+                    _ => Spec::Command(what.clone()),
+                };
+                specs.set(name, spec);
+            }
+            0
+        }
+        _ => {
+            eprintln!(
+                "{}: complete: usage: complete -W WORDS|-C COMMAND NAME... | -r NAME... | -p [NAME...]",
+                location
+            );
+            2
+        }
+    }
+}
+
+// The trap builtin: `trap COMMAND NAME...` runs COMMAND when the shell exits
+// for EXIT, after a command fails for ERR and before each command for
+// DEBUG. `trap - NAME...` takes them away, `trap -p` lists them
+fn trap_builtin(args: &[String], traps: &mut Traps, location: &str) -> i32 {
+    let mut status = 0;
+    let mut each = |names: &[String], action: &mut dyn FnMut(&mut Traps, Trap)| {
+        for name in names {
+            match Trap::from_name(name) {
+                Some(trap) => action(traps, trap),
+                None => {
+                    eprintln!(
+                        "{}: trap: {}: only EXIT, ERR and DEBUG can be trapped",
+                        location, name
+                    );
+                    status = 1;
+                }
+            }
+        }
+    };
+    match args {
+        _ if args.is_empty() || args[0] == "-p" => {
+            let names = &args[args.len().min(1)..];
+            let mut listed = Vec::new();
+            each(names, &mut |_, trap| listed.push(trap));
+            for (trap, command) in traps.iter() {
+                if names.is_empty() || listed.contains(&trap) {
+                    println!("{}", trap::show(trap, command));
+                }
+            }
+        }
+        // `trap EXIT` takes it away like `trap - EXIT`
+        [_] => each(args, &mut |traps, trap| {
+            traps.remove(trap);
+        }),
+        [command, names @ ..] if !names.is_empty() => each(names, &mut |traps, trap| {
+            if command == "-" {
+                traps.remove(trap);
+            } else {
+                traps.set(trap, command);
+            }
+        }),
+        _ => {
+            eprintln!(
+                "{}: trap: usage: trap COMMAND|- NAME... | trap -p [NAME...]",
+                location
+            );
+            return 2;
+        }
+    }
+    status
+}
+
+// The jobs builtin: list the jobs, or show the output captured for one with
+// `jobs -o %n`, the most recent one without a job spec
+fn jobs_builtin(args: &[String], jobs: &[Job]) -> Result<i32, ShellError> {
+    match args.first().map(String::as_str) {
+        None => {
+            for job in jobs {
+                println!("[{}] {} {}", job.number, job.pgid, job.state.name());
+            }
+            Ok(0)
+        }
+        Some("-o") => {
+            let job = match args.get(1) {
+                None => jobs.last(),
+                Some(spec) => parse_job_spec(spec)
+                    .and_then(|number| jobs.iter().find(|job| job.number == number)),
+            };
+            let spec = args.get(1).map_or("%+", String::as_str);
+            let error = |message: &str| ShellError::Job {
+                builtin: "jobs",
+                message: format!("{}: {}", spec, message),
+            };
+            let job = job.ok_or_else(|| error("no such job"))?;
+            let file = job
+                .output
+                .as_ref()
+                .ok_or_else(|| error("output isn't captured"))?;
+            let output = captured(file).map_err(|e| error(&error_message(&e)))?;
+            let _ = stdout().lock().write_all(&output);
+            Ok(0)
+        }
+        Some(arg) => Err(ShellError::Usage {
+            builtin: "jobs",
+            message: format!("{}: invalid option", arg),
+        }),
+    }
+}
+
+// The wait builtin: `wait` waits for the background jobs to finish, `wait
+// %N...` for those jobs and `wait -n [%N...]` for the first of them. The
+// status is the one of the job waited for last, 127 when there was none to
+// wait for
+fn wait_builtin(args: &[String], jobs: &Jobs, location: &str) -> i32 {
+    let (any, specs) = match args {
+        [flag, specs @ ..] if flag == "-n" => (true, specs),
+        [flag, ..] if flag.starts_with('-') && flag.len() > 1 => {
+            eprintln!("{}: wait: usage: wait [-n] [%N...]", location);
+            return 2;
+        }
+        _ => (false, args),
+    };
+    let mut numbers = Vec::new();
+    for spec in specs {
+        let number = parse_job_spec(spec)
+            .filter(|&number| jobs.lock().iter().any(|job| job.number == number));
+        match number {
+            Some(number) => numbers.push(number),
+            None => {
+                eprintln!("{}: wait: {}: no such job", location, spec);
+                return 127;
+            }
+        }
+    }
+    let finished = |waited| match waited {
+        Waited::Finished(job) => {
+            job.finish_output();
+            Ok(job.exit_status())
+        }
+        Waited::NoJobs => Err(127),
+        Waited::Interrupted => Err(130),
+    };
+    if any {
+        let waited = jobs.wait_for_any(|job| numbers.is_empty() || numbers.contains(&job.number));
+        return finished(waited).unwrap_or_else(|status| status);
+    }
+    if numbers.is_empty() {
+        loop {
+            match finished(jobs.wait_for_any(|_| true)) {
+                Ok(_) => {}
+                Err(127) => return 0,
+                Err(status) => return status,
+            }
+        }
+    }
+    let mut status = 0;
+    for number in numbers {
+        status = match finished(jobs.wait_for_any(|job| job.number == number)) {
+            Ok(status) | Err(status @ 127) => status,
+            Err(status) => return status,
+        };
+    }
+    status
+}
+
+// Status for the exit builtin: its argument truncated to 0-255, or $? without one
+pub fn exit_builtin_status(args: &[String], last_status: i32, location: &str) -> i32 {
+    match args.first() {
+        None => last_status,
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(n) => (n & 0xff) as i32,
+            Err(_) => {
+                eprintln!("{}: exit: {}: numeric argument required", location, arg);
+                2
+            }
+        },
+    }
+}
+
+// Where a builtin reads and writes: the shell's stdin, stdout and stderr with
+// the redirections of the command in place, or the pipes around it
+pub struct Io<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+// A command the shell runs itself instead of looking for it in $PATH. More
+// can be added with Shell::register_builtin
+pub trait Builtin {
+    fn name(&self) -> &str;
+    // one line on how it's used and what it does, for `help`
+    fn help(&self) -> &str;
+    // Run with the arguments after the name. Done gives the status it leaves
+    // in $?, Exit makes the shell exit with that status
+    fn execute(&self, shell: &mut Shell, args: &[OsString], io: &mut Io) -> ExitStatus;
+}
+
+// The builtins ish comes with, a function each. They set shell.last_status
+// and return the status to exit with if the shell should exit
+#[derive(Clone, Copy)]
+struct Native {
+    name: &'static str,
+    help: &'static str,
+    run: fn(&mut Shell, &[OsString]) -> Option<i32>,
+}
+
+impl Builtin for Native {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn help(&self) -> &str {
+        self.help
+    }
+
+    // they write to the shell's own stdout and stderr, which is where `io`
+    // goes too
+    fn execute(&self, shell: &mut Shell, args: &[OsString], _: &mut Io) -> ExitStatus {
+        match (self.run)(shell, args) {
+            Some(status) => ExitStatus::Exit(status),
+            None => ExitStatus::Done(shell.last_status),
+        }
+    }
+}
+
+const NATIVE: &[Native] = &[
+    Native {
+        name: "exit",
+        help: "exit [N]: leave the shell with status N, or the status of the last command",
+        run: exit,
+    },
+    Native {
+        name: "fg",
+        help: "fg [%N]: bring job N, or the newest job, to the foreground",
+        run: fg,
+    },
+    Native {
+        name: "bg",
+        help: "bg [%N]: continue job N, or the newest stopped job, in the background",
+        run: bg,
+    },
+    Native {
+        name: "wait",
+        help: "wait [-n] [%N...]: wait for background jobs to finish, only the first with -n",
+        run: wait,
+    },
+    Native {
+        name: "jobs",
+        help: "jobs [-o [%N]]: list the jobs, or show the output captured for one",
+        run: jobs,
+    },
+    Native {
+        name: "cd",
+        help: "cd DIR | cd -N: change the current directory, or go to the Nth on the directory stack",
+        run: cd,
+    },
+    Native {
+        name: "pushd",
+        help: "pushd [DIR]: change the current directory and put the one left on the directory stack",
+        run: pushd,
+    },
+    Native {
+        name: "popd",
+        help: "popd [+N]: go back to the directory on top of the directory stack, or drop the Nth",
+        run: popd,
+    },
+    Native {
+        name: "dirs",
+        help: "dirs [-v|-c]: list the directory stack, numbered with -v, or empty it",
+        run: dirs,
+    },
+    Native {
+        name: "namedir",
+        help: "namedir [NAME=DIR...] | namedir -r NAME...: name directories for ~NAME, or list the names",
+        run: namedir,
+    },
+    Native {
+        name: "j",
+        help: "j WORD... | j -l [WORD...]: go to the most used directory with the words in its path, or list them",
+        run: j,
+    },
+    Native {
+        name: "printf",
+        help: "printf FORMAT [ARGUMENT...]: print the arguments formatted, %q quotes one to be read back in",
+        run: printf,
+    },
+    Native {
+        name: "shopt",
+        help: "shopt [-squ] [NAME...]: list the shell options bash has under shopt, or turn them on or off",
+        run: shopt,
+    },
+    Native {
+        name: "set",
+        help: "set [-o|+o] [NAME...] | set -enx|+enx: list the options, or turn them on or off",
+        run: set,
+    },
+    Native {
+        name: "source",
+        help: "source FILE: run the commands in a file in this shell",
+        run: source,
+    },
+    Native {
+        name: ".",
+        help: ". FILE: run the commands in a file in this shell",
+        run: dot,
+    },
+    Native {
+        name: "history",
+        help: "history [-t] [N] | -d N[-M] | -n: list, delete or reload history entries",
+        run: history,
+    },
+    Native {
+        name: "exec",
+        help: "exec [-a NAME] [COMMAND [ARG...]]: replace the shell with a command",
+        run: exec,
+    },
+    Native {
+        name: "ttyreset",
+        help: "ttyreset: put the terminal back the way it was when the shell started",
+        run: ttyreset,
+    },
+    Native {
+        name: "bindkey",
+        help: "bindkey [-M KEYMAP] [-s|-r] KEYS [ACTION|TEXT]: bind keys of the line editor",
+        run: bindkey,
+    },
+    Native {
+        name: "complete",
+        help:
+            "complete -W WORDS|-C COMMAND NAME... | -r NAME... | -p [NAME...]: complete arguments",
+        run: complete,
+    },
+    Native {
+        name: "trap",
+        help: "trap COMMAND|- NAME... | trap -p [NAME...]: run COMMAND on EXIT, ERR or DEBUG",
+        run: trap,
+    },
+    Native {
+        name: "help",
+        help: "help [NAME...]: describe the builtins",
+        run: help,
+    },
+];
+
+// The builtins of a shell by name
+#[derive(Clone)]
+pub struct Builtins {
+    table: BTreeMap<String, Rc<dyn Builtin>>,
+}
+
+impl Default for Builtins {
+    // the ones ish comes with
+    fn default() -> Builtins {
+        let mut builtins = Builtins {
+            table: BTreeMap::new(),
+        };
+        for native in NATIVE {
+            builtins.add(Rc::new(*native));
+        }
+        builtins
+    }
+}
+
+impl Builtins {
+    // Add a builtin, in place of the one with the same name if there is one
+    pub fn add(&mut self, builtin: Rc<dyn Builtin>) {
+        self.table.insert(builtin.name().to_string(), builtin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.table.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.table.contains_key(name)
+    }
+
+    // their names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.table.keys().map(String::as_str)
+    }
+}
+
+// most builtins only deal with text, paths and commands keep their bytes
+fn text(words: &[OsString]) -> Vec<String> {
+    words
+        .iter()
+        .map(|word| word.to_string_lossy().into_owned())
+        .collect()
+}
+
+// Exit the shell
+fn exit(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    Some(exit_builtin_status(
+        &text(words),
+        shell.last_status,
+        &shell.location(),
+    ))
+}
+
+// Put the terminal back the way it was when the shell started, with echo,
+// line editing and newline translation on whatever they were
+fn ttyreset(shell: &mut Shell, _: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    let startup_modes = shell.startup_modes.filter(|_| shell.job_control);
+    let Some(startup_modes) = startup_modes else {
+        eprintln!("{}: ttyreset: no terminal", location);
+        shell.last_status = 1;
+        return None;
+    };
+    let modes = startup_modes.sane();
+    if let Err(e) = modes.apply(shell.shell_terminal) {
+        eprintln!("{}: ttyreset: {}", location, error_message(&e));
+        shell.last_status = 1;
+        return None;
+    }
+    shell.terminal_modes = Some(modes);
+    shell.last_status = 0;
+    None
+}
+
+// Replace the shell with a command, `-a name` gives it as argv[0]
+fn exec(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    if shell.restricted {
+        shell.report(ShellError::restricted("exec"));
+        return None;
+    }
+    let (argv0, args) = match words {
+        [flag, name, rest @ ..] if flag == "-a" => (Some(name.as_os_str()), rest),
+        [flag] if flag == "-a" => {
+            eprintln!(
+                "{}: exec: -a: option requires an argument",
+                shell.location()
+            );
+            shell.last_status = 2;
+            return None;
+        }
+        _ => (None, words),
+    };
+    // without a command only the redirections matter, they are kept
+    let Some((name, rest)) = args.split_first() else {
+        shell.last_status = 0;
+        return None;
+    };
+    let exec_args =
+        ExecArgs::new(name, rest, &shell.shell_exe, &mut shell.path_cache).and_then(|exec_args| {
+            match argv0 {
+                Some(argv0) => exec_args.argv0(argv0),
+                None => Ok(exec_args),
+            }
+        });
+    let _ = stdout().flush();
+    let err = match exec_args {
+        Ok(exec_args) => exec_args.exec(),
+        Err(e) => e,
+    };
+    shell.report(ShellError::exec(name, err));
+    // a script can't go on when it couldn't be replaced
+    if !shell.interactive {
+        return Some(shell.last_status);
+    }
+    None
+}
+
+// Where the job a `fg` or `bg` argument like %2 names is in the table, the
+// newest one `fits` without an argument
+fn job_index(
+    builtin: &'static str,
+    args: &[String],
+    jobs: &[Job],
+    fits: impl Fn(&Job) -> bool,
+    none: &str,
+) -> Result<usize, ShellError> {
+    let error = |message: String| ShellError::Job { builtin, message };
+    match args {
+        [] => jobs
+            .iter()
+            .rposition(fits)
+            .ok_or_else(|| error(none.to_string())),
+        [spec] => parse_job_spec(spec)
+            .and_then(|number| jobs.iter().position(|job| job.number == number))
+            .ok_or_else(|| error(format!("{}: no such job", spec))),
+        _ => Err(ShellError::Usage {
+            builtin,
+            message: format!("usage: {} [%N]", builtin),
+        }),
+    }
+}
+
+fn fg(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let mut jobs = shell.jobs.lock();
+    let found = job_index("fg", &text(words), &jobs, |_| true, "no current job");
+    let job = found.map(|i| jobs.remove(i));
+    drop(jobs);
+    let mut job = match job {
+        Ok(job) => job,
+        Err(e) => {
+            shell.report(e);
+            return None;
+        }
+    };
+    shell.last_status = wait_for_job(&mut job, true, shell.foreground_terminal());
+    if job.state == JobState::Stopped {
+        shell.jobs.add_stopped(job);
+    } else {
+        job.report_signal();
+        job.finish_output();
+    }
+    None
+}
+
+fn bg(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let stopped = |job: &Job| job.state == JobState::Stopped;
+    let jobs = shell.jobs.lock();
+    let continued = job_index("bg", &text(words), &jobs, stopped, "no stopped job").and_then(|i| {
+        sys::kill_group(jobs[i].pgid, Signal::Cont).map_err(|e| ShellError::Job {
+            builtin: "bg",
+            message: error_message(&e),
+        })
+    });
+    drop(jobs);
+    match continued {
+        Ok(()) => shell.last_status = 0,
+        Err(e) => shell.report(e),
+    }
+    None
+}
+
+fn set(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = set_builtin(&text(words), &mut shell.options, &location);
+    None
+}
+
+fn shopt(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = shopt_builtin(&text(words), &mut shell.options, &location);
+    None
+}
+
+fn printf(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = printf_builtin(words, &location);
+    None
+}
+
+fn namedir(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = namedir_builtin(&text(words), &mut shell.named_dirs, &location);
+    None
+}
+
+fn history(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = history_builtin(&text(words), &mut shell.history, &location);
+    None
+}
+
+fn bindkey(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = bindkey_builtin(
+        &text(words),
+        &mut shell.keymaps,
+        shell.options.vi,
+        &location,
+    );
+    None
+}
+
+fn complete(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = complete_builtin(&text(words), &mut shell.completions, &location);
+    None
+}
+
+fn trap(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = trap_builtin(&text(words), &mut shell.traps, &location);
+    None
+}
+
+fn wait(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let location = shell.location();
+    shell.last_status = wait_builtin(&text(words), &shell.jobs, &location);
+    None
+}
+
+fn jobs(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let listed = jobs_builtin(&text(words), &shell.jobs.lock());
+    match listed {
+        Ok(status) => shell.last_status = status,
+        Err(e) => shell.report(e),
+    }
+    None
+}
+
+// The cd builtin: `cd DIR`, or `cd -N` for the Nth directory `dirs -v`
+// lists, which comes off the stack. With autopushd the directory it leaves
+// goes on the stack
+fn cd(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let Some(arg) = words.first() else {
+        eprintln!("{}: cd: expected an argument", shell.location());
+        shell.last_status = 1;
+        return None;
+    };
+    let previous = env::current_dir();
+    let entry = (arg.to_str())
+        .and_then(|arg| arg.strip_prefix('-'))
+        .and_then(|n| n.parse::<usize>().ok());
+    match entry {
+        Some(n) => {
+            let Some(dir) = n.checked_sub(1).and_then(|i| shell.dir_stack.get(i)) else {
+                let location = shell.location();
+                eprintln!(
+                    "{}: cd: -{}: no such entry in the directory stack",
+                    location, n
+                );
+                shell.last_status = 1;
+                return None;
+            };
+            let dir = dir.clone();
+            if !change_directory(shell, "cd", &dir) {
+                return None;
+            }
+            shell.dir_stack.remove(n - 1);
+        }
+        None => {
+            if !change_directory(shell, "cd", Path::new(arg)) {
+                return None;
+            }
+        }
+    }
+    if shell.options.autopushd {
+        if let Ok(previous) = previous {
+            push_directory(shell, previous);
+        }
+    }
+    None
+}
+
+// Go to `path` for `command`, noting it down in $PWD and for j. False with
+// the error printed when it can't
+fn change_directory(shell: &mut Shell, command: &str, path: &Path) -> bool {
+    if shell.restricted {
+        shell.report(ShellError::restricted(command));
+        return false;
+    }
+    shell.last_status = 1;
+    let mut changed = env::set_current_dir(path);
+    // a directory that doesn't exist may only be mistyped, the one that was
+    // meant is shown before going there
+    if let Err(e) = &changed {
+        let missing = e.kind() == io::ErrorKind::NotFound;
+        let corrected =
+            (shell.interactive && shell.options.cdspell && !shell.options.posix && missing)
+                .then(|| suggest::correct_path(path))
+                .flatten();
+        if let Some(corrected) = corrected {
+            if env::set_current_dir(&corrected).is_ok() {
+                println!("{}", corrected.display());
+                changed = Ok(());
+            }
+        }
+    }
+    if let Err(e) = changed {
+        eprintln!(
+            "{}: {}: {}: {}",
+            shell.location(),
+            command,
+            path.display(),
+            error_message(&e)
+        );
+        return false;
+    }
+    if let Some(pwd) = env::var_os("PWD") {
+        env::set_var("OLDPWD", pwd);
+    }
+    if let Ok(cwd) = env::current_dir() {
+        // for j to jump back to, a directory it can't note down is only
+        // left out
+        if shell.interactive {
+            let _ = jump::record(&cwd);
+        }
+        env::set_var("PWD", cwd);
+    }
+    shell.last_status = 0;
+    true
+}
+
+// how deep the directory stack goes when $DIRSTACKSIZE doesn't say
+const DIRSTACKSIZE: usize = 20;
+
+// Put the directory that was left on top of the stack. It's only on it once,
+// the current directory not at all, and what's deeper than $DIRSTACKSIZE
+// falls off
+fn push_directory(shell: &mut Shell, dir: PathBuf) {
+    let cwd = env::current_dir().ok();
+    if cwd.as_ref() == Some(&dir) {
+        return;
+    }
+    (shell.dir_stack).retain(|entry| *entry != dir && Some(entry) != cwd.as_ref());
+    shell.dir_stack.insert(0, dir);
+    let size = env::var("DIRSTACKSIZE")
+        .ok()
+        .and_then(|size| size.trim().parse::<usize>().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or(DIRSTACKSIZE);
+    shell.dir_stack.truncate(size);
+}
+
+// The pushd builtin: `pushd DIR` goes there and puts the directory it left on
+// the stack, `pushd` swaps the current directory with the top of the stack
+fn pushd(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let Ok(previous) = env::current_dir() else {
+        eprintln!("{}: pushd: the current directory is gone", shell.location());
+        shell.last_status = 1;
+        return None;
+    };
+    match words {
+        [] => {
+            let Some(top) = shell.dir_stack.first().cloned() else {
+                eprintln!("{}: pushd: no other directory", shell.location());
+                shell.last_status = 1;
+                return None;
+            };
+            if !change_directory(shell, "pushd", &top) {
+                return None;
+            }
+            shell.dir_stack[0] = previous;
+        }
+        [dir] => {
+            if !change_directory(shell, "pushd", Path::new(dir)) {
+                return None;
+            }
+            push_directory(shell, previous);
+        }
+        _ => {
+            eprintln!("{}: pushd: usage: pushd [DIR]", shell.location());
+            shell.last_status = 2;
+            return None;
+        }
+    }
+    print_dirs(shell, false);
+    None
+}
+
+// The popd builtin: `popd` goes to the directory on top of the stack and
+// takes it off, `popd +N` only takes off the Nth one `dirs -v` lists
+fn popd(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let args = text(words);
+    let entry = match args.as_slice() {
+        [] => 0,
+        [n] if n
+            .strip_prefix('+')
+            .is_some_and(|n| n.parse::<usize>().is_ok()) =>
+        {
+            n[1..].parse().unwrap()
+        }
+        _ => {
+            eprintln!("{}: popd: usage: popd [+N]", shell.location());
+            shell.last_status = 2;
+            return None;
+        }
+    };
+    if shell.dir_stack.is_empty() {
+        eprintln!("{}: popd: directory stack empty", shell.location());
+        shell.last_status = 1;
+        return None;
+    }
+    if entry == 0 {
+        let top = shell.dir_stack[0].clone();
+        if !change_directory(shell, "popd", &top) {
+            return None;
+        }
+        shell.dir_stack.remove(0);
+    } else if entry <= shell.dir_stack.len() {
+        shell.dir_stack.remove(entry - 1);
+        shell.last_status = 0;
+    } else {
+        let location = shell.location();
+        eprintln!(
+            "{}: popd: +{}: no such entry in the directory stack",
+            location, entry
+        );
+        shell.last_status = 1;
+        return None;
+    }
+    print_dirs(shell, false);
+    None
+}
+
+// The dirs builtin: `dirs` shows the current directory and then the stack on
+// a line, `dirs -v` a line each numbered for cd -N and popd +N, and `dirs -c`
+// empties the stack
+fn dirs(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    match text(words).as_slice() {
+        [] => print_dirs(shell, false),
+        [flag] if flag == "-v" => print_dirs(shell, true),
+        [flag] if flag == "-c" => shell.dir_stack.clear(),
+        _ => {
+            eprintln!("{}: dirs: usage: dirs [-v|-c]", shell.location());
+            shell.last_status = 2;
+            return None;
+        }
+    }
+    shell.last_status = 0;
+    None
+}
+
+fn print_dirs(shell: &Shell, numbered: bool) {
+    let cwd = env::current_dir().unwrap_or_default();
+    let shown = (std::iter::once(&cwd).chain(&shell.dir_stack))
+        .map(|dir| prompt::abbreviated(dir.to_string_lossy().into_owned(), &shell.named_dirs));
+    if numbered {
+        for (i, dir) in shown.enumerate() {
+            println!("{:>2}  {}", i, dir);
+        }
+    } else {
+        println!("{}", shown.collect::<Vec<_>>().join(" "));
+    }
+}
+
+// The j builtin: `j WORD...` goes to the directory cd went to most often and
+// most lately that has the words in its path, `j -l [WORD...]` lists those
+// directories best first and `j` all of them
+fn j(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let args = text(words);
+    let (list, patterns) = match args.split_first() {
+        Some((flag, patterns)) if flag == "-l" => (true, patterns),
+        _ => (args.is_empty(), args.as_slice()),
+    };
+    let ranked = match jump::ranked(patterns) {
+        Ok(ranked) => ranked,
+        Err(e) => {
+            eprintln!("{}: j: {}", shell.location(), error_message(&e));
+            shell.last_status = 1;
+            return None;
+        }
+    };
+    if list {
+        for (frecency, path) in &ranked {
+            println!("{:<10.1}{}", frecency, path.display());
+        }
+        shell.last_status = 0;
+        return None;
+    }
+    match ranked.into_iter().next() {
+        Some((_, path)) => cd(shell, &[path.into_os_string()]),
+        None => {
+            let location = shell.location();
+            eprintln!(
+                "{}: j: {}: no directory matches",
+                location,
+                patterns.join(" ")
+            );
+            shell.last_status = 1;
+            None
+        }
+    }
+}
+
+fn source(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    source_builtin(shell, "source", words)
+}
+
+fn dot(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    source_builtin(shell, ".", words)
+}
+
+// Run the commands in a file in the shell, `command` is the name it was run as
+fn source_builtin(shell: &mut Shell, command: &str, words: &[OsString]) -> Option<i32> {
+    let Some(path) = words.first().map(Path::new) else {
+        eprintln!(
+            "{}: {}: filename argument required",
+            shell.location(),
+            command
+        );
+        shell.last_status = 2;
+        return None;
+    };
+    match shell.source(path) {
+        Ok(Some(status)) => return Some(status),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!(
+                "{}: {}: {}: {}",
+                shell.location(),
+                command,
+                path.display(),
+                error_message(&e)
+            );
+            shell.last_status = 1;
+        }
+    }
+    None
+}
+
+// The help builtin: one line on each builtin, or on the ones named
+fn help(shell: &mut Shell, words: &[OsString]) -> Option<i32> {
+    let names = text(words);
+    let mut out = stdout().lock();
+    shell.last_status = 0;
+    if names.is_empty() {
+        for name in shell.builtins.names() {
+            let builtin = shell.builtins.get(name)?;
+            let _ = writeln!(out, "{}", builtin.help());
+        }
+        return None;
+    }
+    for name in &names {
+        match shell.builtins.get(name) {
+            Some(builtin) => {
+                let _ = writeln!(out, "{}", builtin.help());
+            }
+            None => {
+                eprintln!("{}: help: {}: no such builtin", shell.location(), name);
+                shell.last_status = 1;
+            }
+        }
+    }
+    None
+}
+
+impl Shell {
+    // Run a builtin in the shell itself. Returns the exit status if the shell
+    // should exit
+    pub fn run_builtin(&mut self, command: &str, words: &[OsString]) -> Option<i32> {
+        // an empty command, a line of only redirections, does nothing
+        let builtin = self.builtins.get(command)?;
+        // unbuffered, what the builtin doesn't read is left for what comes
+        // after it. The shell's fd 0 stays open
+        let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(libc::STDIN_FILENO) });
+        let mut io = Io {
+            stdin: &mut *stdin,
+            stdout: &mut io::stdout(),
+            stderr: &mut io::stderr(),
+        };
+        match builtin.execute(self, words, &mut io) {
+            ExitStatus::Done(status) => {
+                self.last_status = status;
+                None
+            }
+            ExitStatus::Exit(status) => Some(status),
+        }
+    }
+
+    // Add a builtin, it's run in place of the command or builtin by that name
+    pub fn register_builtin(&mut self, builtin: impl Builtin + 'static) {
+        self.builtins.add(Rc::new(builtin));
+    }
+}
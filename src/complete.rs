@@ -0,0 +1,461 @@
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{compspec::Spec, editor::Completion, input::Context, jump, options::Options, parser};
+
+// Tab completion of the word in front of the cursor
+
+// quoting still open at the end of the word being completed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Quote {
+    None,
+    Single,
+    Double,
+}
+
+// the word being completed, as far as it's typed
+struct Word {
+    // what it stands for: quotes removed, ~/ and $VAR expanded
+    value: String,
+    quote: Quote,
+    // the words of its command before it, redirection targets left out
+    args: Vec<String>,
+    // whether it's the target of a redirection
+    target: bool,
+}
+
+impl Word {
+    // whether it's where the command name of a command goes
+    fn is_command(&self) -> bool {
+        self.args.is_empty() && !self.target
+    }
+}
+
+// which files in a directory are matches
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Files {
+    All,
+    Executables,
+    Directories,
+}
+
+// Length of the part of the word the matches were looked up for, it's
+// already typed, and the matches
+type Matches = (usize, Vec<String>);
+
+// Completes the arguments of one command, gets the word and the context.
+// None when the command has nothing special there, file names are completed
+// then
+type Hook = fn(&Word, &mut Context) -> Option<Matches>;
+
+// the commands whose arguments are completed by something else than file names
+const HOOKS: &[(&str, Hook)] = &[
+    ("cd", |word, _| Some(files(&word.value, Files::Directories))),
+    ("j", jump_targets),
+    ("pushd", |word, _| {
+        Some(files(&word.value, Files::Directories))
+    }),
+    ("fg", job_specs),
+    ("bg", job_specs),
+    ("jobs", job_specs),
+    ("kill", signal_names),
+    ("set", option_names),
+    ("exec", exec_command),
+];
+
+// names of the signals for kill. Their numbers differ between systems, they
+// are listed in the order of the numbers of the one ish runs on
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    #[cfg(not(target_os = "linux"))]
+    ("EMT", libc::SIGEMT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    #[cfg(target_os = "linux")]
+    ("STKFLT", libc::SIGSTKFLT),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    #[cfg(target_os = "linux")]
+    ("PWR", libc::SIGPWR),
+    #[cfg(not(target_os = "linux"))]
+    ("INFO", libc::SIGINFO),
+    ("SYS", libc::SIGSYS),
+];
+
+// Expand the variable in `line` at `i`, which is a $. Returns the index of the
+// last character of it
+fn variable(line: &[char], i: usize, value: &mut String) -> usize {
+    let (name, end): (String, usize) = if line.get(i + 1) == Some(&'{') {
+        match line[i + 2..].iter().position(|&c| c == '}') {
+            Some(len) => (line[i + 2..i + 2 + len].iter().collect(), i + 2 + len),
+            None => {
+                value.push('$');
+                return i;
+            }
+        }
+    } else {
+        let len = line[i + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .count();
+        if len == 0 {
+            value.push('$');
+            return i;
+        }
+        (line[i + 1..i + 1 + len].iter().collect(), i + len)
+    };
+    if let Some(text) = env::var_os(name) {
+        value.push_str(&text.to_string_lossy());
+    }
+    end
+}
+
+// Find the word at the end of `line`, words end and are quoted where the
+// parser says so
+fn current_word(line: &[char]) -> Word {
+    let mut word = Word {
+        value: String::new(),
+        quote: Quote::None,
+        args: Vec::new(),
+        target: false,
+    };
+    let mut start = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let c = line[i];
+        match word.quote {
+            Quote::Single if c == '\'' => word.quote = Quote::None,
+            Quote::Single => word.value.push(c),
+            Quote::Double => match c {
+                '"' => word.quote = Quote::None,
+                '\\' if matches!(line.get(i + 1), Some('$' | '"' | '\\')) => {
+                    i += 1;
+                    word.value.push(line[i]);
+                }
+                '$' => i = variable(line, i, &mut word.value),
+                _ => word.value.push(c),
+            },
+            Quote::None => match c {
+                c if c.is_whitespace() || parser::is_operator(c) => {
+                    let typed = &line[start..i];
+                    // digits right before < or > are the fd it redirects
+                    let fd = matches!(c, '<' | '>') && typed.iter().all(char::is_ascii_digit);
+                    let value = std::mem::take(&mut word.value);
+                    if !typed.is_empty() && !fd {
+                        if word.target {
+                            word.target = false;
+                        } else {
+                            word.args.push(value);
+                        }
+                    }
+                    match c {
+                        '<' | '>' => word.target = true,
+                        // >& and <& are redirections too
+                        '&' if i > 0 && matches!(line[i - 1], '<' | '>') => {}
+                        '|' | '&' => {
+                            word.args.clear();
+                            word.target = false;
+                        }
+                        _ => {}
+                    }
+                    start = i + 1;
+                }
+                '\'' => word.quote = Quote::Single,
+                '"' => word.quote = Quote::Double,
+                '\\' => {
+                    if let Some(&c) = line.get(i + 1) {
+                        i += 1;
+                        word.value.push(c);
+                    }
+                }
+                '~' if i == start && line.get(i + 1) == Some(&'/') => {
+                    word.value
+                        .push_str(&env::var_os("HOME").unwrap_or_default().to_string_lossy());
+                }
+                '$' => i = variable(line, i, &mut word.value),
+                _ => word.value.push(c),
+            },
+        }
+        i += 1;
+    }
+    word
+}
+
+// Quote `text` to go at the end of a word quoted like `quote`
+fn escape(text: &str, quote: Quote) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match quote {
+            Quote::None if c.is_whitespace() || parser::is_operator(c) => escaped.push('\\'),
+            Quote::None if matches!(c, '\'' | '"' | '\\' | '$' | '#') => escaped.push('\\'),
+            Quote::Double if matches!(c, '"' | '\\' | '$') => escaped.push('\\'),
+            // close the quotes for an escaped ' and open them again
+            Quote::Single if c == '\'' => {
+                escaped.push_str("'\\''");
+                continue;
+            }
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// The names in the directory of `path` that start with its last component,
+// sorted. Directories get a / at the end. Names starting with a dot are only
+// there when the last component does too
+fn files(path: &str, filter: Files) -> Matches {
+    let (dir, base) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let dir = Path::new(if dir.is_empty() { "." } else { dir });
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (base.len(), Vec::new());
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            if !name.starts_with(base) || (name.starts_with('.') && !base.starts_with('.')) {
+                return None;
+            }
+            // metadata follows symlinks, a link to a directory is completed
+            // like one
+            let meta = fs::metadata(dir.join(&name)).ok();
+            match meta {
+                Some(meta) if meta.is_dir() => Some(name + "/"),
+                _ if filter == Files::Directories => None,
+                Some(meta)
+                    if filter == Files::Executables && meta.permissions().mode() & 0o111 == 0 =>
+                {
+                    None
+                }
+                _ => Some(name),
+            }
+        })
+        .collect();
+    names.sort();
+    (base.len(), names)
+}
+
+// The builtins and the commands on $PATH that start with `prefix`, sorted and
+// each only once
+fn commands(prefix: &str, context: &mut Context) -> Matches {
+    let mut names: Vec<String> = context
+        .builtins
+        .names()
+        .chain(context.path_cache.names())
+        .filter(|name| name.starts_with(prefix))
+        .map(String::from)
+        .collect();
+    names.sort();
+    names.dedup();
+    (prefix.len(), names)
+}
+
+// the names in `names` that start with `prefix`, each with it in front
+fn prefixed(prefix: &str, names: impl Iterator<Item = String>, word: &str) -> Matches {
+    let names = names
+        .map(|name| format!("{}{}", prefix, name))
+        .filter(|name| name.starts_with(word))
+        .collect();
+    (word.len(), names)
+}
+
+// %n for each job
+fn job_specs(word: &Word, context: &mut Context) -> Option<Matches> {
+    if !word.value.starts_with('%') {
+        return None;
+    }
+    let numbers = context.jobs.iter().map(usize::to_string);
+    Some(prefixed("%", numbers, &word.value))
+}
+
+// the names of the directories j can go to that start with the word, best
+// ranked first
+fn jump_targets(word: &Word, _: &mut Context) -> Option<Matches> {
+    let ranked = jump::ranked(&[]).ok()?;
+    let mut names: Vec<String> = Vec::new();
+    for (_, path) in ranked {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy().into_owned();
+        if name.starts_with(&word.value) && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Some((word.value.len(), names))
+}
+
+// kill -NAME
+fn signal_names(word: &Word, _: &mut Context) -> Option<Matches> {
+    if !word.value.starts_with('-') {
+        return None;
+    }
+    let mut signals = SIGNALS.to_vec();
+    signals.sort_by_key(|&(_, number)| number);
+    let names = signals.into_iter().map(|(name, _)| name.to_string());
+    Some(prefixed("-", names, &word.value))
+}
+
+// the option after set -o or set +o
+fn option_names(word: &Word, _: &mut Context) -> Option<Matches> {
+    let last = word.args.last().map(String::as_str);
+    if word.args.len() < 2 || !matches!(last, Some("-o" | "+o")) {
+        return None;
+    }
+    let names = Options::NAMES.iter().map(|name| name.to_string());
+    Some(prefixed("", names, &word.value))
+}
+
+// the command exec runs, after -a and its name
+fn exec_command(word: &Word, context: &mut Context) -> Option<Matches> {
+    let options = match word.args.get(1).map(String::as_str) {
+        Some("-a") => 3,
+        _ => 1,
+    };
+    if word.args.len() != options || word.value.starts_with('-') {
+        return None;
+    }
+    Some(commands(&word.value, context))
+}
+
+// What the complete builtin set up for the command, None when it set up
+// nothing for it
+fn registered(word: &Word, line: &[char], context: &mut Context) -> Option<Matches> {
+    let name = word.args.first()?;
+    let mut names = match context.completions.get(name)? {
+        Spec::Words(words) => words.clone(),
+        Spec::Command(command) => completer_output(command, word, line),
+    };
+    names.retain(|name| name.starts_with(&word.value));
+    names.sort();
+    names.dedup();
+    Some((word.value.len(), names))
+}
+
+// Run a command given with `complete -C` like bash does: with the command
+// name, the word and the one before it as arguments, and the line in
+// $COMP_LINE. Its words are in $COMP_WORDS a line each, $COMP_CWORD is the
+// number of the one being completed. The lines it prints are the matches
+fn completer_output(command: &str, word: &Word, line: &[char]) -> Vec<String> {
+    let Ok(ish) = env::current_exe() else {
+        return Vec::new();
+    };
+    let words: Vec<&str> = word.args.iter().map(String::as_str).collect();
+    let output = Command::new(ish)
+        .arg("-c")
+        .arg(format!("{} \"$0\" \"$1\" \"$2\"", command))
+        .args([words[0], &word.value, words[words.len() - 1]])
+        .env("COMP_LINE", line.iter().collect::<String>())
+        .env("COMP_POINT", line.len().to_string())
+        .env(
+            "COMP_WORDS",
+            [&words[..], &[&word.value]].concat().join("\n"),
+        )
+        .env("COMP_CWORD", words.len().to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// the longest start all of `names` share
+fn common_prefix(names: &[String]) -> &str {
+    let first = &names[0];
+    let mut len = first.len();
+    for name in &names[1..] {
+        len = first
+            .char_indices()
+            .zip(name.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, c), _)| i + c.len_utf8())
+            .min(len);
+    }
+    &first[..len]
+}
+
+// Complete the word at the end of `line`, which is the line up to the cursor.
+// A command name is looked for on $PATH unless it's a path, arguments are
+// what the complete builtin set up for the command, or file names unless
+// there's a hook for it. None when nothing matches
+pub fn complete(line: &[char], context: &mut Context) -> Option<Completion> {
+    let word = current_word(line);
+    let hook = HOOKS
+        .iter()
+        .find(|(name, _)| word.args.first().is_some_and(|first| first == name))
+        .filter(|_| !word.target);
+    let registered = if word.target {
+        None
+    } else {
+        registered(&word, line, context)
+    };
+    let matches = registered.or_else(|| hook.and_then(|(_, hook)| hook(&word, context)));
+    let (typed, names) = match matches {
+        Some(matches) => matches,
+        None if word.is_command() && !word.value.contains('/') => commands(&word.value, context),
+        None if word.is_command() => files(&word.value, Files::Executables),
+        None => files(&word.value, Files::All),
+    };
+    // a finished word is closed and followed by a space, a directory can go on
+    let finished = |name: &String| {
+        let mut insert = escape(&name[typed..], word.quote);
+        if !name.ends_with('/') {
+            match word.quote {
+                Quote::None => {}
+                Quote::Single => insert.push('\''),
+                Quote::Double => insert.push('"'),
+            }
+            insert.push(' ');
+        }
+        insert
+    };
+    match names.as_slice() {
+        [] => None,
+        [name] => Some(Completion {
+            insert: finished(name),
+            matches: Vec::new(),
+            choices: Vec::new(),
+        }),
+        _ => Some(Completion {
+            insert: escape(&common_prefix(&names)[typed..], word.quote),
+            choices: names.iter().map(finished).collect(),
+            matches: names,
+        }),
+    }
+}
@@ -0,0 +1,48 @@
+// How the arguments of commands are completed, set with the complete builtin
+
+use std::collections::BTreeMap;
+
+use crate::parser::quote;
+
+// what a command's arguments are completed with
+pub enum Spec {
+    // `complete -W WORDS`: the words of a list
+    Words(Vec<String>),
+    // `complete -C COMMAND`: the lines a command prints, run with the command
+    // name, the word being completed and the one before it as arguments
+    Command(String),
+}
+
+// The completions of commands by name, in the order of the names
+#[derive(Default)]
+pub struct Specs {
+    table: BTreeMap<String, Spec>,
+}
+
+impl Specs {
+    pub fn set(&mut self, name: &str, spec: Spec) {
+        self.table.insert(name.to_string(), spec);
+    }
+
+    // false when there's none for `name`
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.table.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Spec> {
+        self.table.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Spec)> {
+        self.table.iter().map(|(name, spec)| (name.as_str(), spec))
+    }
+}
+
+// The complete command that registers `spec` for `name`, with what it's given
+// quoted to be read back in
+pub fn show(name: &str, spec: &Spec) -> String {
+    match spec {
+        Spec::Words(words) => format!("complete -W {} {}", quote(&words.join(" ")), name),
+        Spec::Command(command) => format!("complete -C {} {}", quote(command), name),
+    }
+}
@@ -0,0 +1,1568 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, Write},
+    ops::Range,
+    os::unix::fs::PermissionsExt,
+    sync::atomic::Ordering,
+};
+
+use libc::{c_int, STDIN_FILENO};
+
+use crate::{
+    complete,
+    highlight::{self, Style},
+    history::History,
+    input::{Context, ReadLine, INTERRUPTED, RESIZED},
+    keymap::{Action, Binding, Keymap, Keymaps},
+    parser, prompt,
+    sys::{self, Termios},
+    unicode,
+};
+
+// What Tab does to the line
+pub struct Completion {
+    // text to insert at the cursor, quoted so the parser reads it back as it's
+    // meant
+    pub insert: String,
+    // all the matches when there's more than one, to show when nothing could
+    // be inserted
+    pub matches: Vec<String>,
+    // what's inserted for each of the matches when it's picked from them,
+    // like when it's the only one
+    pub choices: Vec<String>,
+}
+
+// a key press, decoded from the bytes the terminal sends for it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Key {
+    Char(char),
+    // Ctrl and a letter, given as the lowercase letter
+    Control(u8),
+    // Alt and a key, or Escape before it
+    Alt(u8),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Escape,
+    // Shift-Tab
+    BackTab,
+    // the terminal sends what's pasted between these in bracketed paste mode
+    PasteStart,
+    // not a key, the terminal changed its size while waiting for one
+    Resize,
+    // anything else the terminal sent, it's ignored
+    Unknown,
+}
+
+// a suggestion is only looked for in this many of the newest history
+// entries, so it keeps up with typing however long the history is
+const SUGGESTION_SEARCH_LIMIT: usize = 10_000;
+
+// more completions than this are only listed after asking
+const LIST_WITHOUT_ASKING: usize = 100;
+
+// SGR parameters the completion picked in the menu is shown with
+const MENU_SELECTED: &str = "7";
+
+// texts kept in the kill ring
+const KILL_RING_SIZE: usize = 10;
+
+// how long to wait for the rest of an escape sequence before taking the
+// escape as a key of its own
+const ESCAPE_TIMEOUT_MS: c_int = 50;
+
+// how long a key that's bound on its own waits for the rest of a longer
+// bound sequence it starts, like readline's keyseq-timeout
+const KEY_SEQUENCE_TIMEOUT_MS: c_int = 500;
+
+// The terminal in raw mode for as long as this lives. Dropping it puts the
+// modes back, also when a panic unwinds through the editor, and the cursor
+// shape when vi mode changed it
+struct RawMode {
+    original: Termios,
+    cursor_shape: bool,
+}
+
+impl RawMode {
+    // from `modes`, or from the terminal's modes when the shell doesn't know
+    // them
+    fn enable(modes: Option<Termios>, cursor_shape: bool) -> io::Result<RawMode> {
+        let original = match modes {
+            Some(modes) => modes,
+            None => Termios::current(STDIN_FILENO)?,
+        };
+        original.raw().apply(STDIN_FILENO)?;
+        // pasted text comes marked, so newlines in it don't run anything.
+        // Terminals that don't know the mode ignore this
+        print!("\x1b[?2004h");
+        Ok(RawMode {
+            original,
+            cursor_shape,
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?2004l");
+        if self.cursor_shape {
+            print!("\x1b[0 q");
+        }
+        let _ = io::stdout().flush();
+        let _ = self.original.apply(STDIN_FILENO);
+    }
+}
+
+// Read a byte from the terminal, None at the end of the input. A signal
+// other than SIGINT doesn't interrupt the editor
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = 0u8;
+    loop {
+        let n = unsafe { libc::read(STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        if n > 0 {
+            return Ok(Some(byte));
+        }
+        if n == 0 {
+            return Ok(None);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted || INTERRUPTED.load(Ordering::SeqCst) {
+            return Err(err);
+        }
+    }
+}
+
+// whether more input arrives within `timeout_ms`
+fn input_pending(timeout_ms: c_int) -> bool {
+    let mut fd = libc::pollfd {
+        fd: STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, timeout_ms) > 0 }
+}
+
+// Wait until there's input, returns false when the terminal was resized
+// first. Ctrl-C makes it fail like a read, also when it came while the line
+// was being drawn
+fn wait_for_input() -> io::Result<bool> {
+    loop {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return Err(io::Error::from(io::ErrorKind::Interrupted));
+        }
+        if RESIZED.swap(false, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let mut fd = libc::pollfd {
+            fd: STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, -1) } >= 0 {
+            return Ok(true);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted || INTERRUPTED.load(Ordering::SeqCst) {
+            return Err(err);
+        }
+    }
+}
+
+// Read the next key, with the bytes the terminal sent for it added to `bytes`
+fn read_key_bytes(bytes: &mut Vec<u8>) -> io::Result<Option<Key>> {
+    if !wait_for_input()? {
+        return Ok(Some(Key::Resize));
+    }
+    let Some(byte) = read_byte()? else {
+        return Ok(None);
+    };
+    bytes.push(byte);
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape(bytes)?,
+        1..=26 => Key::Control(byte - 1 + b'a'),
+        0..=0x7f => Key::Char(byte as char),
+        _ => {
+            // the rest of a UTF-8 character
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return Ok(Some(Key::Unknown)),
+            };
+            let start = bytes.len() - 1;
+            for _ in 1..len {
+                match read_byte()? {
+                    Some(byte) => bytes.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            match std::str::from_utf8(&bytes[start..]) {
+                Ok(text) => Key::Char(text.chars().next().unwrap()),
+                Err(_) => Key::Unknown,
+            }
+        }
+    };
+    Ok(Some(key))
+}
+
+// Read what's pasted, up to the sequence that ends the paste. Line ends
+// become newlines and other control characters except tabs are dropped
+fn read_paste() -> io::Result<String> {
+    const END: &[u8] = b"\x1b[201~";
+    let mut bytes = Vec::new();
+    while !bytes.ends_with(END) {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    if bytes.ends_with(END) {
+        bytes.truncate(bytes.len() - END.len());
+    }
+    let text = String::from_utf8_lossy(&bytes).replace("\r\n", "\n");
+    Ok(text
+        .chars()
+        .map(|c| if c == '\r' { '\n' } else { c })
+        .filter(|&c| !c.is_control() || c == '\n' || c == '\t')
+        .collect())
+}
+
+// Decode what follows an escape: CSI sequences like ESC [ D or ESC [ 3 ~ and
+// SS3 ones like ESC O H, the keys that send them differ between terminals
+fn read_escape(bytes: &mut Vec<u8>) -> io::Result<Key> {
+    if !input_pending(ESCAPE_TIMEOUT_MS) {
+        return Ok(Key::Escape);
+    }
+    let Some(kind) = read_byte()? else {
+        return Ok(Key::Escape);
+    };
+    bytes.push(kind);
+    if kind != b'[' && kind != b'O' {
+        return Ok(match kind {
+            0x21..=0x7e => Key::Alt(kind.to_ascii_lowercase()),
+            b'\r' | b'\n' => Key::Alt(b'\r'),
+            _ => Key::Unknown,
+        });
+    }
+    let mut params = Vec::new();
+    let last = loop {
+        let byte = read_byte()?;
+        bytes.extend(byte);
+        match byte {
+            Some(byte @ 0x40..=0x7e) => break byte,
+            Some(byte) => params.push(byte),
+            None => return Ok(Key::Unknown),
+        }
+    };
+    Ok(match (last, params.as_slice()) {
+        // Ctrl or Alt with Left and Right move by words
+        (b'C', b"1;3" | b"1;5") => Key::Alt(b'f'),
+        (b'D', b"1;3" | b"1;5") => Key::Alt(b'b'),
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) => Key::Home,
+        (b'F', _) => Key::End,
+        (b'Z', _) => Key::BackTab,
+        (b'~', b"1" | b"7") => Key::Home,
+        (b'~', b"4" | b"8") => Key::End,
+        (b'~', b"3") => Key::Delete,
+        (b'~', b"200") => Key::PasteStart,
+        _ => Key::Unknown,
+    })
+}
+
+// The sequence `key` is bound with, the same one for all the sequences
+// terminals send for it
+fn key_bytes(key: Key) -> Option<Vec<u8>> {
+    let bytes: &[u8] = match key {
+        Key::Char(c) => return Some(c.to_string().into_bytes()),
+        Key::Control(c) => return Some(vec![c - b'a' + 1]),
+        Key::Alt(c) => return Some(vec![0x1b, c]),
+        Key::Enter => b"\r",
+        Key::Backspace => b"\x7f",
+        Key::Delete => b"\x1b[3~",
+        Key::Left => b"\x1b[D",
+        Key::Right => b"\x1b[C",
+        Key::Up => b"\x1b[A",
+        Key::Down => b"\x1b[B",
+        Key::Home => b"\x1b[H",
+        Key::End => b"\x1b[F",
+        Key::Escape => b"\x1b",
+        Key::BackTab => b"\x1b[Z",
+        Key::PasteStart | Key::Resize | Key::Unknown => return None,
+    };
+    Some(bytes.to_vec())
+}
+
+// What a key is bound to, by the bytes that were read for it or else the
+// sequence it's bound with. Characters that aren't bound are typed, unless
+// it's the keymap of the vi commands
+fn lookup(keymap: &Keymap, key: Key, bytes: &[u8]) -> Option<Binding> {
+    let binding = keymap.get(bytes).or_else(|| keymap.get(&key_bytes(key)?));
+    match (binding, key) {
+        (Some(binding), _) => Some(binding.clone()),
+        (None, Key::Char(_)) if keymap.inserts => Some(Binding::Action(Action::SelfInsert)),
+        (None, _) => None,
+    }
+}
+
+fn bell() -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
+}
+
+// Line editor for interactive shells reading from a terminal: the cursor can
+// be moved around the line with the arrow keys, Home and End (or Ctrl-B,
+// Ctrl-F, Ctrl-A and Ctrl-E) and text inserted and deleted anywhere in it,
+// with the emacs keys of readline for moving by words, killing parts of it
+// and yanking them back. Up and Down go through the history, Ctrl-R searches
+// it and Tab completes command and file names. A command that isn't complete
+// when Enter is pressed goes on on the next line of the same buffer, Up and
+// Down move between its lines. With set -o vi the keys work like in vi
+// instead, Escape goes from inserting text to the vi commands. What the keys
+// do can be changed with bindkey. The terminal is only in raw mode while a
+// line is being read
+#[derive(Default)]
+pub struct Editor {
+    prompt: String,
+    // shown in front of the lines after the first of a command
+    continuation: String,
+    // shown at the right end of the row the prompt ends in
+    right_prompt: String,
+    // rest of the history entry suggested for the line, shown dimmed after
+    // it until it's accepted
+    suggestion: String,
+    // how each character of the line is colored, nothing when it isn't
+    styles: Vec<Style>,
+    buffer: Vec<char>,
+    // position in buffer the cursor is in front of
+    cursor: usize,
+    // number of the history entry that was recalled last, and the line as it
+    // was before going through the history
+    browsing: Option<usize>,
+    saved: Vec<char>,
+    // what was searched for last, Ctrl-R with nothing typed searches it again
+    last_search: String,
+    // when the last key was Alt-.: the history entry its word came from and
+    // how many characters were inserted
+    last_argument: Option<(usize, usize)>,
+    // text taken out of the line with the kill keys, the newest last, and
+    // whether the last key was one of them
+    kill_ring: Vec<String>,
+    killed: bool,
+    // when the last key was Ctrl-Y or Alt-Y: the kill ring entry inserted and
+    // its length in characters
+    yanked: Option<(usize, usize)>,
+    // row of the terminal the cursor is in, counted from the one the line
+    // starts in
+    cursor_row: usize,
+    // rows drawn under the line, the completion menu
+    below: Vec<String>,
+    // keys read while looking for a longer bound sequence that weren't part
+    // of one, with their bytes
+    pending: VecDeque<(Key, Vec<u8>)>,
+    // the keys work like in vi, and whether they're the vi commands instead
+    // of inserting text
+    vi: bool,
+    vi_command: bool,
+}
+
+// Row and column the cursor is in after writing `text` from the start of a
+// row. The column is `columns` when the row is full and the cursor waits at
+// its end for the next character. A wide character that doesn't fit at the
+// end of a row goes to the next one, like terminals do it
+fn position(text: impl Iterator<Item = char>, columns: usize) -> (usize, usize) {
+    let (mut row, mut column) = (0, 0);
+    for (c, width) in unicode::widths(text) {
+        if c == '\n' {
+            row += 1;
+            column = 0;
+            continue;
+        }
+        if column + width > columns {
+            row += 1;
+            column = 0;
+        }
+        column += width;
+    }
+    (row, column)
+}
+
+// characters in the words Alt-F, Alt-B and Alt-D move over, so they also stop
+// at the parts of paths and at the dashes of options
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || unicode::is_extending(c)
+}
+
+// width of the columns completions are shown in, with room between them
+fn column_width(matches: &[String]) -> usize {
+    let widest = matches.iter().map(|m| unicode::text_width(m)).max();
+    widest.unwrap_or(0) + 2
+}
+
+// What a character is for the vi word motions: whitespace, a character of a
+// word or punctuation. A word is a run of either of the last two
+fn vi_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if is_word_char(c) || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    // What takes room on the screen for `prompt` and `text`, the lines after
+    // the first of the text start with the secondary prompt
+    fn shown(&self, prompt: &str, text: impl Iterator<Item = char>) -> String {
+        let mut shown: String = prompt::visible(prompt).collect();
+        for c in text {
+            shown.push(c);
+            if c == '\n' {
+                shown.extend(prompt::visible(&self.continuation));
+            }
+        }
+        shown
+    }
+
+    // the next key with the bytes read for it, the ones read ahead first
+    fn next_key(&mut self) -> io::Result<Option<(Key, Vec<u8>)>> {
+        if let Some(key) = self.pending.pop_front() {
+            return Ok(Some(key));
+        }
+        let mut bytes = Vec::new();
+        Ok(read_key_bytes(&mut bytes)?.map(|key| (key, bytes)))
+    }
+
+    // the keymap of the editing mode the editor is in
+    fn keymap<'k>(&self, keymaps: &'k Keymaps) -> &'k Keymap {
+        match (self.vi, self.vi_command) {
+            (false, _) => &keymaps.emacs,
+            (true, false) => &keymaps.vi_insert,
+            (true, true) => &keymaps.vi_command,
+        }
+    }
+
+    // Read the next key and what it's bound to. A key that starts a longer
+    // bound sequence waits for the rest of it, only for a moment when the
+    // key does something on its own. Keys that turn out not to finish the
+    // sequence are handled after it
+    fn read_binding(&mut self, keymap: &Keymap) -> io::Result<Option<(Key, Option<Binding>)>> {
+        let Some(first) = self.next_key()? else {
+            return Ok(None);
+        };
+        // no bytes were read for it, they'd be the start of every sequence
+        if first.0 == Key::Resize {
+            return Ok(Some((Key::Resize, None)));
+        }
+        let mut sequence = first.1.clone();
+        let mut keys = vec![first];
+        while keymap.is_prefix(&sequence) {
+            let bound = match keys.as_slice() {
+                [(key, bytes)] => lookup(keymap, *key, bytes).is_some(),
+                _ => keymap.get(&sequence).is_some(),
+            };
+            // reading Escape already waited to tell it from the keys that
+            // send sequences starting with it
+            let escape = matches!(keys.as_slice(), [(Key::Escape, _)]);
+            if bound
+                && (escape || self.pending.is_empty() && !input_pending(KEY_SEQUENCE_TIMEOUT_MS))
+            {
+                break;
+            }
+            let Some(next) = self.next_key()? else {
+                break;
+            };
+            let other = matches!(next.0, Key::Resize | Key::PasteStart);
+            sequence.extend_from_slice(&next.1);
+            keys.push(next);
+            if other {
+                break;
+            }
+        }
+        // the longest sequence of the keys that's bound
+        for n in (2..=keys.len()).rev() {
+            let sequence: Vec<u8> = keys[..n]
+                .iter()
+                .flat_map(|(_, bytes)| bytes)
+                .copied()
+                .collect();
+            if let Some(binding) = keymap.get(&sequence).cloned() {
+                for key in keys.drain(n..).rev() {
+                    self.pending.push_front(key);
+                }
+                return Ok(Some((keys[n - 1].0, Some(binding))));
+            }
+        }
+        for key in keys.drain(1..).rev() {
+            self.pending.push_front(key);
+        }
+        let (key, bytes) = keys.remove(0);
+        // Escape typed quickly before a key comes as Alt and the key, in vi
+        // mode it's still the two of them when Alt and the key isn't bound
+        if let (Key::Alt(_), [0x1b, byte @ (b'\r' | 0x20..=0x7e)]) = (key, bytes.as_slice()) {
+            let escape = keymap.get(b"\x1b");
+            if self.vi && keymap.get(&bytes).is_none() && escape.is_some() {
+                let next = match byte {
+                    b'\r' => Key::Enter,
+                    byte => Key::Char(*byte as char),
+                };
+                self.pending.push_front((next, vec![*byte]));
+                return Ok(Some((Key::Escape, escape.cloned())));
+            }
+        }
+        Ok(Some((key, lookup(keymap, key, &bytes))))
+    }
+
+    // Draw `prompt` and `text` where the line starts, with everything that
+    // was after them cleared, and put the cursor in front of character `cursor` of the
+    // text. The line can wrap over several rows of the terminal, the row the
+    // cursor ends up in is kept to find the start again next time
+    fn draw(
+        &mut self,
+        prompt: &str,
+        text: &str,
+        styles: &[Style],
+        hint: &str,
+        cursor: usize,
+    ) -> io::Result<()> {
+        let columns = sys::terminal_columns().unwrap_or(80);
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        // cleared before drawing, at the end of a full row the terminal would
+        // clear the last character too
+        out.push_str("\r\x1b[J");
+        out.push_str(&prompt::printed(prompt));
+        let continuation = format!("\n{}", prompt::printed(&self.continuation));
+        out.push_str(&highlight::paint(text, styles, &continuation));
+        if !hint.is_empty() {
+            out.push_str(&format!("\x1b[2m{}\x1b[0m", hint));
+        }
+        let end = position(
+            self.shown(prompt, text.chars().chain(hint.chars())).chars(),
+            columns,
+        );
+        // the right prompt goes at the end of the row the prompt ends in, as
+        // long as the text leaves room for it, with a column free after it
+        let right: String = prompt::visible(&self.right_prompt).collect();
+        let right_width = unicode::text_width(&right);
+        let prompt_end = position(prompt::visible(prompt), columns);
+        if !right.is_empty()
+            && !right.contains('\n')
+            && end.0 == prompt_end.0
+            && end.1 + right_width + 1 < columns
+        {
+            out.push_str(&format!("\r\x1b[{}C", columns - right_width - 1));
+            out.push_str(&prompt::printed(&self.right_prompt));
+        }
+        // at the end of a row the terminal only moves to the next one when
+        // something more is written
+        if end.1 == columns {
+            out.push_str("\r\n");
+        }
+        let end_row = if end.1 == columns { end.0 + 1 } else { end.0 };
+        let mut bottom_row = end_row;
+        if !self.below.is_empty() {
+            if end.1 != columns {
+                out.push_str("\r\n");
+            }
+            out.push_str(&self.below.join("\r\n"));
+            bottom_row = end.0 + self.below.len();
+        }
+        let (row, column) = match position(
+            self.shown(prompt, text.chars().take(cursor)).chars(),
+            columns,
+        ) {
+            (row, column) if column == columns => (row + 1, 0),
+            position => position,
+        };
+        if bottom_row > row {
+            out.push_str(&format!("\x1b[{}A", bottom_row - row));
+        }
+        out.push('\r');
+        if column > 0 {
+            out.push_str(&format!("\x1b[{}C", column));
+        }
+        self.cursor_row = row;
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()
+    }
+
+    // Draw the prompt and the line again and put the cursor where it belongs
+    fn refresh(&mut self) -> io::Result<()> {
+        let prompt = std::mem::take(&mut self.prompt);
+        let text: String = self.buffer.iter().collect();
+        let hint = std::mem::take(&mut self.suggestion);
+        let styles = std::mem::take(&mut self.styles);
+        let drawn = self.draw(&prompt, &text, &styles, &hint, self.cursor);
+        self.suggestion = hint;
+        self.styles = styles;
+        self.prompt = prompt;
+        drawn
+    }
+
+    // Leave the line as it is and go to the start of the row after it
+    fn end_line(&mut self) -> io::Result<()> {
+        // the right prompt and the suggestion don't stay behind in the
+        // scrollback
+        self.right_prompt.clear();
+        self.suggestion.clear();
+        self.cursor = self.buffer.len();
+        self.refresh()?;
+        self.cursor_row = 0;
+        print!("\r\n");
+        io::stdout().flush()
+    }
+
+    // Suggest the newest history entry that starts with the line, when the
+    // cursor is at its end, and color the line. Returns whether either changed
+    fn update_hints(&mut self, context: &mut Context) -> bool {
+        let styles = if context.highlight {
+            let builtins = context.builtins;
+            let path_cache = &mut *context.path_cache;
+            highlight::highlight(&self.buffer, |name| {
+                if name.contains('/') {
+                    return fs::metadata(name).is_ok_and(|meta| {
+                        meta.is_file() && meta.permissions().mode() & 0o111 != 0
+                    });
+                }
+                builtins.contains(name) || path_cache.contains(name)
+            })
+        } else {
+            Vec::new()
+        };
+        let recolored = styles != self.styles;
+        self.styles = styles;
+        let mut suggestion = "";
+        let line: String = self.buffer.iter().collect();
+        if context.autosuggest && self.cursor == self.buffer.len() && !line.is_empty() {
+            let history = &*context.history;
+            suggestion = (history.numbers().rev())
+                .take(SUGGESTION_SEARCH_LIMIT)
+                .filter_map(|number| history.get(number)?.strip_prefix(&line))
+                .find(|rest| !rest.is_empty() && !rest.contains('\n'))
+                .unwrap_or_default();
+        }
+        if suggestion == self.suggestion {
+            return recolored;
+        }
+        self.suggestion = suggestion.to_string();
+        true
+    }
+
+    // Take the suggestion into the line, all of it or only up to the end of
+    // its first word
+    fn accept_suggestion(&mut self, word: bool) {
+        let suggestion: Vec<char> = std::mem::take(&mut self.suggestion).chars().collect();
+        let mut end = suggestion.len();
+        if word {
+            let start = suggestion.iter().position(|&c| is_word_char(c));
+            let start = start.unwrap_or(suggestion.len());
+            end = (suggestion[start..].iter())
+                .position(|&c| !is_word_char(c))
+                .map_or(suggestion.len(), |i| start + i);
+        }
+        self.buffer.extend(&suggestion[..end]);
+        self.cursor = self.buffer.len();
+    }
+
+    // Leave the line as it was typed when reading it fails, with the cursor
+    // at its end where Ctrl-C shows ^C. Takes the error to return it
+    fn abandon(&mut self, err: io::Error) -> io::Error {
+        self.right_prompt.clear();
+        self.suggestion.clear();
+        self.below.clear();
+        self.cursor = self.buffer.len();
+        let _ = self.refresh();
+        self.buffer.clear();
+        err
+    }
+
+    // Move the cursor to the line above the one it's in, or the one below
+    // when `up` isn't set, in the column nearest to the one it was in.
+    // Returns false when there is no such line
+    fn move_line(&mut self, up: bool) -> bool {
+        let line_start = |end: usize| {
+            (self.buffer[..end].iter())
+                .rposition(|&c| c == '\n')
+                .map_or(0, |i| i + 1)
+        };
+        let width = |range: std::ops::Range<usize>| -> usize {
+            unicode::widths(self.buffer[range].iter().copied())
+                .map(|(_, width)| width)
+                .sum()
+        };
+        let start = line_start(self.cursor);
+        let column = width(start..self.cursor);
+        let target = if up {
+            if start == 0 {
+                return false;
+            }
+            line_start(start - 1)
+        } else {
+            match self.buffer[self.cursor..].iter().position(|&c| c == '\n') {
+                Some(i) => self.cursor + i + 1,
+                None => return false,
+            }
+        };
+        let mut cursor = target;
+        while cursor < self.buffer.len() && self.buffer[cursor] != '\n' {
+            let next = unicode::next_boundary(&self.buffer, cursor);
+            if width(target..next) > column {
+                break;
+            }
+            cursor = next;
+        }
+        self.cursor = cursor;
+        true
+    }
+
+    // Recall the next older history entry, or the next newer one when `older`
+    // isn't set. Going past the newest entry brings back the line that was
+    // typed before. Entries that are the same as the line are skipped
+    fn browse(&mut self, context: &mut Context, older: bool, by_prefix: bool) {
+        // with sharehistory what the other sessions added is there too, a
+        // failed read only leaves it out
+        if self.browsing.is_none() && older && context.share_history {
+            let _ = context.history.merge();
+        }
+        let history = &*context.history;
+        let numbers = history.numbers();
+        if self.browsing.is_none() {
+            if !older {
+                return;
+            }
+            self.saved = self.buffer.clone();
+        }
+        let current = self.browsing.unwrap_or(numbers.end);
+        let prefix: String = if by_prefix || context.history_prefix {
+            self.saved.iter().collect()
+        } else {
+            String::new()
+        };
+        let matches = |number: &usize| {
+            history.get(*number).is_some_and(|entry| {
+                entry.starts_with(&prefix) && !entry.chars().eq(self.buffer.iter().copied())
+            })
+        };
+        let found = if older {
+            (numbers.start..current).rev().find(matches)
+        } else {
+            (current + 1..numbers.end).find(matches)
+        };
+        match found {
+            Some(number) => {
+                self.browsing = Some(number);
+                self.buffer = history.get(number).unwrap_or_default().chars().collect();
+            }
+            None if older => return,
+            None => {
+                self.browsing = None;
+                self.buffer = std::mem::take(&mut self.saved);
+            }
+        }
+        self.cursor = self.buffer.len();
+    }
+
+    // Show the search (prompt and query) instead of the prompt, and the entry
+    // found with the cursor in front of the match
+    fn draw_search(
+        &mut self,
+        query: &str,
+        entry: &str,
+        position: usize,
+        failed: bool,
+    ) -> io::Result<()> {
+        let prompt = if failed {
+            format!("(failed reverse-i-search)`{}': ", query)
+        } else {
+            format!("(reverse-i-search)`{}': ", query)
+        };
+        self.draw(&prompt, entry, &[], "", position)
+    }
+
+    // Search the history for entries containing what's typed, newest first.
+    // Ctrl-R again goes on to older ones and Backspace takes back the last
+    // character, which can only find newer entries. Ctrl-G gives up and brings
+    // back the line from before. Any other key ends the search with the entry
+    // found in the buffer, it's returned to be handled as usual
+    fn reverse_search(&mut self, context: &mut Context) -> io::Result<Option<Key>> {
+        if context.share_history {
+            let _ = context.history.merge();
+        }
+        let history = &*context.history;
+        let numbers = history.numbers();
+        let mut query = String::new();
+        // entry found and the position of the match in it, in characters
+        let mut found: Option<(usize, usize)> = None;
+        let mut failed = false;
+        let original: String = self.buffer.iter().collect();
+        let end = loop {
+            let entry = found.and_then(|(number, _)| history.get(number));
+            let position = found.map_or(self.cursor, |(_, position)| position);
+            self.draw_search(&query, entry.unwrap_or(&original), position, failed)?;
+            let Some((key, _)) = self.next_key()? else {
+                break None;
+            };
+            let (from, skip) = match key {
+                Key::Control(b'r') if query.is_empty() => {
+                    query = self.last_search.clone();
+                    (numbers.end, None)
+                }
+                // older than the entry found, and not the same line again
+                Key::Control(b'r') => match found {
+                    Some((number, _)) => (number, entry),
+                    None => (numbers.end, None),
+                },
+                // a longer query can still match the entry found
+                Key::Char(c) => {
+                    query.push(c);
+                    (found.map_or(numbers.end, |(number, _)| number + 1), None)
+                }
+                Key::Backspace => {
+                    query.pop();
+                    (numbers.end, None)
+                }
+                Key::Control(b'g') => {
+                    found = None;
+                    break None;
+                }
+                Key::Resize => continue,
+                key => break Some(key),
+            };
+            if query.is_empty() {
+                found = None;
+                failed = false;
+                continue;
+            }
+            let next = (numbers.start..from).rev().find_map(|number| {
+                let entry = history.get(number)?;
+                if Some(entry) == skip {
+                    return None;
+                }
+                let i = entry.rfind(&query)?;
+                Some((number, entry[..i].chars().count()))
+            });
+            match next {
+                Some(next) => {
+                    found = Some(next);
+                    failed = false;
+                }
+                None => {
+                    // the last entry found stays, like in bash. The bell rings
+                    // when the search starts failing, not for every key after
+                    if !failed {
+                        bell()?;
+                    }
+                    failed = true;
+                }
+            }
+        };
+        if !query.is_empty() {
+            self.last_search = query;
+        }
+        if let Some((number, position)) = found {
+            if self.browsing.is_none() {
+                self.saved = self.buffer.clone();
+            }
+            self.browsing = Some(number);
+            self.buffer = history.get(number).unwrap_or_default().chars().collect();
+            self.cursor = position;
+        }
+        Ok(end)
+    }
+
+    // Show the matches of a completion in columns under the line, sorted down
+    // the columns like ls does, and draw the line again below them. When there
+    // are lots of them only if the user wants to see them
+    fn list(&mut self, matches: &[String]) -> io::Result<()> {
+        // below the line, it mustn't be written over
+        let cursor = self.cursor;
+        self.end_line()?;
+        self.cursor = cursor;
+        if matches.len() > LIST_WITHOUT_ASKING {
+            print!("Display all {} possibilities? (y or n)", matches.len());
+            io::stdout().flush()?;
+            let answer = self.next_key()?.map(|(key, _)| key);
+            print!("\r\n");
+            if !matches!(answer, Some(Key::Char('y' | 'Y' | ' '))) {
+                return self.refresh();
+            }
+        }
+        let width = column_width(matches);
+        let columns = (sys::terminal_columns().unwrap_or(80) / width).max(1);
+        let rows = matches.len().div_ceil(columns);
+        let mut out = String::new();
+        for row in 0..rows {
+            let line: Vec<&String> = matches.iter().skip(row).step_by(rows).collect();
+            for (i, name) in line.iter().enumerate() {
+                out.push_str(name);
+                if i + 1 < line.len() {
+                    out.push_str(&" ".repeat(width - unicode::text_width(name)));
+                }
+            }
+            out.push_str("\r\n");
+        }
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        drop(stdout);
+        self.refresh()
+    }
+
+    // The rows of the completion menu with `selected` highlighted, sorted
+    // down the columns like the list. When they don't all fit under the line
+    // the page with the selected one is shown, with a row saying which
+    fn menu_rows(&self, matches: &[String], selected: usize) -> Vec<String> {
+        let (columns, rows) = sys::terminal_size().unwrap_or((80, 24));
+        let width = column_width(matches);
+        let total = matches.len().div_ceil((columns / width).max(1));
+        let text: String = self.buffer.iter().collect();
+        let line_rows = position(self.shown(&self.prompt, text.chars()).chars(), columns).0 + 1;
+        let page = rows.saturating_sub(line_rows + 1).max(1);
+        let first = if total > page {
+            selected % total / page * page
+        } else {
+            0
+        };
+        let mut shown = Vec::new();
+        for row in first..total.min(first + page) {
+            let mut line = String::new();
+            let names = matches.iter().enumerate().skip(row).step_by(total);
+            for (i, name) in names {
+                if !line.is_empty() {
+                    line.push_str("  ");
+                }
+                // up to the width of the column, the last one isn't padded
+                let padding = if i + total < matches.len() {
+                    width - 2 - unicode::text_width(name)
+                } else {
+                    0
+                };
+                if i == selected {
+                    line.push_str(&format!("\x1b[{}m{}\x1b[0m", MENU_SELECTED, name));
+                } else {
+                    line.push_str(name);
+                }
+                line.push_str(&" ".repeat(padding));
+            }
+            shown.push(line);
+        }
+        if total > page {
+            let last = total.min(first + page);
+            shown.push(format!("rows {} to {} of {}", first + 1, last, total));
+        }
+        shown
+    }
+
+    // Tab when it can't add anything to the word: a menu of the matches under
+    // the line, the one picked is put in the line. Tab and Shift-Tab pick the
+    // next and the previous one and the arrow keys move around the menu, Enter
+    // or space keep the one picked. Escape puts the line back like it was
+    // before. Any other key keeps it too and is returned to be handled as
+    // usual
+    fn menu(&mut self, completion: &Completion) -> io::Result<Option<Key>> {
+        let count = completion.choices.len();
+        let original = (self.buffer.clone(), self.cursor);
+        let mut selected = 0;
+        self.suggestion.clear();
+        let end = loop {
+            let (buffer, cursor) = &original;
+            self.buffer = buffer.clone();
+            self.cursor = *cursor;
+            self.insert(&completion.choices[selected]);
+            self.below = self.menu_rows(&completion.matches, selected);
+            self.refresh()?;
+            let Some((key, _)) = self.next_key()? else {
+                break None;
+            };
+            // the menu goes down the columns, Left and Right go a whole
+            // column back and on
+            let columns = sys::terminal_columns().unwrap_or(80);
+            let rows = count.div_ceil((columns / column_width(&completion.matches)).max(1));
+            selected = match key {
+                Key::Control(b'i') | Key::Down => (selected + 1) % count,
+                Key::BackTab | Key::Up => (selected + count - 1) % count,
+                Key::Right if selected + rows < count => selected + rows,
+                Key::Right => (selected % rows + 1) % rows,
+                Key::Left if selected >= rows => selected - rows,
+                Key::Left => {
+                    let row = (selected % rows + rows - 1) % rows;
+                    (row..count).step_by(rows).next_back().unwrap_or(row)
+                }
+                Key::Enter | Key::Char(' ') => break None,
+                Key::Escape | Key::Control(b'g') => {
+                    (self.buffer, self.cursor) = original;
+                    break None;
+                }
+                Key::Resize => selected,
+                key => break Some(key),
+            };
+        };
+        self.below.clear();
+        Ok(end)
+    }
+
+    // Tab: complete the word in front of the cursor as far as all the matches
+    // agree. When that adds nothing they're shown in a menu to pick from, or
+    // listed when the size of the terminal isn't known. Returns the key that
+    // ended the menu when it wasn't one of its own
+    fn complete(&mut self, context: &mut Context) -> io::Result<Option<Key>> {
+        let Some(completion) = complete::complete(&self.buffer[..self.cursor], context) else {
+            bell()?;
+            return Ok(None);
+        };
+        if completion.insert.is_empty() {
+            if completion.matches.len() < 2 || sys::terminal_size().is_none() {
+                self.list(&completion.matches)?;
+                return Ok(None);
+            }
+            return self.menu(&completion);
+        }
+        self.insert(&completion.insert);
+        Ok(None)
+    }
+
+    // where the word after the cursor ends, or the word before it starts
+    fn word_end(&self) -> usize {
+        let after = &self.buffer[self.cursor..];
+        let start = after.iter().take_while(|c| !is_word_char(**c)).count();
+        let len = after[start..]
+            .iter()
+            .take_while(|c| is_word_char(**c))
+            .count();
+        self.cursor + start + len
+    }
+
+    fn word_start(&self) -> usize {
+        let before = &self.buffer[..self.cursor];
+        let end = before.len()
+            - before
+                .iter()
+                .rev()
+                .take_while(|c| !is_word_char(**c))
+                .count();
+        let len = before[..end]
+            .iter()
+            .rev()
+            .take_while(|c| is_word_char(**c))
+            .count();
+        end - len
+    }
+
+    // Alt-.: insert the last word of the previous command. Pressed again right
+    // after, it's replaced by the one of the command before that
+    fn insert_last_argument(
+        &mut self,
+        history: &History,
+        previous: Option<(usize, usize)>,
+    ) -> io::Result<()> {
+        let numbers = history.numbers();
+        let before = previous.map_or(numbers.end, |(number, _)| number);
+        let found = (numbers.start..before)
+            .rev()
+            .find_map(|number| Some((number, parser::last_word(history.get(number)?)?)));
+        let Some((number, word)) = found else {
+            self.last_argument = previous;
+            return bell();
+        };
+        if let Some((_, len)) = previous {
+            self.buffer.drain(self.cursor - len..self.cursor);
+            self.cursor -= len;
+        }
+        let len = word.chars().count();
+        self.buffer.splice(self.cursor..self.cursor, word.chars());
+        self.cursor += len;
+        self.last_argument = Some((number, len));
+        self.refresh()
+    }
+
+    // Take out a part of the line and put it in the kill ring, the cursor
+    // stays in front of what was after it. Right after another kill the text
+    // joins what that killed, in front of it when it was before the cursor
+    fn kill(&mut self, range: Range<usize>, after_kill: bool) {
+        let backward = range.end <= self.cursor && range.start < self.cursor;
+        if self.cursor >= range.end {
+            self.cursor -= range.len();
+        } else if self.cursor > range.start {
+            self.cursor = range.start;
+        }
+        let text: String = self.buffer.drain(range).collect();
+        self.killed = true;
+        match self.kill_ring.last_mut() {
+            Some(last) if after_kill && backward => last.insert_str(0, &text),
+            Some(last) if after_kill => last.push_str(&text),
+            _ => self.remember(text),
+        }
+    }
+
+    // put text into the kill ring as its newest entry
+    fn remember(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        if self.kill_ring.len() == KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(text);
+    }
+
+    // Go to the vi commands or back to inserting text. The cursor is a block
+    // for the commands and a bar while inserting, in terminals that can
+    // change its shape
+    fn set_vi_command(&mut self, command: bool) -> io::Result<()> {
+        self.vi_command = command;
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(if command { b"\x1b[2 q" } else { b"\x1b[6 q" })?;
+        stdout.flush()
+    }
+
+    // Where a vi motion moves the cursor to, None for the actions that
+    // aren't one. e moves onto the last character of a word, not past it
+    fn motion(&self, action: Action) -> Option<usize> {
+        let buffer = &self.buffer;
+        let len = buffer.len();
+        let mut i = self.cursor;
+        match action {
+            Action::BackwardChar => Some(unicode::previous_boundary(buffer, i)),
+            Action::ForwardChar => Some(unicode::next_boundary(buffer, i)),
+            Action::BeginningOfLine => Some(0),
+            Action::EndOfLine => Some(len),
+            Action::ViFirstPrint => Some(
+                buffer
+                    .iter()
+                    .position(|c| !c.is_whitespace())
+                    .unwrap_or(len),
+            ),
+            Action::ViForwardWord => {
+                if let Some(&c) = buffer.get(i).filter(|c| !c.is_whitespace()) {
+                    while i < len && vi_class(buffer[i]) == vi_class(c) {
+                        i += 1;
+                    }
+                }
+                while i < len && buffer[i].is_whitespace() {
+                    i += 1;
+                }
+                Some(i)
+            }
+            Action::ViBackwardWord => {
+                while i > 0 && buffer[i - 1].is_whitespace() {
+                    i -= 1;
+                }
+                if let Some(&c) = i.checked_sub(1).map(|i| &buffer[i]) {
+                    while i > 0 && vi_class(buffer[i - 1]) == vi_class(c) {
+                        i -= 1;
+                    }
+                }
+                Some(i)
+            }
+            Action::ViEndWord => {
+                i += 1;
+                while i < len && buffer[i].is_whitespace() {
+                    i += 1;
+                }
+                if i >= len {
+                    return Some(self.cursor.max(len.saturating_sub(1)));
+                }
+                while i + 1 < len && vi_class(buffer[i + 1]) == vi_class(buffer[i]) {
+                    i += 1;
+                }
+                Some(i)
+            }
+            _ => None,
+        }
+    }
+
+    // What a vi operator acts on with the binding of the key typed after it:
+    // the whole line when it's the operator again, or up to where the motion
+    // goes. cw on a word only changes up to its end, like in vi
+    fn operator_range(&self, operator: Action, binding: Option<Binding>) -> Option<Range<usize>> {
+        let Some(Binding::Action(mut motion)) = binding else {
+            return None;
+        };
+        if motion == operator {
+            return Some(0..self.buffer.len());
+        }
+        let on_word = (self.buffer.get(self.cursor)).is_some_and(|c| !c.is_whitespace());
+        if operator == Action::ViChangeTo && motion == Action::ViForwardWord && on_word {
+            motion = Action::ViEndWord;
+        }
+        let target = self.motion(motion)?;
+        if motion == Action::ViEndWord {
+            return Some(self.cursor..(target + 1).min(self.buffer.len()));
+        }
+        Some(self.cursor.min(target)..self.cursor.max(target))
+    }
+
+    // Ctrl-Y: insert the text killed last. Alt-Y right after replaces it with
+    // what was killed before that, going round the ring
+    fn yank(&mut self, previous: Option<(usize, usize)>, older: bool) -> io::Result<()> {
+        let index = match previous {
+            Some((index, len)) if older => {
+                self.buffer.drain(self.cursor - len..self.cursor);
+                self.cursor -= len;
+                (index + self.kill_ring.len() - 1) % self.kill_ring.len()
+            }
+            None if older => return bell(),
+            _ if self.kill_ring.is_empty() => return bell(),
+            _ => self.kill_ring.len() - 1,
+        };
+        let text = &self.kill_ring[index];
+        let len = text.chars().count();
+        self.buffer.splice(self.cursor..self.cursor, text.chars());
+        self.cursor += len;
+        self.yanked = Some((index, len));
+        self.refresh()
+    }
+
+    // Handle a key and what it's bound to, returns whether the line is
+    // finished
+    fn edit(
+        &mut self,
+        key: Key,
+        binding: Option<Binding>,
+        context: &mut Context,
+    ) -> io::Result<bool> {
+        let last_argument = self.last_argument.take();
+        let after_kill = std::mem::take(&mut self.killed);
+        let yanked = self.yanked.take();
+        let action = match (key, binding) {
+            // pasted text goes in as it is, even newlines, nothing runs
+            // before Enter
+            (Key::PasteStart, _) => {
+                let text = read_paste()?;
+                self.insert(&text);
+                None
+            }
+            // drawn again for the new width
+            (Key::Resize, _) => None,
+            (_, Some(Binding::Text(text))) => {
+                self.insert(&text);
+                None
+            }
+            (_, Some(Binding::Action(action))) => Some(action),
+            (_, None) => return Ok(false),
+        };
+        match action {
+            None => {}
+            // a command that isn't finished yet goes on on a new line, Alt-Enter
+            // always starts one
+            Some(Action::AcceptLine) => {
+                let text: String = self.buffer.iter().collect();
+                if parser::is_complete(&text) {
+                    self.end_line()?;
+                    return Ok(true);
+                }
+                self.insert("\n");
+            }
+            Some(Action::Newline) => self.insert("\n"),
+            Some(Action::SelfInsert) => {
+                let Key::Char(c) = key else {
+                    return Ok(false);
+                };
+                self.buffer.insert(self.cursor, c);
+                self.cursor += 1;
+                // typing at the end of the line only needs the new character,
+                // unless the line wraps with it or runs into the right prompt
+                let columns = sys::terminal_columns().unwrap_or(80);
+                let shown = self.shown(&self.prompt, self.buffer.iter().copied());
+                let (row, column) = position(shown.chars(), columns);
+                let at_end = self.cursor == self.buffer.len();
+                let styles = self.styles.clone();
+                let suggested = !self.suggestion.is_empty();
+                self.update_hints(context);
+                // nor when it changes the colors of the line or has one itself
+                let colored = !self.styles.is_empty()
+                    && (!self.styles.starts_with(&styles)
+                        || self.styles[styles.len()..] != [Style::Plain]);
+                let plain = self.right_prompt.is_empty()
+                    && !suggested
+                    && self.suggestion.is_empty()
+                    && !colored;
+                if at_end && column < columns && plain {
+                    self.cursor_row = row;
+                    let mut stdout = io::stdout().lock();
+                    write!(stdout, "{}", c)?;
+                    stdout.flush()?;
+                    return Ok(false);
+                }
+            }
+            // at the end of the line they take the suggestion, or a word of it
+            Some(Action::ForwardChar | Action::EndOfLine) if !self.suggestion.is_empty() => {
+                self.accept_suggestion(false)
+            }
+            Some(Action::ForwardWord) if !self.suggestion.is_empty() => {
+                self.accept_suggestion(true)
+            }
+            // the cursor moves over what's shown as one character, even when
+            // it's made of several
+            Some(Action::BackwardChar) => {
+                self.cursor = unicode::previous_boundary(&self.buffer, self.cursor)
+            }
+            Some(Action::ForwardChar) => {
+                self.cursor = unicode::next_boundary(&self.buffer, self.cursor)
+            }
+            Some(Action::BeginningOfLine) => self.cursor = 0,
+            Some(Action::EndOfLine) => self.cursor = self.buffer.len(),
+            Some(Action::BackwardDeleteChar) => {
+                let start = unicode::previous_boundary(&self.buffer, self.cursor);
+                self.buffer.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Some(Action::DeleteChar) => {
+                let end = unicode::next_boundary(&self.buffer, self.cursor);
+                self.buffer.drain(self.cursor..end);
+            }
+            // kill to the end and the start of the line, and the word before
+            // the cursor up to whitespace
+            Some(Action::KillLine) => self.kill(self.cursor..self.buffer.len(), after_kill),
+            Some(Action::UnixLineDiscard) => self.kill(0..self.cursor, after_kill),
+            Some(Action::UnixWordRubout) => {
+                let before = &self.buffer[..self.cursor];
+                let word = before.len()
+                    - before
+                        .iter()
+                        .rev()
+                        .take_while(|c| c.is_whitespace())
+                        .count();
+                let start = before[..word]
+                    .iter()
+                    .rposition(|c| c.is_whitespace())
+                    .map_or(0, |i| i + 1);
+                self.kill(start..self.cursor, after_kill);
+            }
+            // swap the characters around the cursor, or the last two at the
+            // end of the line, and move past them
+            Some(Action::TransposeChars) => {
+                let mut middle = self.cursor;
+                if middle == self.buffer.len() {
+                    middle = unicode::previous_boundary(&self.buffer, middle);
+                }
+                if middle == 0 {
+                    return Ok(false);
+                }
+                let start = unicode::previous_boundary(&self.buffer, middle);
+                let end = unicode::next_boundary(&self.buffer, middle);
+                self.buffer[start..end].rotate_left(middle - start);
+                self.cursor = end;
+            }
+            // clear the screen, the line is drawn again at the top
+            Some(Action::ClearScreen) => {
+                print!("\x1b[H\x1b[2J");
+                self.cursor_row = 0;
+            }
+            // in a command of several lines they first move between them
+            Some(Action::PreviousHistory) if !self.move_line(true) => {
+                self.browse(context, true, false)
+            }
+            Some(Action::NextHistory) if !self.move_line(false) => {
+                self.browse(context, false, false)
+            }
+            Some(Action::PreviousHistory | Action::NextHistory) => {}
+            Some(Action::HistorySearchBackward) => self.browse(context, true, true),
+            Some(Action::HistorySearchForward) => self.browse(context, false, true),
+            Some(Action::ForwardWord) => self.cursor = self.word_end(),
+            Some(Action::BackwardWord) => self.cursor = self.word_start(),
+            Some(Action::KillWord) => self.kill(self.cursor..self.word_end(), after_kill),
+            Some(Action::BackwardKillWord) => self.kill(self.word_start()..self.cursor, after_kill),
+            Some(action @ (Action::Yank | Action::YankPop)) => {
+                self.yank(yanked, action == Action::YankPop)?;
+                return Ok(false);
+            }
+            Some(Action::YankLastArg) => {
+                self.insert_last_argument(&*context.history, last_argument)?;
+                return Ok(false);
+            }
+            // the shell takes it from here
+            Some(Action::EditAndExecuteCommand) => {
+                context.edit = true;
+                self.end_line()?;
+                return Ok(true);
+            }
+            Some(Action::Complete) => {
+                if let Some(key) = self.complete(context)? {
+                    let keymap = self.keymap(context.keymaps);
+                    let binding = lookup(keymap, key, &key_bytes(key).unwrap_or_default());
+                    return self.edit(key, binding, context);
+                }
+            }
+            Some(Action::ReverseSearchHistory) => {
+                if let Some(key) = self.reverse_search(context)? {
+                    self.refresh()?;
+                    let keymap = self.keymap(context.keymaps);
+                    let binding = lookup(keymap, key, &key_bytes(key).unwrap_or_default());
+                    return self.edit(key, binding, context);
+                }
+            }
+            // Escape, the cursor goes back onto the last character typed
+            Some(Action::ViMovementMode) => {
+                if self.cursor > 0 && self.buffer[self.cursor - 1] != '\n' {
+                    self.cursor = unicode::previous_boundary(&self.buffer, self.cursor);
+                }
+                self.set_vi_command(true)?;
+            }
+            Some(Action::ViInsertionMode) => self.set_vi_command(false)?,
+            Some(Action::ViAppendMode) => {
+                self.cursor = unicode::next_boundary(&self.buffer, self.cursor);
+                self.set_vi_command(false)?;
+            }
+            Some(Action::ViInsertBeg) => {
+                self.cursor = self.motion(Action::ViFirstPrint).unwrap_or(0);
+                self.set_vi_command(false)?;
+            }
+            Some(Action::ViAppendEol) => {
+                self.cursor = self.buffer.len();
+                self.set_vi_command(false)?;
+            }
+            Some(
+                motion @ (Action::ViForwardWord
+                | Action::ViBackwardWord
+                | Action::ViEndWord
+                | Action::ViFirstPrint),
+            ) => self.cursor = self.motion(motion).unwrap_or(self.cursor),
+            // the key after an operator says what it acts on, anything but a
+            // motion or the operator again cancels it
+            Some(operator @ (Action::ViDeleteTo | Action::ViChangeTo | Action::ViYankTo)) => {
+                let keymaps = context.keymaps;
+                let Some((_, binding)) = self.read_binding(&keymaps.vi_command)? else {
+                    return Ok(false);
+                };
+                let Some(range) = self.operator_range(operator, binding) else {
+                    bell()?;
+                    return Ok(false);
+                };
+                if operator == Action::ViYankTo {
+                    let text = self.buffer[range.clone()].iter().collect();
+                    self.remember(text);
+                    self.cursor = range.start;
+                } else {
+                    self.kill(range, false);
+                }
+                if operator == Action::ViChangeTo {
+                    self.set_vi_command(false)?;
+                }
+            }
+            Some(Action::ViChangeEol) => {
+                self.kill(self.cursor..self.buffer.len(), false);
+                self.set_vi_command(false)?;
+            }
+            Some(Action::ViChangeWholeLine) => {
+                self.kill(0..self.buffer.len(), false);
+                self.set_vi_command(false)?;
+            }
+            // r and the character that replaces the one under the cursor
+            Some(Action::ViChangeChar) => {
+                if let Some((Key::Char(c), _)) = self.next_key()? {
+                    if self.cursor < self.buffer.len() {
+                        let end = unicode::next_boundary(&self.buffer, self.cursor);
+                        self.buffer.splice(self.cursor..end, [c]);
+                    }
+                }
+            }
+            // put what was deleted or yanked last after the cursor or in
+            // front of it, the cursor ends up on its last character
+            Some(action @ (Action::ViPut | Action::ViPutBefore)) => {
+                let Some(text) = self.kill_ring.last().cloned() else {
+                    bell()?;
+                    return Ok(false);
+                };
+                if action == Action::ViPut {
+                    self.cursor = unicode::next_boundary(&self.buffer, self.cursor);
+                }
+                self.insert(&text);
+                self.cursor = unicode::previous_boundary(&self.buffer, self.cursor);
+            }
+        }
+        // the vi commands keep the cursor on a character of the line
+        if self.vi_command && self.cursor == self.buffer.len() {
+            self.cursor = unicode::previous_boundary(&self.buffer, self.cursor);
+        }
+        self.update_hints(context);
+        self.refresh()?;
+        Ok(false)
+    }
+
+    // put `text` into the line at the cursor and move past it
+    fn insert(&mut self, text: &str) {
+        let len = text.chars().count();
+        self.buffer.splice(self.cursor..self.cursor, text.chars());
+        self.cursor += len;
+    }
+}
+
+impl ReadLine for Editor {
+    fn read_line(&mut self, line: &mut String, context: &mut Context) -> io::Result<usize> {
+        let raw = RawMode::enable(context.terminal_modes, context.vi)?;
+        self.continuation = std::mem::take(&mut context.continuation_prompt);
+        self.right_prompt = std::mem::take(&mut context.right_prompt);
+        self.buffer = std::mem::take(&mut context.line).chars().collect();
+        self.cursor = self.buffer.len();
+        self.cursor_row = 0;
+        self.browsing = None;
+        // nothing of the line before carries over, even when it was thrown
+        // away with Ctrl-C right after a yank
+        self.last_argument = None;
+        self.killed = false;
+        self.yanked = None;
+        // every line starts out inserting text
+        self.vi = context.vi;
+        self.vi_command = false;
+        if self.vi {
+            self.set_vi_command(false)?;
+        }
+        self.refresh()?;
+        loop {
+            let keymap = self.keymap(context.keymaps);
+            let (key, binding) = match self.read_binding(keymap) {
+                Ok(Some(key)) => key,
+                // ^D on an empty line ends the input like it does in cooked mode
+                Ok(None) => return Ok(0),
+                Err(e) => return Err(self.abandon(e)),
+            };
+            if key == Key::Control(b'd') && self.buffer.is_empty() {
+                return Ok(0);
+            }
+            // Ctrl-C in a search or at a question interrupts them too
+            match self.edit(key, binding, context) {
+                Ok(true) => break,
+                // after the keys that draw the line themselves
+                Ok(false) if self.update_hints(context) => self.refresh()?,
+                Ok(false) => {}
+                Err(e) => return Err(self.abandon(e)),
+            }
+        }
+        drop(raw);
+        let text: String = self.buffer.iter().collect();
+        line.push_str(&text);
+        line.push('\n');
+        Ok(text.len() + 1)
+    }
+
+    fn show_prompt(&mut self, prompt: &str) {
+        // drawn with the line, it's drawn again whenever the line changes
+        self.prompt = prompt.to_string();
+    }
+
+    fn interrupted(&mut self) {
+        println!("^C");
+    }
+}
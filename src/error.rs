@@ -0,0 +1,183 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::error_message;
+
+// What can go wrong running a line or a script. None of them stop the shell:
+// each is reported once, as `location: message`, and the line ends with its
+// status
+#[derive(Debug)]
+pub enum ShellError {
+    // a script that couldn't be opened
+    Script {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // a line that doesn't parse
+    Parse(String),
+    // a file a redirection couldn't open, or a descriptor it couldn't duplicate
+    Redirect {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // a redirection whose target didn't expand to one word
+    AmbiguousRedirect(String),
+    // a ${...} ish doesn't have
+    BadSubstitution(String),
+    // a command that couldn't be started, or that exec couldn't replace the shell with
+    Spawn {
+        command: OsString,
+        source: io::Error,
+    },
+    Exec {
+        command: OsString,
+        source: io::Error,
+    },
+    // a job a builtin can't find or continue
+    Job {
+        builtin: &'static str,
+        message: String,
+    },
+    // arguments a builtin doesn't take
+    Usage {
+        builtin: &'static str,
+        message: String,
+    },
+    // the arguments of a `timeout` prefix
+    Timeout(String),
+    // what a restricted shell doesn't do, and why when it's not a builtin
+    // that's refused altogether
+    Restricted {
+        what: String,
+        reason: Option<&'static str>,
+    },
+    // what the shell needs from the system to run a line, like a pipe
+    Io {
+        context: Option<&'static str>,
+        source: io::Error,
+    },
+}
+
+impl ShellError {
+    // A command that couldn't be started. A directory can't be run, that's
+    // said instead of why the exec failed
+    pub fn spawn(command: &OsStr, source: io::Error) -> ShellError {
+        ShellError::Spawn {
+            command: command.to_owned(),
+            source: directory_error(command, source),
+        }
+    }
+
+    pub fn exec(command: &OsStr, source: io::Error) -> ShellError {
+        ShellError::Exec {
+            command: command.to_owned(),
+            source: directory_error(command, source),
+        }
+    }
+
+    // a builtin a restricted shell doesn't run
+    pub fn restricted(builtin: &str) -> ShellError {
+        ShellError::Restricted {
+            what: builtin.to_string(),
+            reason: None,
+        }
+    }
+
+    pub fn io(source: io::Error) -> ShellError {
+        ShellError::Io {
+            context: None,
+            source,
+        }
+    }
+
+    // The status a line that failed this way ends with: 127 for a command
+    // that wasn't found and 126 for one that couldn't be executed
+    pub fn status(&self) -> i32 {
+        match self {
+            ShellError::Script { .. } => 127,
+            ShellError::Parse(_) | ShellError::Usage { .. } => 2,
+            ShellError::Spawn { source, .. } | ShellError::Exec { source, .. } => {
+                if source.kind() == io::ErrorKind::NotFound {
+                    127
+                } else {
+                    126
+                }
+            }
+            ShellError::Timeout(_) => 125,
+            _ => 1,
+        }
+    }
+}
+
+fn directory_error(command: &OsStr, source: io::Error) -> io::Error {
+    if source.kind() != io::ErrorKind::NotFound && Path::new(command).is_dir() {
+        io::Error::from_raw_os_error(libc::EISDIR)
+    } else {
+        source
+    }
+}
+
+// "name: command not found" for a command looked up in $PATH, the error
+// for a path
+fn spawn_message(f: &mut fmt::Formatter, command: &OsString, source: &io::Error) -> fmt::Result {
+    let name = Path::new(command).display();
+    if source.kind() == io::ErrorKind::NotFound && !command.as_bytes().contains(&b'/') {
+        write!(f, "{}: command not found", name)
+    } else {
+        write!(f, "{}: {}", name, error_message(source))
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::Parse(message) => write!(f, "{}", message),
+            ShellError::Script { path, source } | ShellError::Redirect { path, source } => {
+                write!(f, "{}: {}", path.display(), error_message(source))
+            }
+            ShellError::AmbiguousRedirect(target) => write!(f, "{}: ambiguous redirect", target),
+            ShellError::BadSubstitution(text) => write!(f, "{}: bad substitution", text),
+            ShellError::Spawn { command, source } => spawn_message(f, command, source),
+            ShellError::Exec { command, source } => {
+                write!(f, "exec: ")?;
+                spawn_message(f, command, source)
+            }
+            ShellError::Job { builtin, message } | ShellError::Usage { builtin, message } => {
+                write!(f, "{}: {}", builtin, message)
+            }
+            ShellError::Timeout(message) => write!(f, "timeout: {}", message),
+            ShellError::Restricted { what, reason } => {
+                write!(f, "{}: restricted", what)?;
+                match reason {
+                    Some(reason) => write!(f, ": {}", reason),
+                    None => Ok(()),
+                }
+            }
+            ShellError::Io {
+                context: Some(context),
+                source,
+            } => write!(f, "{}: {}", context, error_message(source)),
+            ShellError::Io {
+                context: None,
+                source,
+            } => write!(f, "{}", error_message(source)),
+        }
+    }
+}
+
+impl std::error::Error for ShellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShellError::Script { source, .. }
+            | ShellError::Redirect { source, .. }
+            | ShellError::Spawn { source, .. }
+            | ShellError::Exec { source, .. }
+            | ShellError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
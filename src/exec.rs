@@ -0,0 +1,855 @@
+use std::{
+    borrow::Cow,
+    env,
+    ffi::{CStr, CString, OsStr, OsString},
+    fs::OpenOptions,
+    io::{self, stdout, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt, process::CommandExt},
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    ptr,
+    time::{Duration, Instant},
+};
+
+use libc::{c_char, c_int, pid_t, STDIN_FILENO};
+
+use crate::{
+    builtins::Builtins,
+    error::ShellError,
+    expand::{expand_fields, expand_word, Parameters},
+    jobs::{
+        abandon_pipeline, capture_file, next_job_number, tag_output, wait_for_job, Job, JobState,
+    },
+    parser::{self, Redirect, RedirectOp},
+    path_cache::PathCache,
+    redirect::{self, Redirection},
+    shell::Shell,
+    suggest,
+    sys::{self, Disposition, Signal},
+    term::exit_shell,
+    timeout::{self, Timeout},
+};
+
+// Everything needed to exec a command, built before forking because the
+// child can't safely allocate while other threads of the shell are running
+pub struct ExecArgs {
+    path: CString,
+    // owns the strings argv_ptrs points into
+    argv: Vec<CString>,
+    argv_ptrs: Vec<*const c_char>,
+    // how to run the file as an ish script if the kernel doesn't know the format
+    script_argv: Vec<CString>,
+    script_argv_ptrs: Vec<*const c_char>,
+    // the shell's environment with $_ set to the path of the command, like
+    // bash does it. Only owns the strings env_ptrs points into
+    _env: Vec<CString>,
+    env_ptrs: Vec<*const c_char>,
+}
+
+// signals the shell ignores that commands should get back, ignoring is
+// inherited through exec. Rust ignores SIGPIPE before main
+const IGNORED: [Signal; 3] = [Signal::Ttou, Signal::Ttin, Signal::Pipe];
+
+// the pointers only point into the CStrings owned by the same struct
+unsafe impl Send for ExecArgs {}
+unsafe impl Sync for ExecArgs {}
+
+fn cstring(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+// null terminated array of pointers to the strings, as execv wants it
+fn pointers(strings: &[CString]) -> Vec<*const c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain([ptr::null()])
+        .collect()
+}
+
+impl ExecArgs {
+    // Resolve `command` on $PATH and prepare its argv, fails with NotFound when
+    // there is nothing to execute
+    pub fn new(
+        command: &OsStr,
+        args: &[OsString],
+        shell: &Path,
+        path_cache: &mut PathCache,
+    ) -> io::Result<ExecArgs> {
+        let path = if command.as_bytes().contains(&b'/') {
+            Path::new(command).to_path_buf()
+        } else {
+            path_cache.find(command).ok_or(io::ErrorKind::NotFound)?
+        };
+        let path = cstring(path.as_os_str().as_bytes())?;
+
+        let mut argv = vec![cstring(command.as_bytes())?];
+        for arg in args {
+            argv.push(cstring(arg.as_bytes())?);
+        }
+
+        let mut script_argv = vec![cstring(shell.as_os_str().as_bytes())?, path.clone()];
+        script_argv.extend(argv[1..].iter().cloned());
+
+        let mut vars = vec![cstring(&[b"_=", path.as_bytes()].concat())?];
+        for (name, value) in env::vars_os().filter(|(name, _)| name != "_") {
+            vars.push(cstring(
+                &[name.as_bytes(), b"=", value.as_bytes()].concat(),
+            )?);
+        }
+
+        Ok(ExecArgs {
+            argv_ptrs: pointers(&argv),
+            script_argv_ptrs: pointers(&script_argv),
+            env_ptrs: pointers(&vars),
+            path,
+            argv,
+            script_argv,
+            _env: vars,
+        })
+    }
+
+    // Give the command `name` as its argv[0] instead of the name it was run as
+    pub fn argv0(mut self, name: &OsStr) -> io::Result<ExecArgs> {
+        self.argv[0] = cstring(name.as_bytes())?;
+        self.argv_ptrs = pointers(&self.argv);
+        Ok(self)
+    }
+
+    pub fn path(&self) -> &CStr {
+        &self.path
+    }
+
+    // Replace the current process with the command, only returns on failure.
+    // Files the kernel can't execute are run by the shell when they look like
+    // text, like POSIX shells do for scripts without a #! line
+    pub fn exec(&self) -> io::Error {
+        unsafe {
+            for signal in IGNORED {
+                let _ = sys::set_signal(signal, Disposition::Default);
+            }
+            libc::execve(
+                self.path.as_ptr(),
+                self.argv_ptrs.as_ptr(),
+                self.env_ptrs.as_ptr(),
+            );
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOEXEC) && looks_like_text(&self.path) {
+                libc::execve(
+                    self.script_argv[0].as_ptr(),
+                    self.script_argv_ptrs.as_ptr(),
+                    self.env_ptrs.as_ptr(),
+                );
+            }
+            // the exec builtin goes on running the shell
+            for signal in IGNORED {
+                let _ = sys::set_signal(signal, Disposition::Ignore);
+            }
+            err
+        }
+    }
+
+    // Start the command in a new process without forking the shell, in the
+    // process group `pgid` or a new one when it's 0, or in the shell's with
+    // None. Much cheaper than a fork
+    // but nothing can be done in the child before the exec, so it's only for
+    // commands that don't need their fds or the terminal set up
+    pub fn spawn(&self, pgid: Option<pid_t>) -> io::Result<pid_t> {
+        unsafe {
+            let mut attr = mem::zeroed();
+            let err = libc::posix_spawnattr_init(&mut attr);
+            if err != 0 {
+                return Err(io::Error::from_raw_os_error(err));
+            }
+            let mut signals = mem::zeroed();
+            libc::sigemptyset(&mut signals);
+            for signal in IGNORED {
+                libc::sigaddset(&mut signals, signal.number());
+            }
+            libc::posix_spawnattr_setsigdefault(&mut attr, &signals);
+            let mut flags = libc::POSIX_SPAWN_SETSIGDEF;
+            if let Some(pgid) = pgid {
+                libc::posix_spawnattr_setpgroup(&mut attr, pgid);
+                flags |= libc::POSIX_SPAWN_SETPGROUP;
+            }
+            libc::posix_spawnattr_setflags(&mut attr, flags as libc::c_short);
+
+            let mut pid = 0;
+            let mut err = libc::posix_spawn(
+                &mut pid,
+                self.path.as_ptr(),
+                ptr::null(),
+                &attr,
+                self.argv_ptrs.as_ptr().cast(),
+                self.env_ptrs.as_ptr().cast(),
+            );
+            if err == libc::ENOEXEC && looks_like_text(&self.path) {
+                err = libc::posix_spawn(
+                    &mut pid,
+                    self.script_argv[0].as_ptr(),
+                    ptr::null(),
+                    &attr,
+                    self.script_argv_ptrs.as_ptr().cast(),
+                    self.env_ptrs.as_ptr().cast(),
+                );
+            }
+            libc::posix_spawnattr_destroy(&mut attr);
+            if err != 0 {
+                return Err(io::Error::from_raw_os_error(err));
+            }
+            Ok(pid)
+        }
+    }
+}
+
+// Check for NUL bytes at the start of the file, binaries for other
+// architectures have them and scripts don't. Only uses syscalls so it's
+// safe to call between fork and exec
+fn looks_like_text(path: &CStr) -> bool {
+    unsafe {
+        let fd = sys::retry(|| libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC));
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 128];
+        let n = sys::retry(|| libc::read(fd, buf.as_mut_ptr().cast(), buf.len()));
+        libc::close(fd);
+        n >= 0 && !buf[..n as usize].contains(&0)
+    }
+}
+
+// Print the commands with names close to a command that wasn't found
+fn suggest_commands(command: &str, builtins: &Builtins, path_cache: &mut PathCache) {
+    let candidates = builtins.names().chain(path_cache.names());
+    let matches = suggest::similar(command, candidates);
+    if !matches.is_empty() {
+        eprintln!("Did you mean: {}?", matches.join(", "));
+    }
+}
+
+// Take a `timeout [-k grace] duration` prefix off the words of a command.
+// Returns the time limit and grace period, None when the limit is 0
+fn timeout_prefix(words: &mut Vec<Cow<str>>) -> Result<Option<(Duration, Duration)>, String> {
+    let mut grace = timeout::DEFAULT_GRACE;
+    let mut i = 1;
+    if words.get(i).is_some_and(|word| word == "-k") {
+        let text = words.get(i + 1).ok_or("-k: option requires an argument")?;
+        grace = timeout::parse_duration(text)
+            .ok_or_else(|| format!("{}: invalid time interval", text))?;
+        i += 2;
+    }
+    let text = words.get(i).ok_or("missing operand")?;
+    let limit =
+        timeout::parse_duration(text).ok_or_else(|| format!("{}: invalid time interval", text))?;
+    if words.len() == i + 1 {
+        return Err("missing command".to_string());
+    }
+    words.drain(..=i);
+    Ok((!limit.is_zero()).then_some((limit, grace)))
+}
+
+// What a forked process of a job does before it runs anything: go in its
+// process group, take the terminal `terminal` for a job in the foreground and
+// ignore hangups for a nohup one. Only does what's safe between fork and exec
+#[derive(Clone, Copy)]
+struct Setup {
+    group: Option<pid_t>,
+    take_terminal: bool,
+    nohup: bool,
+}
+
+impl Setup {
+    fn apply(self, terminal: c_int) {
+        if self.nohup {
+            let _ = sys::set_signal(Signal::Hup, Disposition::Ignore);
+        }
+        if let Some(pgid) = self.group {
+            let _ = sys::set_process_group(0, pgid);
+        }
+        if self.take_terminal {
+            let _ = sys::set_foreground_pgrp(terminal, sys::process_group());
+        }
+    }
+}
+
+// The file nohup appends what would go to the terminal to: nohup.out, or
+// ~/nohup.out when that can't be written. It says so like nohup(1) does
+fn nohup_output() -> Result<PathBuf, ShellError> {
+    let mut paths = vec![PathBuf::from("nohup.out")];
+    if let Some(home) = env::var_os("HOME") {
+        paths.push(Path::new(&home).join("nohup.out"));
+    }
+    let mut error = None;
+    for path in paths {
+        let opened = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o600)
+            .open(&path);
+        match opened {
+            Ok(_) => {
+                eprintln!("nohup: appending output to '{}'", path.display());
+                return Ok(path);
+            }
+            Err(source) => error = Some(ShellError::Redirect { path, source }),
+        }
+    }
+    Err(error.unwrap())
+}
+
+// Where a stage of a nohup pipeline that would use the terminal gets pointed
+// instead: the first reads /dev/null, the last writes `output` and all of
+// them write their errors there
+fn nohup_redirections(
+    nohup: bool,
+    first: bool,
+    last: bool,
+    output: Option<&Path>,
+) -> Result<Vec<Redirection>, ShellError> {
+    let mut redirections = Vec::new();
+    if !nohup {
+        return Ok(redirections);
+    }
+    if first && sys::is_terminal(STDIN_FILENO) {
+        let null = OsStr::new("/dev/null");
+        redirections.push(redirect::open(STDIN_FILENO, RedirectOp::Read, null, &[])?);
+    }
+    if let Some(output) = output {
+        for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            if sys::is_terminal(fd) && (last || fd == libc::STDERR_FILENO) {
+                let output = output.as_os_str();
+                redirections.push(redirect::open(fd, RedirectOp::Append, output, &[])?);
+            }
+        }
+    }
+    Ok(redirections)
+}
+
+// user and system CPU time of the children the shell has waited for so far
+fn children_times() -> (Duration, Duration) {
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    unsafe {
+        libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage);
+    }
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    (time(usage.ru_utime), time(usage.ru_stime))
+}
+
+// Show how long a foreground pipeline took if that's more than $ISH_REPORTTIME
+// seconds, like zsh's REPORTTIME
+fn report_time(line: &str, started: Instant, times_before: (Duration, Duration)) {
+    let elapsed = started.elapsed();
+    let threshold = env::var("ISH_REPORTTIME")
+        .ok()
+        .and_then(|seconds| seconds.trim().parse::<f64>().ok())
+        .filter(|seconds| *seconds > 0.0);
+    if threshold.is_none_or(|seconds| elapsed.as_secs_f64() < seconds) {
+        return;
+    }
+    let (user, system) = children_times();
+    let user = user.saturating_sub(times_before.0);
+    let system = system.saturating_sub(times_before.1);
+    let cpu = (user + system).as_secs_f64() / elapsed.as_secs_f64() * 100.0;
+    // in one piece, jobs finishing in the background print with stdout locked
+    let _stdout = stdout().lock();
+    eprintln!(
+        "{}  {:.2}s user {:.2}s system {:.0}% cpu {:.3} total",
+        line.trim(),
+        user.as_secs_f64(),
+        system.as_secs_f64(),
+        cpu,
+        elapsed.as_secs_f64()
+    );
+}
+
+// With xtrace a command is shown like `+ echo 'a b'` before it runs, words
+// quoted when they have to be to be typed back in
+fn print_xtrace(words: &[OsString]) {
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| parser::quote(&word.to_string_lossy()).into_owned())
+        .collect();
+    eprintln!("+ {}", words.join(" "));
+}
+
+// How running a line ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExitStatus {
+    // with the status it left in $?
+    Done(i32),
+    // with the shell to exit with this status, from `exit` or from an exec
+    // that failed in a script
+    Exit(i32),
+}
+
+// Read end of a pipe that has nothing in it, for a pipeline stage whose input
+// never started
+fn closed_pipe() -> Option<OwnedFd> {
+    sys::pipe().ok().map(|(read, _)| read)
+}
+
+impl Shell {
+    // Whether `words` are only the name of a directory, which autocd goes
+    // into. Never when a command by that name would run instead
+    fn is_autocd(&mut self, words: &[OsString]) -> bool {
+        let [word] = words else {
+            return false;
+        };
+        if !self.interactive || !self.options.autocd || self.options.posix {
+            return false;
+        }
+        if !word.as_bytes().contains(&b'/') {
+            let builtin = word
+                .to_str()
+                .is_some_and(|name| self.builtins.contains(name));
+            if builtin || self.path_cache.find(word).is_some() {
+                return false;
+            }
+        }
+        Path::new(word).is_dir()
+    }
+
+    // Fork a subshell that runs a builtin with `stdio` in place of the
+    // shell's stdin and stdout and then `redirections`, set up like a command
+    // of the pipeline is. Returns its pid
+    fn spawn_subshell(
+        &mut self,
+        command: &str,
+        args: &[OsString],
+        stdio: (Option<OwnedFd>, Option<OwnedFd>),
+        redirections: &[Redirection],
+        setup: Setup,
+    ) -> io::Result<pid_t> {
+        // hold the locks the monitor thread takes while forking, so the child
+        // doesn't start with one of them taken by a thread it doesn't have
+        let _ = io::stdout().flush();
+        let jobs = self.jobs.lock();
+        let out = io::stdout().lock();
+        let pid = unsafe { libc::fork() };
+        drop(out);
+        drop(jobs);
+        if pid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if pid > 0 {
+            return Ok(pid);
+        }
+
+        setup.apply(self.shell_terminal);
+        for signal in [Signal::Int, Signal::Tstp, Signal::Ttou, Signal::Ttin] {
+            let _ = sys::set_signal(signal, Disposition::Default);
+        }
+        unsafe {
+            if let Some(fd) = &stdio.0 {
+                sys::retry(|| libc::dup2(fd.as_raw_fd(), STDIN_FILENO));
+            }
+            if let Some(fd) = &stdio.1 {
+                sys::retry(|| libc::dup2(fd.as_raw_fd(), libc::STDOUT_FILENO));
+            }
+        }
+        drop(stdio);
+        if let Err(e) = redirect::apply(redirections) {
+            self.report(ShellError::io(e));
+            exit_shell(1);
+        }
+        // the subshell is never interactive, there is no job control in it
+        self.interactive = false;
+        self.job_control = false;
+        let status = self.run_builtin(command, args).unwrap_or(self.last_status);
+        exit_shell(status)
+    }
+
+    // the fields a word of a command expands to, split like sh does in posix mode
+    fn expand(&self, word: &str) -> Result<Vec<OsString>, ShellError> {
+        let parameters = Parameters {
+            last_status: self.last_status,
+            positional: &self.positional,
+            line_number: self.line_number,
+            last_argument: &self.last_argument,
+            extglob: self.options.extglob,
+            nocasematch: self.options.nocasematch,
+            named_dirs: &self.named_dirs,
+        };
+        let fields = if self.options.posix {
+            expand_fields(word, &parameters)?
+        } else {
+            expand_word(word, &parameters)?
+        };
+        trace!("expand", "{:?} -> {:?}", word, fields);
+        Ok(fields)
+    }
+
+    // Open the redirections of a command, up to the first one that fails
+    fn open_redirections(&self, redirects: &[Redirect]) -> Result<Vec<Redirection>, ShellError> {
+        let mut redirections = Vec::new();
+        for redirect in redirects {
+            let mut targets = self.expand(&redirect.target)?;
+            let output = matches!(redirect.op, RedirectOp::Write | RedirectOp::Append);
+            let opened = match (targets.pop(), targets.is_empty()) {
+                (Some(target), true) if output && self.restricted => Err(ShellError::Restricted {
+                    what: Path::new(&target).display().to_string(),
+                    reason: Some("cannot redirect output"),
+                }),
+                (Some(target), true) => {
+                    trace!("redirect", "{} {:?} {:?}", redirect.fd, redirect.op, target);
+                    redirect::open(redirect.fd, redirect.op, &target, &redirections)
+                }
+                _ => Err(ShellError::AmbiguousRedirect(redirect.target.to_string())),
+            };
+            redirections.push(opened?);
+        }
+        Ok(redirections)
+    }
+
+    // Run one line of input. An error that ends the line is returned without
+    // being shown, its status is left in $?
+    pub fn run_line(&mut self, line: &str) -> Result<ExitStatus, ShellError> {
+        match self.execute(line) {
+            Ok(Some(status)) => Ok(ExitStatus::Exit(status)),
+            Ok(None) => Ok(ExitStatus::Done(self.last_status)),
+            Err(e) => {
+                self.last_status = e.status();
+                Err(e)
+            }
+        }
+    }
+
+    // The work of run_line. Errors that only end a stage of the pipeline are
+    // reported as they happen. Returns the exit status if the shell should exit
+    fn execute(&mut self, line: &str) -> Result<Option<i32>, ShellError> {
+        let mut pipeline = parser::parse(line)?;
+        let wait = !pipeline.background;
+
+        // `nohup` in front of a pipeline keeps its commands running when the
+        // terminal hangs up, and their input and output away from it
+        let mut nohup = false;
+        if let Some(words) = pipeline.commands.first_mut().map(|c| &mut c.words) {
+            if words.first().is_some_and(|word| word == "nohup") && !self.options.posix {
+                if words.len() == 1 {
+                    return Err(ShellError::Usage {
+                        builtin: "nohup",
+                        message: "missing command".to_string(),
+                    });
+                }
+                words.remove(0);
+                nohup = true;
+            }
+        }
+        // a restricted shell can't write nohup.out
+        let mut nohup_out = None;
+        if nohup && (sys::is_terminal(libc::STDOUT_FILENO) || sys::is_terminal(libc::STDERR_FILENO))
+        {
+            if self.restricted {
+                return Err(ShellError::Restricted {
+                    what: "nohup".to_string(),
+                    reason: Some("cannot redirect output"),
+                });
+            }
+            nohup_out = Some(nohup_output()?);
+        }
+
+        // `timeout duration` in front of a pipeline puts a time limit on all of it
+        let mut time_limit = None;
+        if let Some(words) = pipeline.commands.first_mut().map(|c| &mut c.words) {
+            if words.first().is_some_and(|word| word == "timeout") && !self.options.posix {
+                time_limit = timeout_prefix(words).map_err(ShellError::Timeout)?;
+            }
+        }
+
+        // for $ISH_REPORTTIME
+        let started = Instant::now();
+        let times_before = children_times();
+
+        // processes of the pipeline, all of them are put in the process group of the first one.
+        // Without job control a foreground pipeline stays in the shell's
+        // group, so it gets Ctrl-C and can read the terminal like the shell
+        // does. One with a time limit still gets a group, like timeout(1)
+        // gives it one, for everything it started to be killed with it
+        let grouped = self.job_control || !wait || time_limit.is_some();
+        let mut pgid: pid_t = 0;
+        let mut pids: Vec<pid_t> = Vec::new();
+        let mut previous_stdout: Option<OwnedFd> = None;
+        // whether the status of the pipeline comes from waiting on its last process
+        let mut last_spawned = false;
+
+        // builtins that aren't the whole foreground pipeline run in a
+        // subshell, so their output can go down the pipe and their side
+        // effects stay out of the shell
+        let subshell = pipeline.commands.len() > 1 || !wait;
+
+        // stdout and stderr for a background job whose output doesn't go
+        // straight to the terminal
+        let mut job_number = 0;
+        let mut job_output = None;
+        let mut job_fds: Option<(OwnedFd, OwnedFd)> = None;
+        if !wait && (self.options.bgcapture || self.options.bgtag) {
+            job_number = next_job_number(&self.jobs.lock());
+            if self.options.bgcapture {
+                match capture_file(job_number).and_then(|file| {
+                    let out = OwnedFd::from(file.try_clone()?);
+                    Ok((file, out.try_clone()?, out))
+                }) {
+                    Ok((file, out, err)) => {
+                        job_output = Some(file);
+                        job_fds = Some((out, err));
+                    }
+                    Err(e) => self.warn(ShellError::Io {
+                        context: Some("can't capture output"),
+                        source: e,
+                    }),
+                }
+            } else {
+                match sys::pipe().and_then(|out| Ok((out, sys::pipe()?))) {
+                    Ok(((out_read, out_write), (err_read, err_write))) => {
+                        tag_output(job_number, out_read, false);
+                        tag_output(job_number, err_read, true);
+                        job_fds = Some((out_write, err_write));
+                    }
+                    Err(e) => self.warn(ShellError::Io {
+                        context: Some("can't tag output"),
+                        source: e,
+                    }),
+                }
+            }
+        }
+
+        // the last word of the last command, for $_ once the pipeline ran
+        let mut last_argument = None;
+
+        // loop through each command
+        for (i, stage) in pipeline.commands.iter().enumerate() {
+            let last_stage = i == pipeline.commands.len() - 1;
+            let expanded: Result<Vec<_>, _> =
+                stage.words.iter().map(|word| self.expand(word)).collect();
+            let mut words: Vec<OsString> = match expanded {
+                Ok(words) => words.into_iter().flatten().collect(),
+                Err(e) => {
+                    self.report(e);
+                    previous_stdout = if last_stage { None } else { closed_pipe() };
+                    continue;
+                }
+            };
+            last_argument = words.last().cloned();
+            if self.is_autocd(&words) {
+                words.insert(0, "cd".into());
+            }
+            let command = words.first().map(OsString::as_os_str).unwrap_or_default();
+            let args = words.get(1..).unwrap_or_default();
+            let builtin = command
+                .to_str()
+                .filter(|name| name.is_empty() || self.builtins.contains(name));
+            last_spawned = false;
+            let take_terminal = wait && self.job_control;
+            trace!("command", "{:?}, builtin: {}", words, builtin.is_some());
+            if self.options.xtrace {
+                print_xtrace(&words);
+            }
+            if self.restricted && command.as_bytes().contains(&b'/') {
+                self.report(ShellError::Restricted {
+                    what: Path::new(command).display().to_string(),
+                    reason: Some("cannot specify `/' in command names"),
+                });
+                previous_stdout = if last_stage { None } else { closed_pipe() };
+                continue;
+            }
+
+            let opened = match nohup_redirections(nohup, i == 0, last_stage, nohup_out.as_deref())
+                .and_then(|nohup| Ok((nohup, self.open_redirections(&stage.redirects)?)))
+            {
+                Ok(opened) => opened,
+                Err(e) => {
+                    self.report(e);
+                    // the next stage gets an empty input instead of the terminal
+                    previous_stdout = if last_stage { None } else { closed_pipe() };
+                    continue;
+                }
+            };
+            let (mut redirections, opened) = opened;
+            // the job's own redirections come after, so they still win
+            if let Some((out, err)) = &job_fds {
+                if let Ok(err) = err.try_clone() {
+                    redirections.push(redirect::to(libc::STDERR_FILENO, err));
+                }
+                if let Some(out) = last_stage.then(|| out.try_clone().ok()).flatten() {
+                    redirections.push(redirect::to(libc::STDOUT_FILENO, out));
+                }
+            }
+            redirections.extend(opened);
+
+            if let Some(builtin) = builtin {
+                if !subshell || builtin.is_empty() {
+                    previous_stdout = None;
+                    let saved = match redirect::redirect_shell(&redirections) {
+                        Ok(saved) => saved,
+                        Err(e) => {
+                            self.report(ShellError::io(e));
+                            continue;
+                        }
+                    };
+                    let exit = self.run_builtin(builtin, args);
+                    // `exec` without a command redirects the shell for good
+                    if builtin == "exec" && args.is_empty() {
+                        saved.keep();
+                    } else {
+                        drop(saved);
+                    }
+                    if exit.is_some() {
+                        return Ok(exit);
+                    }
+                    continue;
+                }
+                let stdin = previous_stdout.take();
+                let (stdout, next_stdin) = if last_stage {
+                    (None, None)
+                } else {
+                    match sys::pipe() {
+                        Ok((read, write)) => (Some(write), Some(read)),
+                        Err(e) => {
+                            self.report(ShellError::io(e));
+                            continue;
+                        }
+                    }
+                };
+                let subshell = self.spawn_subshell(
+                    builtin,
+                    args,
+                    (stdin, stdout),
+                    &redirections,
+                    Setup {
+                        group: grouped.then_some(pgid),
+                        take_terminal,
+                        nohup,
+                    },
+                );
+                match subshell {
+                    Ok(pid) => {
+                        if grouped {
+                            if pgid == 0 {
+                                pgid = pid;
+                            }
+                            let _ = sys::set_process_group(pid, pgid);
+                        }
+                        trace!("spawn", "subshell pid {} in group {}", pid, pgid);
+                        previous_stdout = next_stdin;
+                        pids.push(pid);
+                        last_spawned = true;
+                    }
+                    Err(e) => {
+                        self.report(ShellError::spawn(command, e));
+                        abandon_pipeline(pgid, &pids);
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            // a command on its own that doesn't need anything set up in
+            // the child is started without forking the shell
+            let simple =
+                pipeline.commands.len() == 1 && redirections.is_empty() && !take_terminal && !nohup;
+
+            let stdin = previous_stdout.take().map_or(Stdio::inherit(), Stdio::from);
+            let stdout = if last_stage {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            };
+
+            let shell_terminal = self.shell_terminal;
+            let group = grouped.then_some(pgid);
+            let setup = Setup {
+                group,
+                take_terminal,
+                nohup,
+            };
+            let spawned = ExecArgs::new(command, args, &self.shell_exe, &mut self.path_cache)
+                .and_then(|exec_args| {
+                    if simple {
+                        return exec_args.spawn(group).map(|pid| (pid, None));
+                    }
+                    // the Command owns the parent's copies of the stage's fds,
+                    // they are closed when it goes out of scope after the spawn.
+                    // It's only used to fork and set up the fds, the exec is done
+                    // by hand so files without a #! line can be run by ish
+                    let mut process = Command::new(OsStr::from_bytes(exec_args.path().to_bytes()));
+                    process.stdin(stdin).stdout(stdout);
+                    unsafe {
+                        process.pre_exec(move || {
+                            setup.apply(shell_terminal);
+                            redirect::apply(&redirections)?;
+                            Err(exec_args.exec())
+                        });
+                    }
+                    let mut child = process.spawn()?;
+                    Ok((child.id() as pid_t, child.stdout.take().map(OwnedFd::from)))
+                });
+            match spawned {
+                Ok((pid, stdout)) => {
+                    // also set it from the parent so it's in place before we
+                    // wait. It fails when the child already did the exec, it's
+                    // in the group by then
+                    if grouped {
+                        if pgid == 0 {
+                            pgid = pid;
+                        }
+                        let _ = sys::set_process_group(pid, pgid);
+                    }
+                    trace!("spawn", "pid {} in group {}, simple: {}", pid, pgid, simple);
+                    previous_stdout = stdout;
+                    pids.push(pid);
+                    last_spawned = true;
+                }
+                Err(e) => {
+                    // the child may have taken the terminal before its exec failed
+                    if take_terminal {
+                        let _ = sys::set_foreground_pgrp(shell_terminal, sys::process_group());
+                    }
+                    self.report(ShellError::spawn(command, e));
+                    // scripts don't want the noise
+                    if let Some(command) = command.to_str().filter(|c| !c.contains('/')) {
+                        if self.last_status == 127 && self.interactive && self.options.suggest {
+                            suggest_commands(command, &self.builtins, &mut self.path_cache);
+                        }
+                    }
+                    abandon_pipeline(pgid, &pids);
+                    break;
+                }
+            }
+        }
+
+        if let Some(argument) = last_argument {
+            self.last_argument = argument;
+        }
+        // the job has its own copies, the tagging threads only see the end of
+        // the output once these are closed
+        drop(job_fds);
+        if pids.is_empty() {
+            return Ok(None);
+        }
+        let mut job = Job::new(pgid, pids);
+        job.number = job_number;
+        job.output = job_output;
+        job.timeout = time_limit.map(|(limit, grace)| Timeout::start(pgid, limit, grace));
+        if wait {
+            // block until the pipeline has finished or is stopped
+            let status = wait_for_job(&mut job, false, self.foreground_terminal());
+            if last_spawned {
+                self.last_status = status;
+                job.report_signal();
+            }
+            if job.state == JobState::Stopped {
+                self.jobs.add_stopped(job);
+            } else {
+                report_time(line, started, times_before);
+            }
+        } else {
+            self.jobs.add(job);
+            self.last_status = 0;
+        }
+        Ok(None)
+    }
+}
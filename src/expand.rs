@@ -0,0 +1,540 @@
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    iter::Peekable,
+    mem,
+    ops::Range,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    str::Chars,
+};
+
+use crate::{
+    error::ShellError,
+    named_dirs::{self, NamedDirs},
+    pattern::{boundaries, char_count, Anchor, Pattern},
+    sys,
+};
+
+// How a word is expanded: to itself, split into fields, or to a pattern
+// where what was quoted is escaped so it only matches itself
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Word,
+    Fields,
+    Pattern,
+}
+
+// What the special and positional parameters of a word are expanded from
+pub struct Parameters<'a> {
+    pub last_status: i32,
+    // $0 followed by the arguments
+    pub positional: &'a [OsString],
+    // of the line being run, for $LINENO
+    pub line_number: usize,
+    // the last word of the command before, for $_
+    pub last_argument: &'a OsStr,
+    // whether patterns take extglob's groups
+    pub extglob: bool,
+    // whether ${1/pattern/with} matches letters in either case
+    pub nocasematch: bool,
+    // what ~name stands for
+    pub named_dirs: &'a NamedDirs,
+}
+
+// the parameters with a name, other names are left as they are since ish
+// has no variables
+const NAMED: &[&str] = &["LINENO", "_"];
+
+// Value of a special or positional parameter. Unknown or unset parameters
+// expand to nothing
+fn parameter(name: &str, parameters: &Parameters) -> OsString {
+    let Parameters {
+        last_status,
+        positional,
+        line_number,
+        last_argument,
+        ..
+    } = parameters;
+    match name {
+        "LINENO" => line_number.to_string().into(),
+        "_" => last_argument.to_os_string(),
+        "?" => last_status.to_string().into(),
+        "#" => (positional.len() - 1).to_string().into(),
+        "@" | "*" => positional[1..].join(OsStr::new(" ")),
+        _ => match name.parse::<usize>() {
+            Ok(n) => positional.get(n).cloned().unwrap_or_default(),
+            Err(_) => OsString::new(),
+        },
+    }
+}
+
+// Expand the special and positional parameters in a word and remove its
+// quotes. A word that is only $@ or "$@" expands to one word per argument,
+// and an unquoted word that expands to nothing is dropped. Parameters can hold
+// any bytes, so words aren't necessarily UTF-8 once they are expanded.
+// A ${...} ish doesn't have and a $(<file) that can't be read are errors
+pub fn expand_word(word: &str, parameters: &Parameters) -> Result<Vec<OsString>, ShellError> {
+    expand(word, parameters, Mode::Word)
+}
+
+// Like expand_word, with the values of unquoted parameters split into fields
+// at spaces, tabs and newlines the way sh does it
+pub fn expand_fields(word: &str, parameters: &Parameters) -> Result<Vec<OsString>, ShellError> {
+    expand(word, parameters, Mode::Fields)
+}
+
+fn expand(word: &str, parameters: &Parameters, mode: Mode) -> Result<Vec<OsString>, ShellError> {
+    let split = mode == Mode::Fields;
+    // unquoted, sh splits the arguments too
+    if (word == "$@" && !split) || word == "\"$@\"" {
+        return Ok(parameters.positional[1..].to_vec());
+    }
+    // and ${@...} makes a word of each argument it's done to
+    let inner = match word
+        .strip_prefix('"')
+        .and_then(|word| word.strip_suffix('"'))
+    {
+        Some(inner) => Some(inner),
+        None => (!split).then_some(word),
+    };
+    if let Some(braced) = inner.and_then(|inner| inner.strip_prefix("${@")) {
+        let mut chars = braced.chars().peekable();
+        if let Some(text) = braces(&mut chars).filter(|_| chars.next().is_none()) {
+            return substitute(&format!("@{}", text), parameters);
+        }
+    }
+
+    // the fields before the one being expanded, when it's split
+    let mut fields = Vec::new();
+    let mut expanded = OsString::new();
+    let mut quoted = false;
+    let mut double_quoted = false;
+    // quoted text stands for itself in a pattern
+    let literal = |expanded: &mut OsString, text: &str| {
+        if mode == Mode::Pattern {
+            for c in text.chars() {
+                expanded.push("\\");
+                expanded.push(c.encode_utf8(&mut [0; 4]));
+            }
+        } else {
+            expanded.push(text);
+        }
+    };
+    let mut chars = word.chars().peekable();
+    // a ~ at the start is the home directory, ~name a named directory or the
+    // home of a user. One that's none of them stays as it is
+    if let Some(rest) = word.strip_prefix('~') {
+        let name = rest.split('/').next().unwrap_or_default();
+        if let Some(dir) = tilde(name, parameters.named_dirs) {
+            if mode == Mode::Pattern {
+                literal(&mut expanded, &dir.to_string_lossy());
+            } else {
+                expanded.push(dir);
+            }
+            chars.nth(name.chars().count());
+        }
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                // inside double quotes a backslash only escapes what's special there
+                Some(c) if double_quoted && !matches!(c, '$' | '"' | '\\') => {
+                    literal(&mut expanded, "\\");
+                    literal(&mut expanded, c.encode_utf8(&mut [0; 4]));
+                }
+                Some(c) => {
+                    quoted = true;
+                    literal(&mut expanded, c.encode_utf8(&mut [0; 4]));
+                }
+                None => literal(&mut expanded, "\\"),
+            },
+            '\'' if !double_quoted => {
+                quoted = true;
+                let text: String = chars.by_ref().take_while(|&c| c != '\'').collect();
+                literal(&mut expanded, &text);
+            }
+            '"' => {
+                quoted = true;
+                double_quoted = !double_quoted;
+            }
+            '$' => {
+                let value = match chars.peek() {
+                    // ${name} and the ${name#pattern} kinds
+                    Some('{') => {
+                        chars.next();
+                        // one that isn't closed goes on to the end of the word
+                        let rest: String = chars.clone().collect();
+                        let Some(text) = braces(&mut chars) else {
+                            return Err(ShellError::BadSubstitution(format!("${{{}", rest)));
+                        };
+                        substitute(&text, parameters)?.join(OsStr::new(" "))
+                    }
+                    // $(<file) is what's in the file, there's no other
+                    // command substitution
+                    Some('(') => {
+                        let mut after = chars.clone();
+                        after.next();
+                        let text = parentheses(&mut after);
+                        let Some(path) = text.trim_start().strip_prefix('<') else {
+                            expanded.push("$");
+                            continue;
+                        };
+                        chars = after;
+                        contents(path.trim(), parameters)?
+                    }
+                    // single character names, multi digit positionals need braces
+                    Some(&name)
+                        if matches!(name, '?' | '#' | '@' | '*') || name.is_ascii_digit() =>
+                    {
+                        chars.next();
+                        parameter(&name.to_string(), parameters)
+                    }
+                    Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+                        let name: String = (chars.clone())
+                            .take_while(|&c| c.is_ascii_alphanumeric() || c == '_')
+                            .collect();
+                        if !NAMED.contains(&name.as_str()) {
+                            expanded.push("$");
+                            continue;
+                        }
+                        chars.nth(name.len() - 1);
+                        parameter(&name, parameters)
+                    }
+                    _ => {
+                        expanded.push("$");
+                        continue;
+                    }
+                };
+                if split && !double_quoted {
+                    split_into(&mut fields, &mut expanded, &mut quoted, &value);
+                } else if double_quoted && mode == Mode::Pattern {
+                    literal(&mut expanded, &value.to_string_lossy());
+                } else {
+                    expanded.push(value);
+                }
+            }
+            _ if double_quoted => literal(&mut expanded, c.encode_utf8(&mut [0; 4])),
+            _ => expanded.push(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+    if !expanded.is_empty() || quoted {
+        fields.push(expanded);
+    }
+    Ok(fields)
+}
+
+// The directory ~name stands for, $HOME for a ~ on its own
+pub fn tilde(name: &str, named_dirs: &NamedDirs) -> Option<OsString> {
+    if name.is_empty() {
+        return env::var_os("HOME").filter(|home| !home.is_empty());
+    }
+    if !named_dirs::is_name(name) {
+        return None;
+    }
+    match named_dirs.get(name) {
+        Some(dir) => Some(dir.as_os_str().to_os_string()),
+        None => sys::user_home(name),
+    }
+}
+
+// What's between the braces of a ${...}, read up to the } that closes it.
+// Braces of a ${...} inside it and escaped ones don't close it. None when
+// nothing closes it
+fn braces(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut text = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '}' if depth == 0 => return Some(text),
+            '}' => depth -= 1,
+            '{' if text.ends_with('$') => depth += 1,
+            '\\' => {
+                text.push(c);
+                match chars.next() {
+                    Some(c) => text.push(c),
+                    None => break,
+                }
+                continue;
+            }
+            _ => {}
+        }
+        text.push(c);
+    }
+    None
+}
+
+// What's between the parentheses of a $(...), read up to the ) that closes
+// it
+fn parentheses(chars: &mut Peekable<Chars>) -> String {
+    let mut text = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            '(' => depth += 1,
+            '\\' => {
+                text.push(c);
+                match chars.next() {
+                    Some(c) => text.push(c),
+                    None => break,
+                }
+                continue;
+            }
+            _ => {}
+        }
+        text.push(c);
+    }
+    text
+}
+
+// What's in the file of a $(<file) without the newlines at its end
+fn contents(path: &str, parameters: &Parameters) -> Result<OsString, ShellError> {
+    let path = expand(path, parameters, Mode::Word)?.join(OsStr::new(" "));
+    match fs::read(&path) {
+        Ok(mut contents) => {
+            let len = contents.len() - contents.iter().rev().take_while(|&&c| c == b'\n').count();
+            contents.truncate(len);
+            Ok(OsString::from_vec(contents))
+        }
+        Err(source) => Err(ShellError::Redirect {
+            path: path.into(),
+            source,
+        }),
+    }
+}
+
+// what's done to the value of a ${name...}
+enum Operation {
+    Value,
+    // ${name#pattern} and ${name##pattern} strip a start, ${name%pattern}
+    // and ${name%%pattern} an end
+    Strip {
+        pattern: Pattern,
+        suffix: bool,
+        longest: bool,
+    },
+    // ${name/pattern/with}, ${name//pattern/with} for every match
+    Replace {
+        pattern: Pattern,
+        with: Vec<u8>,
+        all: bool,
+        anchor: Anchor,
+    },
+    // ${name:offset} and ${name:offset:length}, in characters. Done to $@
+    // it's the arguments that are sliced, $0 being the one at 0
+    Slice {
+        offset: i64,
+        length: Option<i64>,
+    },
+}
+
+impl Operation {
+    fn apply(&self, value: &[u8]) -> Vec<u8> {
+        match self {
+            Operation::Value => value.to_vec(),
+            Operation::Strip {
+                pattern,
+                suffix: false,
+                longest,
+            } => pattern.strip_prefix(value, *longest).to_vec(),
+            Operation::Strip {
+                pattern,
+                suffix: true,
+                longest,
+            } => pattern.strip_suffix(value, *longest).to_vec(),
+            Operation::Replace {
+                pattern,
+                with,
+                all,
+                anchor,
+            } => pattern.replace(value, with, *all, *anchor),
+            Operation::Slice { offset, length } => {
+                let bounds = boundaries(value);
+                let range = slice(bounds.len() - 1, *offset, *length);
+                value[bounds[range.start]..bounds[range.end]].to_vec()
+            }
+        }
+    }
+}
+
+// whether ${#name} is the length of a parameter
+fn is_name(name: &str) -> bool {
+    matches!(name, "?" | "#" | "@" | "*")
+        || (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+// The values of a ${...} with `text` between the braces, one for each
+// argument of ${@...} and ${*...}
+fn substitute(text: &str, parameters: &Parameters) -> Result<Vec<OsString>, ShellError> {
+    let positional = parameters.positional;
+    // ${#@} counts the arguments like $# does
+    if let Some(name) = text.strip_prefix('#').filter(|name| is_name(name)) {
+        let length = match name {
+            "@" | "*" => positional.len() - 1,
+            _ => char_count(parameter(name, parameters).as_bytes()),
+        };
+        return Ok(vec![length.to_string().into()]);
+    }
+    let name_len = match text.chars().next() {
+        Some('?' | '#' | '@' | '*') => 1,
+        _ => text
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(text.len()),
+    };
+    let (name, operation) = text.split_at(name_len);
+    let bad = || ShellError::BadSubstitution(format!("${{{}}}", text));
+    if name.is_empty() {
+        return Err(bad());
+    }
+    let operation = operation_of(operation, parameters)?.ok_or_else(bad)?;
+    let values = match (name, &operation) {
+        ("@" | "*", Operation::Slice { offset, length }) => {
+            let range = slice(positional.len(), *offset, *length);
+            return Ok(positional[range].to_vec());
+        }
+        ("@" | "*", _) => positional[1..].to_vec(),
+        _ => vec![parameter(name, parameters)],
+    };
+    Ok(values
+        .iter()
+        .map(|value| OsString::from_vec(operation.apply(value.as_bytes())))
+        .collect())
+}
+
+// The operation written after the name in a ${...}, None for one ish
+// doesn't have. The patterns and what replaces them are expanded first
+fn operation_of(text: &str, parameters: &Parameters) -> Result<Option<Operation>, ShellError> {
+    let pattern = |text: &str| -> Result<Pattern, ShellError> {
+        let expanded = expand(text, parameters, Mode::Pattern)?;
+        let text = expanded.join(OsStr::new(" "));
+        Ok(Pattern::new(&text.to_string_lossy(), parameters.extglob))
+    };
+    let strip = |suffix: bool, pattern_text: &str, longest: bool| {
+        Ok::<_, ShellError>(Operation::Strip {
+            pattern: pattern(pattern_text)?,
+            suffix,
+            longest,
+        })
+    };
+    let operation = match text.chars().next() {
+        None => Operation::Value,
+        Some(c @ ('#' | '%')) => {
+            let suffix = c == '%';
+            match text[1..].strip_prefix(c) {
+                Some(rest) => strip(suffix, rest, true)?,
+                None => strip(suffix, &text[1..], false)?,
+            }
+        }
+        Some('/') => {
+            let rest = &text[1..];
+            let (all, anchor, rest) = match rest.chars().next() {
+                Some('/') => (true, Anchor::None, &rest[1..]),
+                Some('#') => (false, Anchor::Start, &rest[1..]),
+                Some('%') => (false, Anchor::End, &rest[1..]),
+                _ => (false, Anchor::None, rest),
+            };
+            let (pattern_text, with) = split_replacement(rest);
+            let with = expand(with, parameters, Mode::Word)?;
+            let mut pattern = pattern(pattern_text)?;
+            if parameters.nocasematch {
+                pattern = pattern.ignore_case();
+            }
+            Operation::Replace {
+                pattern,
+                with: with.join(OsStr::new(" ")).into_vec(),
+                all,
+                anchor,
+            }
+        }
+        // ${name:-word} and the like aren't slices, a negative offset needs
+        // a space or parentheses before it
+        Some(':') if !text[1..].starts_with(['-', '=', '?', '+']) => {
+            let (offset, length) = match text[1..].split_once(':') {
+                Some((offset, length)) => (offset, Some(length)),
+                None => (&text[1..], None),
+            };
+            let number = |text: &str| -> Result<Option<i64>, ShellError> {
+                let expanded = expand(text, parameters, Mode::Word)?;
+                let expanded = expanded.join(OsStr::new(" "));
+                let number = expanded.to_str().map(str::trim).and_then(|number| {
+                    match number.strip_prefix('(') {
+                        Some(number) => number.strip_suffix(')').map(str::trim),
+                        None => Some(number),
+                    }
+                });
+                Ok(match number {
+                    Some("") => Some(0),
+                    Some(number) => number.parse::<i64>().ok(),
+                    None => None,
+                })
+            };
+            let Some(offset) = number(offset)? else {
+                return Ok(None);
+            };
+            let length = match length {
+                Some(length) => match number(length)? {
+                    Some(length) => Some(length),
+                    None => return Ok(None),
+                },
+                None => None,
+            };
+            Operation::Slice { offset, length }
+        }
+        Some(_) => return Ok(None),
+    };
+    Ok(Some(operation))
+}
+
+// The part of `len` things a ${name:offset:length} takes. A negative offset
+// counts from the end and a negative length leaves that many off the end,
+// what goes past either end stops at it
+fn slice(len: usize, offset: i64, length: Option<i64>) -> Range<usize> {
+    let len = len as i64;
+    let start = if offset < 0 { len + offset } else { offset }.clamp(0, len);
+    let end = match length {
+        None => len,
+        Some(length) if length < 0 => len + length,
+        Some(length) => start.saturating_add(length),
+    };
+    start as usize..end.clamp(start, len) as usize
+}
+
+// the pattern and what replaces it in a ${name/pattern/with}, split at the
+// first / that isn't escaped or quoted
+fn split_replacement(text: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                chars.next();
+            }
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('/', None) => return (&text[..i], &text[i + 1..]),
+            _ => {}
+        }
+    }
+    (text, "")
+}
+
+// Add the value of an unquoted parameter to the field being expanded. Where
+// it has whitespace the field ends and the next one starts, a field left
+// empty is dropped unless it had quotes
+fn split_into(fields: &mut Vec<OsString>, field: &mut OsString, quoted: &mut bool, value: &OsStr) {
+    let mut pieces = value
+        .as_bytes()
+        .split(|b| matches!(b, b' ' | b'\t' | b'\n'));
+    if let Some(first) = pieces.next() {
+        field.push(OsStr::from_bytes(first));
+    }
+    for piece in pieces {
+        if !field.is_empty() || *quoted {
+            fields.push(mem::take(field));
+            *quoted = false;
+        }
+        field.push(OsStr::from_bytes(piece));
+    }
+}
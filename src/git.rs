@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// the dirty check doesn't look at more files than this, in a bigger
+// repository the prompt only shows the branch
+const DIRTY_CHECK_LIMIT: usize = 20_000;
+
+// a file in the index, with what git knew about it when it was added
+struct Entry {
+    // relative to the work tree
+    path: PathBuf,
+    mtime: (i64, i64),
+    size: u32,
+}
+
+// What the prompt shows about the git repository the shell is in, found by
+// reading the files in .git without running git. Finding the repository and
+// its HEAD only takes a few small reads. The index, which can be big, is read
+// again only when it changes
+#[derive(Default)]
+pub struct Git {
+    // files of each index read so far, with its modification time
+    indexes: HashMap<PathBuf, (SystemTime, Option<Vec<Entry>>)>,
+}
+
+// the work tree and the git directory of the repository `dir` is in
+fn find_repository(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for dir in dir.ancestors() {
+        let dot_git = dir.join(".git");
+        let Ok(meta) = fs::metadata(&dot_git) else {
+            continue;
+        };
+        if meta.is_dir() {
+            return Some((dir.to_path_buf(), dot_git));
+        }
+        // in worktrees and submodules it's a file saying where it is
+        let text = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = text.strip_prefix("gitdir:")?.trim();
+        return Some((dir.to_path_buf(), dir.join(git_dir)));
+    }
+    None
+}
+
+// Number at the start of `data` in the encoding of version 4 indexes, returns
+// it with the number of bytes it took
+fn varint(data: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, &byte) in data.iter().enumerate() {
+        if i > 0 {
+            value = value.checked_add(1)?.checked_mul(128)?;
+        }
+        value += (byte & 0x7f) as usize;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+// The files in an index of version 2, 3 or 4 of a repository with sha1 ids.
+// The ones git is told not to look at in the work tree are left out
+fn parse_index(data: &[u8]) -> Option<Vec<Entry>> {
+    let u16_at = |i: usize| Some(u16::from_be_bytes(data.get(i..i + 2)?.try_into().ok()?));
+    let u32_at = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?));
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = u32_at(4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let mut entries = Vec::new();
+    let mut name = Vec::new();
+    let mut at = 12;
+    for _ in 0..u32_at(8)? {
+        let mtime = (u32_at(at + 8)? as i64, u32_at(at + 12)? as i64);
+        let mode = u32_at(at + 24)?;
+        let size = u32_at(at + 36)?;
+        let flags = u16_at(at + 60)?;
+        let mut header = 62;
+        // assume-valid, and skip-worktree in the extended flags
+        let mut ignored = flags & 0x8000 != 0;
+        if flags & 0x4000 != 0 && version >= 3 {
+            ignored |= u16_at(at + 62)? & 0x4000 != 0;
+            header = 64;
+        }
+        let start = at + header;
+        if version == 4 {
+            // the name is the one before with some bytes cut off its end
+            // and others added
+            let (cut, len) = varint(data.get(start..)?)?;
+            name.truncate(name.len().checked_sub(cut)?);
+            let rest = data.get(start + len..)?;
+            let end = rest.iter().position(|&b| b == 0)?;
+            name.extend_from_slice(&rest[..end]);
+            at = start + len + end + 1;
+        } else {
+            let rest = data.get(start..)?;
+            let end = rest.iter().position(|&b| b == 0)?;
+            name = rest[..end].to_vec();
+            // padded with NULs to a multiple of 8 bytes
+            at += (header + end + 8) & !7;
+        }
+        // submodules are directories in the work tree
+        if ignored || mode & 0o170000 == 0o160000 {
+            continue;
+        }
+        entries.push(Entry {
+            path: PathBuf::from(OsStr::from_bytes(&name)),
+            mtime,
+            size,
+        });
+    }
+    Some(entries)
+}
+
+impl Git {
+    // The branch checked out in the repository `dir` is in, or the start of
+    // the commit when it's detached, with a * after it when files were
+    // changed since they were last added. None outside a repository
+    pub fn status(&mut self, dir: &Path) -> Option<String> {
+        let (work_tree, git_dir) = find_repository(dir)?;
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        let mut status = match head.strip_prefix("ref:") {
+            Some(name) => {
+                let name = name.trim();
+                name.strip_prefix("refs/heads/").unwrap_or(name).to_string()
+            }
+            None => head.chars().take(7).collect(),
+        };
+        if self.is_dirty(&work_tree, &git_dir) {
+            status.push('*');
+        }
+        Some(status)
+    }
+
+    // Whether a file in the work tree isn't the size or from the time the
+    // index has for it, the quick check git does itself before it looks at
+    // what's in the file
+    fn is_dirty(&mut self, work_tree: &Path, git_dir: &Path) -> bool {
+        let index = git_dir.join("index");
+        let Ok(modified) = fs::metadata(&index).and_then(|meta| meta.modified()) else {
+            return false;
+        };
+        let current = matches!(self.indexes.get(&index), Some((time, _)) if *time == modified);
+        if !current {
+            let entries = fs::read(&index).ok().and_then(|data| parse_index(&data));
+            self.indexes.insert(index.clone(), (modified, entries));
+        }
+        let Some((_, Some(entries))) = self.indexes.get(&index) else {
+            return false;
+        };
+        if entries.len() > DIRTY_CHECK_LIMIT {
+            return false;
+        }
+        entries.iter().any(
+            |entry| match fs::symlink_metadata(work_tree.join(&entry.path)) {
+                Ok(meta) => {
+                    meta.size() as u32 != entry.size
+                        || (meta.mtime(), meta.mtime_nsec()) != entry.mtime
+                }
+                Err(_) => true,
+            },
+        )
+    }
+}
@@ -0,0 +1,167 @@
+use std::env;
+
+use crate::parser::is_operator;
+
+// what a character of the command line is part of, for its color
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Style {
+    Plain,
+    Command,
+    UnknownCommand,
+    Quoted,
+    Operator,
+    // a quote that isn't closed, to the end of the line
+    Unclosed,
+}
+
+// The style of each character of `line`. `is_command` says whether a
+// command name can be run
+pub fn highlight(line: &[char], mut is_command: impl FnMut(&str) -> bool) -> Vec<Style> {
+    let mut styles = vec![Style::Plain; line.len()];
+    // whether the next word is a command name, and whether it's the target
+    // of a redirection
+    let mut command_next = true;
+    let mut target_next = false;
+    let mut i = 0;
+    while i < line.len() {
+        let c = line[i];
+        if c.is_whitespace() {
+            // a command line of several lines has a command on each
+            command_next |= c == '\n' && !(i > 0 && line[i - 1] == '\\');
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < line.len() && line[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if is_operator(c) {
+            let start = i;
+            i += 1;
+            if matches!(c, '<' | '>') {
+                while i < line.len() && matches!(line[i], '>' | '&') {
+                    i += 1;
+                }
+                target_next = true;
+            } else {
+                command_next = true;
+            }
+            styles[start..i].fill(Style::Operator);
+            continue;
+        }
+        let start = i;
+        let mut word = String::new();
+        while i < line.len() && !line[i].is_whitespace() && !is_operator(line[i]) {
+            match line[i] {
+                '\\' => {
+                    word.extend(line.get(i + 1));
+                    i += 2;
+                }
+                quote @ ('\'' | '"') => {
+                    let open = i;
+                    i += 1;
+                    while i < line.len() && line[i] != quote {
+                        if quote == '"' && line[i] == '\\' {
+                            i += 1;
+                        }
+                        word.extend(line.get(i));
+                        i += 1;
+                    }
+                    if i >= line.len() {
+                        styles[open..].fill(Style::Unclosed);
+                        return styles;
+                    }
+                    i += 1;
+                    styles[open..i].fill(Style::Quoted);
+                }
+                c => {
+                    word.push(c);
+                    i += 1;
+                }
+            }
+        }
+        i = i.min(line.len());
+        // the file descriptor of a redirection like 2>
+        let fd = line[start..i].iter().all(|c| c.is_ascii_digit());
+        if fd && matches!(line.get(i), Some('<' | '>')) {
+            styles[start..i].fill(Style::Operator);
+        } else if target_next {
+            target_next = false;
+        } else if command_next {
+            command_next = false;
+            let style = if is_command(&word) {
+                Style::Command
+            } else {
+                Style::UnknownCommand
+            };
+            for s in &mut styles[start..i] {
+                if *s == Style::Plain {
+                    *s = style;
+                }
+            }
+        }
+    }
+    styles
+}
+
+// SGR parameters for each style but Plain. They can be changed with
+// $ISH_HIGHLIGHT, like command=1;32:quoted=35
+fn colors() -> [(Style, String); 5] {
+    let mut colors = [
+        (Style::Command, "32".to_string()),
+        (Style::UnknownCommand, "31".to_string()),
+        (Style::Quoted, "33".to_string()),
+        (Style::Operator, "36".to_string()),
+        (Style::Unclosed, "1;31".to_string()),
+    ];
+    let setting = env::var("ISH_HIGHLIGHT").unwrap_or_default();
+    for (name, code) in setting.split(':').filter_map(|part| part.split_once('=')) {
+        let style = match name {
+            "command" => Style::Command,
+            "unknown" => Style::UnknownCommand,
+            "quoted" => Style::Quoted,
+            "operator" => Style::Operator,
+            "unclosed" => Style::Unclosed,
+            _ => continue,
+        };
+        if let Some(color) = colors.iter_mut().find(|(s, _)| *s == style) {
+            color.1 = code.to_string();
+        }
+    }
+    colors
+}
+
+// `text` with the escape sequences for `styles` in it, one style for each
+// character, and `newline` for its newlines. Plain when the styles don't fit
+pub fn paint(text: &str, styles: &[Style], newline: &str) -> String {
+    let plain = styles.len() != text.chars().count();
+    let colors = colors();
+    let mut out = String::new();
+    let mut current = Style::Plain;
+    for (i, c) in text.chars().enumerate() {
+        let style = if plain || c == '\n' {
+            Style::Plain
+        } else {
+            styles[i]
+        };
+        if style != current {
+            if current != Style::Plain {
+                out.push_str("\x1b[0m");
+            }
+            if let Some((_, code)) = colors.iter().find(|(s, _)| *s == style) {
+                out.push_str(&format!("\x1b[{}m", code));
+            }
+            current = style;
+        }
+        match c {
+            '\n' => out.push_str(newline),
+            c => out.push(c),
+        }
+    }
+    if current != Style::Plain {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
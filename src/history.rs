@@ -0,0 +1,415 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+    os::{fd::AsRawFd, unix::fs::MetadataExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::sys;
+
+// default for both $HISTSIZE and $HISTFILESIZE
+pub const DEFAULT_SIZE: usize = 500;
+
+// Commands entered in interactive shells. Every entry is appended to the
+// history file as soon as it's added, so a crashed session loses nothing.
+// Sessions that run at the same time take turns with the file under flock(2)
+// and can merge in what the others appended to it
+pub struct History {
+    entries: VecDeque<Entry>,
+    // number of entries dropped from the front to stay under max_entries, so
+    // entry numbers don't change as old entries go away
+    dropped: usize,
+    // $HISTSIZE, entries kept in memory
+    max_entries: usize,
+    // file the history is loaded from and saved to
+    file: Option<PathBuf>,
+    // inode of the file and how much of it is in memory, what other sessions
+    // append after that is merged in
+    read: Option<(u64, u64)>,
+    // entries saved after ones of other sessions that aren't merged yet, so
+    // merging skips them
+    unmerged: Vec<Entry>,
+}
+
+// a command and when it was entered, in seconds since the epoch. Entries from
+// files saved without times have none
+#[derive(Clone, PartialEq, Eq)]
+struct Entry {
+    text: String,
+    time: Option<i64>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            entries: VecDeque::new(),
+            dropped: 0,
+            max_entries: DEFAULT_SIZE,
+            file: None,
+            read: None,
+            unmerged: Vec::new(),
+        }
+    }
+}
+
+// One entry per line in the file, backslashes and newlines are escaped so
+// multi-line commands round-trip
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => entry.push('\n'),
+                Some(c) => entry.push(c),
+                None => entry.push('\\'),
+            }
+        } else {
+            entry.push(c);
+        }
+    }
+    entry
+}
+
+// Entries in `data` from a history file, with the number of bytes they take.
+// Like bash does it, a line like #1700000000 before an entry is the time it
+// was entered. A line without its newline yet is left for later
+fn parse_entries(data: &[u8]) -> (Vec<Entry>, usize) {
+    let mut entries = Vec::new();
+    let mut time = None;
+    let (mut at, mut used) = (0, 0);
+    while let Some(end) = data[at..].iter().position(|&b| b == b'\n') {
+        let line = String::from_utf8_lossy(&data[at..at + end]);
+        at += end + 1;
+        let digits = line.strip_prefix('#').unwrap_or_default();
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            time = digits.parse().ok();
+            continue;
+        }
+        entries.push(Entry {
+            text: unescape(&line),
+            time: time.take(),
+        });
+        used = at;
+    }
+    (entries, used)
+}
+
+// Open `path` with `options` and wait for a lock on it, exclusive or shared.
+// When another session replaced the file in the meantime the new one is
+// opened, a lock on the old one keeps nothing out
+fn lock(path: &Path, options: &OpenOptions, exclusive: bool) -> io::Result<File> {
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    loop {
+        let file = options.open(path)?;
+        if sys::retry(|| unsafe { libc::flock(file.as_raw_fd(), operation) }) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let inode = file.metadata()?.ino();
+        if fs::metadata(path).is_ok_and(|meta| meta.ino() == inode) {
+            return Ok(file);
+        }
+    }
+}
+
+// an entry as it's written to the file, with the line of its time first
+fn saved(entry: &Entry) -> String {
+    match entry.time {
+        Some(time) => format!("#{}\n{}\n", time, escape(&entry.text)),
+        None => format!("{}\n", escape(&entry.text)),
+    }
+}
+
+// Replace the contents of `file` with `entries`, with the file locked. They're
+// written next to it and moved into place, so the history file is never seen
+// half written. Returns the inode and size of the new file
+fn write_entries(file: &Path, entries: &[Entry]) -> io::Result<(u64, u64)> {
+    let mut temporary = file.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    let contents: String = entries.iter().map(saved).collect();
+    fs::write(&temporary, &contents)?;
+    let inode = fs::metadata(&temporary)?.ino();
+    fs::rename(&temporary, file)?;
+    Ok((inode, contents.len() as u64))
+}
+
+impl History {
+    // Load the history saved in `file`, a missing file is an empty history.
+    // A file with more than `max_file_entries` entries is cut down to the
+    // newest ones, that's what keeps it from growing forever
+    pub fn load(file: PathBuf, max_entries: usize, max_file_entries: usize) -> io::Result<History> {
+        let mut history = History {
+            max_entries,
+            ..History::default()
+        };
+        let mut f = match lock(&file, OpenOptions::new().read(true), true) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                history.file = Some(file);
+                return Ok(history);
+            }
+            Err(e) => return Err(e),
+        };
+        let mut data = Vec::new();
+        f.read_to_end(&mut data)?;
+        let (mut entries, used) = parse_entries(&data);
+        let mut read = (f.metadata()?.ino(), used as u64);
+        // the last line is all there is of it when the file is locked
+        if used < data.len() {
+            entries.extend(parse_entries(&[&data[used..], b"\n"].concat()).0);
+            read.1 = data.len() as u64;
+        }
+        if entries.len() > max_file_entries {
+            entries.drain(..entries.len() - max_file_entries);
+            read = write_entries(&file, &entries)?;
+        }
+
+        let skip = entries.len().saturating_sub(max_entries);
+        entries.drain(..skip);
+        history.entries = entries.into();
+        history.file = Some(file);
+        history.read = Some(read);
+        Ok(history)
+    }
+
+    // add an entry to the ones in memory, the oldest go when there are too many
+    fn push(&mut self, entry: Entry) {
+        self.entries.push_back(entry);
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    // Add an entry and append it to the history file, unless it's the same as
+    // the previous one. If saving fails the history stops being saved so the
+    // error is only reported once
+    pub fn add(&mut self, entry: &str) -> io::Result<()> {
+        if self.entries.back().map(|last| last.text.as_str()) == Some(entry) {
+            return Ok(());
+        }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH);
+        let entry = Entry {
+            text: entry.to_string(),
+            time: time.ok().map(|time| time.as_secs() as i64),
+        };
+        self.push(entry.clone());
+
+        let Some(path) = self.file.clone() else {
+            return Ok(());
+        };
+        let line = saved(&entry);
+        let options = OpenOptions::new().create(true).append(true).clone();
+        let saved = lock(&path, &options, true).and_then(|mut file| {
+            let start = (file.metadata()?.ino(), file.metadata()?.len());
+            file.write_all(line.as_bytes())?;
+            // the file is in memory up to the end again, unless other
+            // sessions appended to it since it was last read
+            if self.read.unwrap_or((start.0, 0)) == start {
+                self.read = Some((start.0, start.1 + line.len() as u64));
+            } else {
+                self.unmerged.push(entry);
+            }
+            Ok(())
+        });
+        if saved.is_err() {
+            self.file = None;
+        }
+        saved
+    }
+
+    // Add the entries other sessions appended to the history file since it
+    // was last read, so sessions running at the same time share what's typed
+    // in them. The entries of this session aren't added again, and neither
+    // are ones that are the same as the entry before them
+    pub fn merge(&mut self) -> io::Result<()> {
+        let Some(path) = self.file.clone() else {
+            return Ok(());
+        };
+        match lock(&path, OpenOptions::new().read(true), false) {
+            Ok(mut file) => self.merge_from(&mut file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // merge the history file `file`, which is locked
+    fn merge_from(&mut self, file: &mut File) -> io::Result<()> {
+        let meta = file.metadata()?;
+        // a file that was trimmed or rewritten by another session is read
+        // from the start, what's older than the newest entry is already here
+        let (start, newest) = match self.read {
+            Some((inode, read)) if inode == meta.ino() && read <= meta.len() => (read, None),
+            _ => (0, self.entries.iter().filter_map(|entry| entry.time).max()),
+        };
+        file.seek(SeekFrom::Start(start))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let (entries, used) = parse_entries(&data);
+        for entry in entries {
+            if newest.is_some_and(|newest| entry.time.is_none_or(|time| time <= newest)) {
+                continue;
+            }
+            if let Some(i) = self.unmerged.iter().position(|own| *own == entry) {
+                self.unmerged.remove(i);
+                continue;
+            }
+            if self
+                .entries
+                .back()
+                .is_some_and(|last| last.text == entry.text)
+            {
+                continue;
+            }
+            self.push(entry);
+        }
+        if newest.is_some() {
+            self.unmerged.clear();
+        }
+        self.read = Some((meta.ino(), start + used as u64));
+        Ok(())
+    }
+
+    pub fn file(&self) -> Option<&PathBuf> {
+        self.file.as_ref()
+    }
+
+    // Forget the entries with the numbers in `numbers`, the ones after them
+    // move up. They're also taken out of the history file, where the newest
+    // entry entered at the same time with the same text is the same one
+    pub fn delete(&mut self, numbers: RangeInclusive<usize>) -> io::Result<()> {
+        let start = numbers.start().saturating_sub(self.dropped + 1);
+        let end = (numbers.end() + 1).saturating_sub(self.dropped + 1);
+        let end = end.min(self.entries.len());
+        if start >= end {
+            return Ok(());
+        }
+        let Some(path) = self.file.clone() else {
+            self.entries.drain(start..end);
+            return Ok(());
+        };
+        let mut file = match lock(&path, OpenOptions::new().read(true), true) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.entries.drain(start..end);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        // the file is written again without them, what other sessions
+        // appended is taken in first so it isn't skipped later
+        let deleted: Vec<Entry> = self.entries.drain(start..end).collect();
+        self.merge_from(&mut file)?;
+        let (mut saved, _) = parse_entries(&fs::read(&path)?);
+        for entry in deleted.iter().rev() {
+            let found = saved.iter().rposition(|saved| {
+                saved.text == entry.text && (entry.time.is_none() || saved.time == entry.time)
+            });
+            if let Some(i) = found {
+                saved.remove(i);
+            }
+        }
+        self.read = Some(write_entries(&path, &saved)?);
+        Ok(())
+    }
+
+    // number the next entry will get
+    fn next_number(&self) -> usize {
+        self.dropped + self.entries.len() + 1
+    }
+
+    // numbers of the entries that are kept, oldest first
+    pub fn numbers(&self) -> std::ops::Range<usize> {
+        self.dropped + 1..self.next_number()
+    }
+
+    // the entry with the given number, if it's still kept
+    pub fn get(&self, number: usize) -> Option<&str> {
+        let index = number.checked_sub(self.dropped + 1)?;
+        self.entries.get(index).map(|entry| entry.text.as_str())
+    }
+
+    // when the entry with the given number was entered, in seconds since the
+    // epoch
+    pub fn time(&self, number: usize) -> Option<i64> {
+        let index = number.checked_sub(self.dropped + 1)?;
+        self.entries.get(index)?.time
+    }
+
+    // entries with their numbers, oldest first
+    pub fn numbered(&self) -> impl DoubleEndedIterator<Item = (usize, &str)> + ExactSizeIterator {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (self.dropped + i + 1, entry.text.as_str()))
+    }
+
+    // Expand the history references in a line: `!!` is the previous entry,
+    // `!n` entry number n and `!-n` the nth previous entry. Returns the line if
+    // anything was expanded, or the reference that doesn't match an entry
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        if !line.contains('!') {
+            return Ok(None);
+        }
+
+        let mut expanded = String::with_capacity(line.len());
+        let mut changed = false;
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c != '!' {
+                expanded.push(c);
+                continue;
+            }
+
+            let number = match chars.peek() {
+                Some((_, '!')) => {
+                    chars.next();
+                    self.next_number().checked_sub(1)
+                }
+                Some((_, c)) if c.is_ascii_digit() || *c == '-' => {
+                    let relative = *c == '-';
+                    if relative {
+                        chars.next();
+                    }
+                    let mut digits = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    match digits.parse::<usize>() {
+                        Ok(n) if relative => self.next_number().checked_sub(n),
+                        Ok(n) => Some(n),
+                        // a lone `!-` isn't a reference
+                        Err(_) => {
+                            expanded.push_str("!-");
+                            continue;
+                        }
+                    }
+                }
+                // anything else is a literal !
+                _ => {
+                    expanded.push(c);
+                    continue;
+                }
+            };
+
+            let end = chars.peek().map_or(line.len(), |(i, _)| *i);
+            match number.and_then(|n| self.get(n)) {
+                Some(entry) => expanded.push_str(entry),
+                None => return Err(line[start..end].to_string()),
+            }
+            changed = true;
+        }
+        Ok(changed.then_some(expanded))
+    }
+}
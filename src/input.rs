@@ -0,0 +1,151 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::atomic::AtomicBool,
+};
+
+use libc::STDIN_FILENO;
+
+use crate::{
+    builtins::Builtins, compspec::Specs, history::History, keymap::Keymaps, path_cache::PathCache,
+    prompt, sys::Termios,
+};
+
+// Set by the SIGINT handler. A read at the prompt fails with EINTR when a
+// signal arrives, this tells Ctrl-C apart from the other signals
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Set by the SIGWINCH handler, the line editor draws the line again
+pub static RESIZED: AtomicBool = AtomicBool::new(false);
+
+// What the shell gives the line editor to work with
+pub struct Context<'a> {
+    pub history: &'a mut History,
+    // entries other sessions saved are merged in before going through them
+    pub share_history: bool,
+    // Up and Down only recall entries that start with what was typed
+    pub history_prefix: bool,
+    // the newest history entry starting with the line is suggested
+    pub autosuggest: bool,
+    // the command line is colored
+    pub highlight: bool,
+    // what the keys do
+    pub keymaps: &'a Keymaps,
+    // the modes the shell keeps the terminal in, so they needn't be read
+    // again for each line. None without job control
+    pub terminal_modes: Option<Termios>,
+    // the keys work like in vi instead of emacs
+    pub vi: bool,
+    // what the complete builtin set up for the arguments of commands
+    pub completions: &'a Specs,
+    // for completing and coloring command names
+    pub builtins: &'a Builtins,
+    pub path_cache: &'a mut PathCache,
+    // shown in front of the lines after the first of a command
+    pub continuation_prompt: String,
+    // shown at the right end of the row while there's room for it
+    pub right_prompt: String,
+    // numbers of the jobs in the job table
+    pub jobs: Vec<usize>,
+    // what the line starts out with, a command that came back from $EDITOR
+    pub line: String,
+    // set by the line editor when the line it returns is to be edited in
+    // $VISUAL or $EDITOR instead of run
+    pub edit: bool,
+}
+
+// Where the shell reads its commands from, a line at a time
+pub trait ReadLine {
+    // like BufRead::read_line, returns 0 at the end of the input
+    fn read_line(&mut self, line: &mut String, context: &mut Context) -> io::Result<usize>;
+
+    // show the prompt for the next line
+    fn show_prompt(&mut self, prompt: &str) {
+        print!("{}", prompt::printed(prompt));
+        let _ = io::stdout().flush();
+    }
+
+    // Ctrl-C was pressed while reading a line, the line is thrown away
+    fn interrupted(&mut self) {
+        println!("^C");
+    }
+}
+
+impl<T: BufRead + ?Sized> ReadLine for T {
+    fn read_line(&mut self, line: &mut String, _: &mut Context) -> io::Result<usize> {
+        // a line that isn't UTF-8 is still run, the bad bytes are replaced
+        let mut bytes = Vec::new();
+        let n = self.read_until(b'\n', &mut bytes)?;
+        line.push_str(&String::from_utf8_lossy(&bytes));
+        Ok(n)
+    }
+}
+
+// Reads the commands the user types on stdin with plain read(2) calls. A read
+// interrupted by a signal fails with ErrorKind::Interrupted instead of being
+// retried like BufRead does, so the shell can react to Ctrl-C at the prompt.
+// Used when stdin isn't a terminal the line editor can drive
+pub struct Terminal {
+    // input read past the end of the last line
+    buf: Vec<u8>,
+    // on a terminal ^D only ends the input on an empty line, and reading
+    // again after it works
+    tty: bool,
+}
+
+impl Terminal {
+    pub fn new() -> Terminal {
+        Terminal {
+            buf: Vec::new(),
+            tty: unsafe { libc::isatty(STDIN_FILENO) } == 1,
+        }
+    }
+}
+
+impl Default for Terminal {
+    fn default() -> Terminal {
+        Terminal::new()
+    }
+}
+
+impl ReadLine for Terminal {
+    fn read_line(&mut self, line: &mut String, _: &mut Context) -> io::Result<usize> {
+        loop {
+            let end = match self.buf.iter().position(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None => {
+                    let mut chunk = [0u8; 4096];
+                    let n =
+                        unsafe { libc::read(STDIN_FILENO, chunk.as_mut_ptr().cast(), chunk.len()) };
+                    if n < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    if n > 0 {
+                        self.buf.extend_from_slice(&chunk[..n as usize]);
+                        continue;
+                    }
+                    // ^D in the middle of a line hands over what was typed so
+                    // far, the line goes on until it's ended with enter
+                    if self.tty && !self.buf.is_empty() {
+                        continue;
+                    }
+                    // a last line without a newline still counts
+                    self.buf.len()
+                }
+            };
+            let rest = self.buf.split_off(end);
+            let bytes = std::mem::replace(&mut self.buf, rest);
+            line.push_str(&String::from_utf8_lossy(&bytes));
+            return Ok(bytes.len());
+        }
+    }
+
+    fn interrupted(&mut self) {
+        self.buf.clear();
+        // a terminal in canonical mode already shows the ^C
+        if Termios::current(STDIN_FILENO).is_ok_and(|modes| modes.echoes_control()) {
+            println!();
+        } else {
+            println!("^C");
+        }
+    }
+}
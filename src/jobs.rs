@@ -0,0 +1,469 @@
+use std::{
+    ffi::CStr,
+    fs::{self, File},
+    io::{self, stdout, BufRead, BufReader, Write},
+    os::{fd::OwnedFd, unix::fs::FileExt},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use libc::{c_int, pid_t, SIGINT, WCONTINUED, WNOHANG, WUNTRACED};
+
+use crate::{
+    input::INTERRUPTED,
+    sys::{self, Signal, Termios},
+    timeout::Timeout,
+};
+
+// state of a job as last reported by waitpid
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done,
+}
+
+impl JobState {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobState::Running => "Running",
+            JobState::Stopped => "Stopped",
+            JobState::Done => "Done",
+        }
+    }
+}
+
+// a pipeline started by the shell. Only pids are kept so the shell never holds
+// on to the pipe ends of its children, and the state is only ever changed from
+// waitpid results
+pub struct Job {
+    // number the user refers to the job by, given when it's added to the table
+    pub number: usize,
+    // 0 for a pipeline that was left in the shell's process group
+    pub pgid: pid_t,
+    // processes of the job that haven't been reaped yet
+    pub pids: Vec<pid_t>,
+    // last process of the pipeline, its status is the status of the job
+    pub last_pid: pid_t,
+    // raw wait status of the last process
+    pub status: c_int,
+    pub state: JobState,
+    // file the output of the job goes to when it's captured
+    pub output: Option<File>,
+    // time limit of the job while it runs, and whether it was killed for
+    // going over it
+    pub timeout: Option<Timeout>,
+    pub timed_out: bool,
+}
+
+impl Job {
+    pub fn new(pgid: pid_t, pids: Vec<pid_t>) -> Job {
+        trace!("job", "{} started with pids {:?}", pgid, pids);
+        Job {
+            number: 0,
+            pgid,
+            last_pid: *pids.last().unwrap(),
+            pids,
+            status: 0,
+            state: JobState::Running,
+            output: None,
+            timeout: None,
+            timed_out: false,
+        }
+    }
+
+    // The time limit of a job only runs while the job does, it's kept on hold
+    // while the job is stopped. A job that finished leaves it behind
+    fn settle_timeout(&mut self) {
+        match self.state {
+            JobState::Done => {
+                if let Some(timeout) = self.timeout.take() {
+                    self.timed_out = timeout.cancel();
+                }
+            }
+            state => {
+                if let Some(timeout) = &self.timeout {
+                    timeout.pause(state == JobState::Stopped);
+                }
+            }
+        }
+    }
+
+    // show what the job wrote to its capture file, once it's done
+    pub fn finish_output(&self) {
+        if let Some(Ok(output)) = self.output.as_ref().map(captured) {
+            let _ = stdout().lock().write_all(&output);
+        }
+    }
+
+    fn set_state(&mut self, state: JobState) {
+        if state != self.state {
+            trace!(
+                "job",
+                "{} {} -> {}",
+                self.pgid,
+                self.state.name(),
+                state.name()
+            );
+        }
+        self.state = state;
+    }
+
+    // record a status reported by waitpid for one of the job's processes
+    fn update(&mut self, pid: pid_t, status: c_int) {
+        trace!(
+            "wait",
+            "pid {} of job {}, status {:#x}",
+            pid,
+            self.pgid,
+            status
+        );
+        if libc::WIFSTOPPED(status) {
+            self.set_state(JobState::Stopped);
+            self.status = status;
+        } else if libc::WIFCONTINUED(status) {
+            self.set_state(JobState::Running);
+        } else {
+            if pid == self.last_pid {
+                self.status = status;
+            }
+            self.pids.retain(|&p| p != pid);
+            if self.pids.is_empty() {
+                self.set_state(JobState::Done);
+            }
+        }
+    }
+
+    // tell the user if the job was killed by a signal, when it finished in
+    // the foreground
+    pub fn report_signal(&self) {
+        if self.state == JobState::Done && !self.timed_out {
+            if let Some(message) = signal_message(self.status) {
+                eprintln!("{}", message);
+            }
+        }
+    }
+
+    // The value of $? for the job once it's done. Like timeout(1), a job
+    // killed for going over its time limit exits with 124
+    pub fn exit_status(&self) -> i32 {
+        if self.timed_out {
+            124
+        } else {
+            exit_status(self.status)
+        }
+    }
+
+    // drop a process that can't be waited for anymore
+    fn forget(&mut self, pid: pid_t) {
+        self.pids.retain(|&p| p != pid);
+        if self.pids.is_empty() {
+            self.set_state(JobState::Done);
+        }
+    }
+}
+
+// Convert a raw wait status to the value of $?: the exit code, or 128 plus
+// the number of the signal that killed or stopped the process
+fn exit_status(status: c_int) -> i32 {
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else if libc::WIFSTOPPED(status) {
+        128 + libc::WSTOPSIG(status)
+    } else {
+        0
+    }
+}
+
+// What to tell the user about a process that was killed by a signal, like
+// "Segmentation fault (core dumped)". Nothing for SIGINT and SIGPIPE, the
+// user pressed Ctrl-C or a later stage of a pipeline stopped reading
+fn signal_message(status: c_int) -> Option<String> {
+    if !libc::WIFSIGNALED(status) {
+        return None;
+    }
+    let signal = libc::WTERMSIG(status);
+    if signal == SIGINT || signal == libc::SIGPIPE {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(libc::strsignal(signal)) };
+    let mut message = name.to_string_lossy().into_owned();
+    if libc::WCOREDUMP(status) {
+        message.push_str(" (core dumped)");
+    }
+    Some(message)
+}
+
+// The background and stopped jobs. A thread keeps their states up to date
+// and takes them out of the table when they're done, also while the shell
+// waits for input
+pub struct Jobs {
+    table: Arc<Mutex<Vec<Job>>>,
+    // while the wait builtin runs the jobs that are done are left for it
+    waiting: Arc<AtomicBool>,
+}
+
+// what waiting for a background job came to
+pub enum Waited {
+    // it's done and out of the table
+    Finished(Job),
+    // none of the jobs waited for is running
+    NoJobs,
+    // Ctrl-C
+    Interrupted,
+}
+
+impl Jobs {
+    // an empty table, with the thread watching it started
+    pub fn start() -> Jobs {
+        let table = Arc::new(Mutex::new(Vec::new()));
+        let waiting = Arc::new(AtomicBool::new(false));
+        let watched = Arc::clone(&table);
+        let waited = Arc::clone(&waiting);
+        sys::spawn_thread(move || loop {
+            // wait a bit between checks
+            thread::sleep(Duration::from_millis(100));
+            let mut jobs = watched.lock().unwrap_or_else(PoisonError::into_inner);
+            if !waited.load(Ordering::SeqCst) {
+                reap_jobs(&mut jobs);
+            }
+        });
+        Jobs { table, waiting }
+    }
+
+    // the table stays usable even if a thread panicked holding it, a job in
+    // it is still a process to wait for
+    pub fn lock(&self) -> MutexGuard<'_, Vec<Job>> {
+        self.table.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Add a job to the table, numbering it if it doesn't have a number yet
+    pub fn add(&self, mut job: Job) {
+        let mut jobs = self.lock();
+        if job.number == 0 {
+            job.number = next_job_number(&jobs);
+        }
+        jobs.push(job);
+    }
+
+    // Add a job that was stopped in the foreground, telling the user the
+    // number it's known by from now on
+    pub fn add_stopped(&self, mut job: Job) {
+        let mut jobs = self.lock();
+        if job.number == 0 {
+            job.number = next_job_number(&jobs);
+        }
+        println!("[{}] Stopped", job.number);
+        jobs.push(job);
+    }
+
+    // report the jobs that finished and take them out
+    pub fn reap(&self) {
+        reap_jobs(&mut self.lock());
+    }
+
+    // Wait for one of the jobs that are `wanted` to be done, and take it out
+    // of the table without reporting it. One that was done before and
+    // hasn't been reported yet counts too
+    pub fn wait_for_any(&self, wanted: impl Fn(&Job) -> bool) -> Waited {
+        // set with the table locked, so the thread can't be halfway through
+        // reaping the jobs
+        let jobs = self.lock();
+        self.waiting.store(true, Ordering::SeqCst);
+        drop(jobs);
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        let waited = loop {
+            let mut jobs = self.lock();
+            update_jobs(&mut jobs);
+            if let Some(i) = jobs
+                .iter()
+                .position(|job| job.state == JobState::Done && wanted(job))
+            {
+                break Waited::Finished(jobs.remove(i));
+            }
+            if !jobs
+                .iter()
+                .any(|job| job.state == JobState::Running && wanted(job))
+            {
+                break Waited::NoJobs;
+            }
+            drop(jobs);
+            if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                break Waited::Interrupted;
+            }
+            // Sleep until a child changes state, or Ctrl-C interrupts the
+            // wait. All the shell's children are in the table while it waits,
+            // the status goes to the job the child belongs to
+            if let Ok(Some((pid, status))) = sys::wait_child(WUNTRACED | WCONTINUED) {
+                let mut jobs = self.lock();
+                if let Some(job) = jobs.iter_mut().find(|job| job.pids.contains(&pid)) {
+                    job.update(pid, status);
+                    job.settle_timeout();
+                }
+            }
+        };
+        self.waiting.store(false, Ordering::SeqCst);
+        waited
+    }
+}
+
+// Collect what happened to the jobs since the last check
+fn update_jobs(background_tasks: &mut [Job]) {
+    for task in background_tasks.iter_mut() {
+        for pid in task.pids.clone() {
+            match sys::wait_pid(pid, WNOHANG | WUNTRACED | WCONTINUED) {
+                // it was already reaped somewhere else, there's nothing left to wait for
+                Err(_) => task.forget(pid),
+                Ok(None) => {} // no change since the last check
+                Ok(Some((pid, status))) => task.update(pid, status),
+            }
+        }
+        task.settle_timeout();
+    }
+}
+
+// Collect what happened to the jobs, the ones that are done are reported and
+// taken out of the table
+fn reap_jobs(background_tasks: &mut Vec<Job>) {
+    update_jobs(background_tasks);
+    for task in background_tasks.iter() {
+        if task.state == JobState::Done {
+            match signal_message(task.status).filter(|_| !task.timed_out) {
+                Some(message) => println!("[{}] killed: {}", task.number, message),
+                None => println!("[{}] Done", task.number),
+            }
+            task.finish_output();
+        }
+    }
+
+    background_tasks.retain(|task| task.state != JobState::Done);
+}
+
+// Number of the job a job spec like %2 refers to
+pub fn parse_job_spec(spec: &str) -> Option<usize> {
+    spec.strip_prefix('%').unwrap_or(spec).parse().ok()
+}
+
+// Number for the next job, one more than the highest in use like other
+// shells do
+pub fn next_job_number(jobs: &[Job]) -> usize {
+    jobs.iter().map(|job| job.number).max().unwrap_or(0) + 1
+}
+
+// File the output of background job `number` is captured in. It's removed
+// right away, so it goes away with the last process that has it open and
+// nothing is left behind when the shell exits before the job does
+pub fn capture_file(number: usize) -> io::Result<File> {
+    let prefix = format!("ish-{}-job-{}-", std::process::id(), number);
+    let (file, path) = sys::temporary_file(&prefix, "")?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+// everything written to a capture file so far
+pub fn captured(file: &File) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buf = [0; 8192];
+    loop {
+        match file.read_at(&mut buf, output.len() as u64)? {
+            0 => return Ok(output),
+            n => output.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+// Copy what background job `number` writes to `pipe` to the shell's stdout or
+// stderr, with the job number in front of each line. Runs until every process
+// holding the other end of the pipe is gone
+pub fn tag_output(number: usize, pipe: OwnedFd, to_stderr: bool) {
+    sys::spawn_thread(move || {
+        let mut reader = BufReader::new(File::from(pipe));
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let tagged = [format!("[{}] ", number).as_bytes(), &line].concat();
+            let _ = if to_stderr {
+                io::stderr().lock().write_all(&tagged)
+            } else {
+                let mut out = stdout().lock();
+                out.write_all(&tagged).and_then(|_| out.flush())
+            };
+            line.clear();
+        }
+    });
+}
+
+// Wait for a foreground job to exit or stop, giving it the terminal meanwhile.
+// A stopped job is continued first when `resume` is set.
+// The terminal is only handed over when there is one, with the modes the
+// shell keeps it in.
+// Returns the value of $? for the job, check its state to see if it was stopped
+pub fn wait_for_job(job: &mut Job, resume: bool, terminal: Option<(c_int, &mut Termios)>) -> i32 {
+    if let Some((fd, _)) = &terminal {
+        let _ = sys::set_foreground_pgrp(*fd, job.pgid);
+    }
+    if resume {
+        job.set_state(JobState::Running);
+        job.settle_timeout();
+        let _ = sys::kill_group(job.pgid, Signal::Cont);
+    }
+
+    while job.state == JobState::Running {
+        // Wait for any process of the job to change state. Without a group
+        // of its own the job is waited for a process at a time, and only
+        // stops when the shell does too
+        let waited = match job.pgid {
+            0 => sys::wait_pid(job.pids[0], 0),
+            pgid => sys::wait_pid(-pgid, WUNTRACED),
+        };
+        match waited {
+            Ok(Some((pid, status))) => job.update(pid, status),
+            // nothing left to wait for
+            _ => job.set_state(JobState::Done),
+        }
+    }
+    job.settle_timeout();
+
+    if let Some((fd, modes)) = terminal {
+        let _ = sys::set_foreground_pgrp(fd, sys::process_group());
+        // a command that exited by itself changed the modes on purpose, like
+        // stty does, unless it left the terminal unusable. One that crashed
+        // or was stopped gets its changes undone
+        if job.state == JobState::Done && libc::WIFEXITED(job.status) {
+            if let Some(current) = Termios::current(fd).ok().filter(Termios::usable) {
+                *modes = current;
+            }
+        }
+        let _ = modes.apply(fd);
+    }
+
+    job.exit_status()
+}
+
+// Stop the stages of a pipeline that already started when a later one can't
+// be started, they'd be left writing to a pipe nobody reads. The rest of the
+// pipeline isn't started and they are reaped like any other job
+pub fn abandon_pipeline(pgid: pid_t, pids: &[pid_t]) {
+    if !pids.is_empty() {
+        signal_processes(pgid, pids, Signal::Term);
+        // stopped processes only see the SIGTERM once they continue
+        signal_processes(pgid, pids, Signal::Cont);
+    }
+}
+
+// Send `signal` to the process group `pgid`, or to each of `pids` when it's
+// 0 and they're in the shell's group
+pub fn signal_processes(pgid: pid_t, pids: &[pid_t], signal: Signal) {
+    if pgid != 0 {
+        let _ = sys::kill_group(pgid, signal);
+        return;
+    }
+    for &pid in pids {
+        let _ = sys::kill(pid, signal);
+    }
+}
@@ -0,0 +1,153 @@
+// The directories cd goes to in interactive shells, ranked by how often and
+// how lately, for the j builtin to jump to. They are kept in $ISH_JUMPFILE or
+// ~/.ish_jumps, a line for each with its rank, the time it was last gone to
+// and its path
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// the ranks are aged when they add up to more than this, the directories
+// whose rank falls under 1 are forgotten
+const MAX_RANK: f64 = 9000.0;
+// and only the best ranked of them are kept
+const MAX_ENTRIES: usize = 1000;
+
+struct Entry {
+    path: PathBuf,
+    rank: f64,
+    // seconds since the epoch
+    time: u64,
+}
+
+impl Entry {
+    // the rank, counting more for a directory gone to in the last hour or day
+    // and less for one not gone to in a week
+    fn frecency(&self, now: u64) -> f64 {
+        match now.saturating_sub(self.time) {
+            age if age < 3600 => self.rank * 4.0,
+            age if age < 86400 => self.rank * 2.0,
+            age if age < 604800 => self.rank / 2.0,
+            _ => self.rank / 4.0,
+        }
+    }
+}
+
+fn file() -> Option<PathBuf> {
+    env::var_os("ISH_JUMPFILE")
+        .filter(|file| !file.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".ish_jumps")))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+// the lines that don't parse are left out, a file that isn't there has none
+fn load(file: &Path) -> io::Result<Vec<Entry>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let entries = text.lines().filter_map(|line| {
+        let mut fields = line.splitn(3, '\t');
+        Some(Entry {
+            rank: fields.next()?.parse().ok()?,
+            time: fields.next()?.parse().ok()?,
+            path: PathBuf::from(fields.next()?),
+        })
+    });
+    Ok(entries.collect())
+}
+
+// written next to the file and moved over it, so another shell reading it
+// never sees it half written
+fn save(file: &Path, entries: &[Entry]) -> io::Result<()> {
+    let mut text = String::new();
+    for entry in entries {
+        let path = entry.path.to_string_lossy();
+        text.push_str(&format!("{}\t{}\t{}\n", entry.rank, entry.time, path));
+    }
+    let mut temporary = file.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, file)
+}
+
+// Count a visit to `dir`. One whose path can't go on a line of the file
+// isn't counted
+pub fn record(dir: &Path) -> io::Result<()> {
+    let Some(file) = file() else {
+        return Ok(());
+    };
+    if dir.to_str().is_none_or(|dir| dir.contains('\n')) {
+        return Ok(());
+    }
+    let mut entries = load(&file)?;
+    let now = now();
+    match entries.iter_mut().find(|entry| entry.path == dir) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.time = now;
+        }
+        None => entries.push(Entry {
+            path: dir.to_path_buf(),
+            rank: 1.0,
+            time: now,
+        }),
+    }
+    if entries.iter().map(|entry| entry.rank).sum::<f64>() > MAX_RANK {
+        entries.iter_mut().for_each(|entry| entry.rank *= 0.99);
+        entries.retain(|entry| entry.rank >= 1.0);
+    }
+    if entries.len() > MAX_ENTRIES {
+        entries.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+        entries.truncate(MAX_ENTRIES);
+    }
+    save(&file, &entries)
+}
+
+// The directories with all of `words` in their path in that order, the best
+// first with their frecency. Case only matters when a word has a capital
+// letter in it. Directories that are gone are left out
+pub fn ranked(words: &[String]) -> io::Result<Vec<(f64, PathBuf)>> {
+    let Some(file) = file() else {
+        return Ok(Vec::new());
+    };
+    let ignore_case = !words
+        .iter()
+        .any(|word| word.chars().any(char::is_uppercase));
+    let fold = |text: &str| {
+        if ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    };
+    let words: Vec<String> = words.iter().map(|word| fold(word)).collect();
+    let matches = |path: &Path| {
+        let path = fold(&path.to_string_lossy());
+        let mut rest = path.as_str();
+        words.iter().all(|word| match rest.find(word.as_str()) {
+            Some(i) => {
+                rest = &rest[i + word.len()..];
+                true
+            }
+            None => false,
+        })
+    };
+    let now = now();
+    let mut ranked: Vec<(f64, PathBuf)> = load(&file)?
+        .into_iter()
+        .filter(|entry| matches(&entry.path) && entry.path.is_dir())
+        .map(|entry| (entry.frecency(now), entry.path))
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Ok(ranked)
+}
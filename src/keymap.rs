@@ -0,0 +1,379 @@
+// Key bindings of the line editor, changed with the bindkey builtin
+
+// what a key does in the line editor, named like the readline functions
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    AcceptLine,
+    // start a new line in the command, also when it's complete
+    Newline,
+    SelfInsert,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardChar,
+    ForwardChar,
+    BackwardWord,
+    ForwardWord,
+    BackwardDeleteChar,
+    DeleteChar,
+    KillLine,
+    UnixLineDiscard,
+    UnixWordRubout,
+    KillWord,
+    BackwardKillWord,
+    Yank,
+    YankPop,
+    YankLastArg,
+    TransposeChars,
+    ClearScreen,
+    PreviousHistory,
+    NextHistory,
+    HistorySearchBackward,
+    HistorySearchForward,
+    ReverseSearchHistory,
+    Complete,
+    // the line goes to $VISUAL or $EDITOR
+    EditAndExecuteCommand,
+    // the vi commands, the ones that go back to inserting text first
+    ViMovementMode,
+    ViInsertionMode,
+    ViAppendMode,
+    ViInsertBeg,
+    ViAppendEol,
+    ViForwardWord,
+    ViBackwardWord,
+    ViEndWord,
+    ViFirstPrint,
+    // the operators, they act on the text up to where the motion typed next
+    // goes, or on the whole line when the same key is typed again
+    ViDeleteTo,
+    ViChangeTo,
+    ViYankTo,
+    ViChangeEol,
+    ViChangeWholeLine,
+    ViChangeChar,
+    ViPut,
+    ViPutBefore,
+}
+
+// every action by name
+const ACTIONS: &[(&str, Action)] = &[
+    ("accept-line", Action::AcceptLine),
+    ("newline", Action::Newline),
+    ("self-insert", Action::SelfInsert),
+    ("beginning-of-line", Action::BeginningOfLine),
+    ("end-of-line", Action::EndOfLine),
+    ("backward-char", Action::BackwardChar),
+    ("forward-char", Action::ForwardChar),
+    ("backward-word", Action::BackwardWord),
+    ("forward-word", Action::ForwardWord),
+    ("backward-delete-char", Action::BackwardDeleteChar),
+    ("delete-char", Action::DeleteChar),
+    ("kill-line", Action::KillLine),
+    ("unix-line-discard", Action::UnixLineDiscard),
+    ("unix-word-rubout", Action::UnixWordRubout),
+    ("kill-word", Action::KillWord),
+    ("backward-kill-word", Action::BackwardKillWord),
+    ("yank", Action::Yank),
+    ("yank-pop", Action::YankPop),
+    ("yank-last-arg", Action::YankLastArg),
+    ("transpose-chars", Action::TransposeChars),
+    ("clear-screen", Action::ClearScreen),
+    ("previous-history", Action::PreviousHistory),
+    ("next-history", Action::NextHistory),
+    ("history-search-backward", Action::HistorySearchBackward),
+    ("history-search-forward", Action::HistorySearchForward),
+    ("reverse-search-history", Action::ReverseSearchHistory),
+    ("complete", Action::Complete),
+    ("edit-and-execute-command", Action::EditAndExecuteCommand),
+    ("vi-movement-mode", Action::ViMovementMode),
+    ("vi-insertion-mode", Action::ViInsertionMode),
+    ("vi-append-mode", Action::ViAppendMode),
+    ("vi-insert-beg", Action::ViInsertBeg),
+    ("vi-append-eol", Action::ViAppendEol),
+    ("vi-forward-word", Action::ViForwardWord),
+    ("vi-backward-word", Action::ViBackwardWord),
+    ("vi-end-word", Action::ViEndWord),
+    ("vi-first-print", Action::ViFirstPrint),
+    ("vi-delete-to", Action::ViDeleteTo),
+    ("vi-change-to", Action::ViChangeTo),
+    ("vi-yank-to", Action::ViYankTo),
+    ("vi-change-eol", Action::ViChangeEol),
+    ("vi-change-whole-line", Action::ViChangeWholeLine),
+    ("vi-change-char", Action::ViChangeChar),
+    ("vi-put", Action::ViPut),
+    ("vi-put-before", Action::ViPutBefore),
+];
+
+// the keys bound when the shell starts, the ones the line editor always had
+const DEFAULTS: &[(&[u8], Action)] = &[
+    (b"\r", Action::AcceptLine),
+    (b"\x1b\r", Action::Newline),
+    (b"\x01", Action::BeginningOfLine),
+    (b"\x1b[H", Action::BeginningOfLine),
+    (b"\x05", Action::EndOfLine),
+    (b"\x1b[F", Action::EndOfLine),
+    (b"\x02", Action::BackwardChar),
+    (b"\x1b[D", Action::BackwardChar),
+    (b"\x06", Action::ForwardChar),
+    (b"\x1b[C", Action::ForwardChar),
+    (b"\x1bb", Action::BackwardWord),
+    (b"\x1bf", Action::ForwardWord),
+    (b"\x7f", Action::BackwardDeleteChar),
+    (b"\x04", Action::DeleteChar),
+    (b"\x1b[3~", Action::DeleteChar),
+    (b"\x0b", Action::KillLine),
+    (b"\x15", Action::UnixLineDiscard),
+    (b"\x17", Action::UnixWordRubout),
+    (b"\x1bd", Action::KillWord),
+    (b"\x19", Action::Yank),
+    (b"\x1by", Action::YankPop),
+    (b"\x1b.", Action::YankLastArg),
+    (b"\x14", Action::TransposeChars),
+    (b"\x0c", Action::ClearScreen),
+    (b"\x10", Action::PreviousHistory),
+    (b"\x1b[A", Action::PreviousHistory),
+    (b"\x0e", Action::NextHistory),
+    (b"\x1b[B", Action::NextHistory),
+    (b"\x12", Action::ReverseSearchHistory),
+    (b"\t", Action::Complete),
+    (b"\x18\x05", Action::EditAndExecuteCommand),
+];
+
+// the keys bound in vi mode while typing, Escape goes to the vi commands
+const VI_INSERT_DEFAULTS: &[(&[u8], Action)] = &[
+    (b"\r", Action::AcceptLine),
+    (b"\x1b\r", Action::Newline),
+    (b"\x1b", Action::ViMovementMode),
+    (b"\x1b[H", Action::BeginningOfLine),
+    (b"\x1b[F", Action::EndOfLine),
+    (b"\x1b[D", Action::BackwardChar),
+    (b"\x1b[C", Action::ForwardChar),
+    (b"\x7f", Action::BackwardDeleteChar),
+    (b"\x04", Action::DeleteChar),
+    (b"\x1b[3~", Action::DeleteChar),
+    (b"\x15", Action::UnixLineDiscard),
+    (b"\x17", Action::UnixWordRubout),
+    (b"\x19", Action::Yank),
+    (b"\x0c", Action::ClearScreen),
+    (b"\x1b[A", Action::PreviousHistory),
+    (b"\x1b[B", Action::NextHistory),
+    (b"\x12", Action::ReverseSearchHistory),
+    (b"\t", Action::Complete),
+];
+
+// the vi commands, characters that aren't bound here aren't typed
+const VI_COMMAND_DEFAULTS: &[(&[u8], Action)] = &[
+    (b"\r", Action::AcceptLine),
+    (b"i", Action::ViInsertionMode),
+    (b"a", Action::ViAppendMode),
+    (b"I", Action::ViInsertBeg),
+    (b"A", Action::ViAppendEol),
+    (b"h", Action::BackwardChar),
+    (b"\x1b[D", Action::BackwardChar),
+    (b"\x7f", Action::BackwardChar),
+    (b"l", Action::ForwardChar),
+    (b" ", Action::ForwardChar),
+    (b"\x1b[C", Action::ForwardChar),
+    (b"0", Action::BeginningOfLine),
+    (b"\x1b[H", Action::BeginningOfLine),
+    (b"^", Action::ViFirstPrint),
+    (b"$", Action::EndOfLine),
+    (b"\x1b[F", Action::EndOfLine),
+    (b"w", Action::ViForwardWord),
+    (b"b", Action::ViBackwardWord),
+    (b"e", Action::ViEndWord),
+    (b"j", Action::NextHistory),
+    (b"\x1b[B", Action::NextHistory),
+    (b"k", Action::PreviousHistory),
+    (b"\x1b[A", Action::PreviousHistory),
+    (b"/", Action::ReverseSearchHistory),
+    (b"x", Action::DeleteChar),
+    (b"\x1b[3~", Action::DeleteChar),
+    (b"X", Action::BackwardDeleteChar),
+    (b"d", Action::ViDeleteTo),
+    (b"c", Action::ViChangeTo),
+    (b"y", Action::ViYankTo),
+    (b"D", Action::KillLine),
+    (b"C", Action::ViChangeEol),
+    (b"S", Action::ViChangeWholeLine),
+    (b"r", Action::ViChangeChar),
+    (b"p", Action::ViPut),
+    (b"P", Action::ViPutBefore),
+    (b"\x0c", Action::ClearScreen),
+    (b"\t", Action::Complete),
+    (b"v", Action::EditAndExecuteCommand),
+];
+
+// what a key sequence is bound to
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Action(Action),
+    // inserted into the line as it is
+    Text(String),
+}
+
+// Key sequences and what they're bound to, in the order they were bound. Keys
+// that send a sequence of their own in some terminals are bound with the one
+// the line editor reads them as, like \e[H for Home
+pub struct Keymap {
+    bindings: Vec<(Vec<u8>, Binding)>,
+    // characters that aren't bound are typed
+    pub inserts: bool,
+}
+
+// The keymaps of the editing modes: emacs, and vi while typing and for its
+// commands
+pub struct Keymaps {
+    pub emacs: Keymap,
+    pub vi_insert: Keymap,
+    pub vi_command: Keymap,
+}
+
+fn keymap(defaults: &[(&[u8], Action)], inserts: bool) -> Keymap {
+    Keymap {
+        bindings: (defaults.iter())
+            .map(|(keys, action)| (keys.to_vec(), Binding::Action(*action)))
+            .collect(),
+        inserts,
+    }
+}
+
+impl Default for Keymaps {
+    fn default() -> Keymaps {
+        Keymaps {
+            emacs: keymap(DEFAULTS, true),
+            vi_insert: keymap(VI_INSERT_DEFAULTS, true),
+            vi_command: keymap(VI_COMMAND_DEFAULTS, false),
+        }
+    }
+}
+
+impl Keymaps {
+    // the keymap bindkey -M calls `name`: emacs, viins or vicmd
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Keymap> {
+        match name {
+            "emacs" => Some(&mut self.emacs),
+            "viins" => Some(&mut self.vi_insert),
+            "vicmd" => Some(&mut self.vi_command),
+            _ => None,
+        }
+    }
+}
+
+pub fn action(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, action)| *action)
+}
+
+pub fn action_name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|(_, a)| *a == action).unwrap().0
+}
+
+// The bytes a key sequence written like readline does it stands for: \C-x
+// or ^X for Ctrl and a key, \M-x or \e and the key for Alt, \e for Escape
+// and the C escapes. None when it ends in the middle of one
+pub fn parse_keys(text: &str) -> Option<Vec<u8>> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' if chars.peek().is_some() => keys.push(control(chars.next()?)?),
+            '\\' => match chars.next()? {
+                'C' if chars.next_if_eq(&'-').is_some() => keys.push(control(chars.next()?)?),
+                'M' if chars.next_if_eq(&'-').is_some() => {
+                    keys.push(0x1b);
+                    let c = chars.next()?;
+                    keys.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                'e' | 'E' => keys.push(0x1b),
+                'a' => keys.push(0x07),
+                'b' => keys.push(0x08),
+                'n' => keys.push(b'\n'),
+                'r' => keys.push(b'\r'),
+                't' => keys.push(b'\t'),
+                digit @ '0'..='7' => {
+                    let mut value = digit.to_digit(8)?;
+                    for _ in 0..2 {
+                        match chars.next_if(|c| c.is_digit(8)) {
+                            Some(digit) => value = value * 8 + digit.to_digit(8)?,
+                            None => break,
+                        }
+                    }
+                    keys.push(u8::try_from(value).ok()?);
+                }
+                c => keys.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            },
+            c => keys.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Some(keys)
+}
+
+// the byte Ctrl and `c` send, ? is Delete
+fn control(c: char) -> Option<u8> {
+    match c {
+        '?' => Some(0x7f),
+        '@'..='_' | 'a'..='z' => Some(c.to_ascii_uppercase() as u8 & 0x1f),
+        _ => None,
+    }
+}
+
+// Key sequence in the notation parse_keys reads, written so it can be put
+// in single quotes
+pub fn show_keys(keys: &[u8]) -> String {
+    let mut shown = String::new();
+    for chunk in keys.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\x1b' => shown.push_str("\\e"),
+                '\x7f' => shown.push_str("\\C-?"),
+                '\0'..='\x1f' => {
+                    shown.push_str("\\C-");
+                    shown.push((c as u8 + 0x40).to_ascii_lowercase() as char);
+                }
+                '\\' => shown.push_str("\\\\"),
+                '\'' => shown.push_str("\\047"),
+                '^' => shown.push_str("\\^"),
+                c => shown.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            shown.push_str(&format!("\\{:03o}", byte));
+        }
+    }
+    shown
+}
+
+impl Keymap {
+    // what `keys` are bound to
+    pub fn get(&self, keys: &[u8]) -> Option<&Binding> {
+        (self.bindings.iter())
+            .find(|(bound, _)| bound == keys)
+            .map(|(_, binding)| binding)
+    }
+
+    // whether a longer sequence than `keys` starts with them
+    pub fn is_prefix(&self, keys: &[u8]) -> bool {
+        (self.bindings.iter()).any(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys))
+    }
+
+    pub fn bind(&mut self, keys: Vec<u8>, binding: Binding) {
+        match self.bindings.iter_mut().find(|(bound, _)| *bound == keys) {
+            Some(bound) => bound.1 = binding,
+            None => self.bindings.push((keys, binding)),
+        }
+    }
+
+    // take the binding of `keys` away, returns whether they were bound
+    pub fn unbind(&mut self, keys: &[u8]) -> bool {
+        let before = self.bindings.len();
+        self.bindings.retain(|(bound, _)| bound != keys);
+        self.bindings.len() < before
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (&[u8], &Binding)> {
+        (self.bindings.iter()).map(|(keys, binding)| (keys.as_slice(), binding))
+    }
+}
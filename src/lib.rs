@@ -0,0 +1,87 @@
+// The shell as a library: parsing, expansion, running commands and the
+// builtins, with the state they work on in a Shell. The ish binary only reads
+// its arguments and starts one
+
+// first, the trace! macro is used throughout
+#[macro_use]
+pub mod trace;
+
+pub mod builtins;
+#[cfg(feature = "completion")]
+mod complete;
+mod compspec;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod error;
+pub mod exec;
+pub mod expand;
+#[cfg(feature = "prompt-git")]
+mod git;
+#[cfg(feature = "editor")]
+mod highlight;
+mod history;
+pub mod input;
+pub mod jobs;
+mod jump;
+mod keymap;
+pub mod named_dirs;
+pub mod options;
+pub mod parser;
+mod path_cache;
+mod pattern;
+mod printf;
+mod prompt;
+mod redirect;
+pub mod shell;
+mod suggest;
+mod sys;
+pub mod term;
+mod timeout;
+mod trap;
+#[cfg(feature = "editor")]
+mod unicode;
+
+// Tab finds nothing to complete and rings the bell
+#[cfg(all(feature = "editor", not(feature = "completion")))]
+mod complete {
+    use crate::{editor::Completion, input::Context};
+
+    pub fn complete(_: &[char], _: &mut Context) -> Option<Completion> {
+        None
+    }
+}
+
+// \g in the prompt shows nothing
+#[cfg(not(feature = "prompt-git"))]
+mod git {
+    use std::path::Path;
+
+    #[derive(Default)]
+    pub struct Git {}
+
+    impl Git {
+        pub fn status(&mut self, _: &Path) -> Option<String> {
+            None
+        }
+    }
+}
+
+use std::{ffi::CStr, io};
+
+// Message for an io error without the " (os error N)" suffix std adds
+pub fn error_message(err: &io::Error) -> String {
+    match err.raw_os_error() {
+        Some(errno) => unsafe { CStr::from_ptr(libc::strerror(errno)) }
+            .to_string_lossy()
+            .into_owned(),
+        None => err.to_string(),
+    }
+}
+
+// Prefix for error messages: "ish", or the script and line being run
+pub fn error_location(script: Option<&str>, line_number: usize) -> String {
+    match script {
+        Some(script) => format!("ish: {}: line {}", script, line_number),
+        None => "ish".to_string(),
+    }
+}
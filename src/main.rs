@@ -1,319 +1,187 @@
 use std::{
-    io::{stdin, stdout, Write},
-    os::unix::process::CommandExt,
+    env,
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{self, stdin, BufReader},
+    os::unix::ffi::{OsStrExt, OsStringExt},
     path::Path,
-    process::{Child, Command, Stdio},
-    sync::{Arc, Mutex},
-    thread,
-    time::Duration,
 };
 
-use libc::{
-    c_int, pid_t, SIGCONT, SIGINT, SIGTSTP, STDIN_FILENO, TCSADRAIN, WNOHANG,
-    WUNTRACED,
+use libc::STDIN_FILENO;
+
+#[cfg(feature = "editor")]
+use ish::editor::Editor;
+use ish::{
+    error_message,
+    input::{ReadLine, Terminal},
+    options::Options,
+    shell::{export_shell_variables, Shell},
+    term::{self, exit_shell},
+    trace,
 };
 
-// Empty signal handler so we don't exit on signals
-extern "C" fn handle_signal(_: c_int) {}
-
-// simple check to see if a process is running
-fn is_process_running(pid: pid_t) -> bool {
-    let result = unsafe { libc::kill(pid, 0) };
-    result == 0
-}
-
-// Monitor background tasks and remove them from the vector when they exit
-fn monitor_background_tasks(backgound_tasks: Arc<Mutex<Vec<Child>>>) {
-    loop {
-        // wait a bit between checks
-        thread::sleep(Duration::from_millis(100));
-
-        // Lock the mutex before accessing the vector
-        let mut background_tasks = backgound_tasks.lock().unwrap();
-
-        background_tasks.retain(|task| {
-            let pid = task.id() as i32;
-            let result = unsafe { libc::waitpid(pid, std::ptr::null_mut(), WNOHANG) };
-
-            match result {
-                -1 => {
-                    eprintln!("Error checking status for background task {}", task.id());
-                    true // Keep the task in the vector
-                }
-                0 => true, // Task is still running
-                _ => {
-                    // Task is in a Zombie state, remove it from the vector
-                    println!("Background task {} exited", task.id());
-                    false
-                }
-            }
-        });
-    }
-}
+// shown for --help and an option ish doesn't know
+const USAGE: &str = "\
+usage: ish [-eilnrsx] [+enx] [--login] [--norc] [--rcfile FILE] [--posix] [--debug]
+                  [-c COMMAND [NAME [ARG...]] | SCRIPT [ARG...]]
+       ish --help | --version";
 
 fn main() {
-    // Ignore signals so they don't kill the shell
-    unsafe {
-        libc::signal(SIGINT, handle_signal as usize);
-        libc::signal(SIGTSTP, handle_signal as usize);
+    term::install_signal_handlers();
+    if env::var_os("ISH_DEBUG").is_some_and(|debug| !debug.is_empty()) {
+        trace::set_enabled(true);
     }
-    // list of current stopped processes
-    let mut current_stopped: Option<Child> = None;
-
-    // vector of background tasks
-    let backgound_tasks = Arc::new(Mutex::new(Vec::new()));
-
-    // Spawn a background thread to monitor background tasks
-    let _background_thread = {
-        let backgound_tasks = Arc::clone(&backgound_tasks);
-        thread::spawn(move || {
-            monitor_background_tasks(backgound_tasks);
-        })
-    };
-
-    // main loop
-    loop {
-        print!("> ");
-        let _ = stdout().flush(); // flush stdout so the prompt doesn't read '>'
-        let mut raw_input: String = String::new(); // read input from stdin
 
-        // exit when ^D is pressed
-        match stdin().read_line(&mut raw_input) {
-            Ok(0) => break, // Exit the loop on EOF (^D)
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("Error reading input: {}", err);
+    // commands are read from the string given with -c, the script given as
+    // the first argument, or from stdin
+    // arguments are kept as they are, they don't have to be UTF-8
+    let mut argv = env::args_os().peekable();
+    let shell_name = argv.next().unwrap_or_else(|| "ish".into());
+    let mut force_interactive = false;
+    let mut read_stdin = false;
+    let mut command_string = false;
+    let mut norc = false;
+    // login passes the shell a name starting with a dash, like -ish
+    let mut login = shell_name.as_bytes().starts_with(b"-");
+    let name = Path::new(&shell_name).file_name().unwrap_or_default();
+    let name = OsStr::from_bytes(
+        name.as_bytes()
+            .strip_prefix(b"-")
+            .unwrap_or(name.as_bytes()),
+    );
+    // run as sh, like from a /bin/sh link, ish behaves like one
+    let mut posix = name == "sh";
+    // and run as rish it's restricted
+    let mut restricted = name == "rish";
+    let mut rcfile = None;
+    // options like -x and +e, set once the shell is started
+    let mut preset = Vec::new();
+    while let Some(arg) = argv.peek() {
+        let flag = arg.to_str().unwrap_or_default();
+        if let Some(name) = Options::by_letter(flag) {
+            preset.push((name, flag.starts_with('-')));
+            argv.next();
+            continue;
+        }
+        match flag {
+            "-i" => force_interactive = true,
+            "-s" => read_stdin = true,
+            "-c" => command_string = true,
+            "-r" => restricted = true,
+            "-l" | "--login" => login = true,
+            "--norc" => norc = true,
+            "--posix" => posix = true,
+            "--debug" => trace::set_enabled(true),
+            "--rcfile" => {
+                argv.next();
+                let Some(path) = argv.peek() else {
+                    eprintln!("ish: --rcfile: option requires an argument");
+                    exit_shell(2);
+                };
+                rcfile = Some(path.clone());
+            }
+            "--version" => {
+                println!("ish {}", env!("CARGO_PKG_VERSION"));
+                exit_shell(0);
+            }
+            "--help" => {
+                println!("{}", USAGE);
+                exit_shell(0);
+            }
+            // what comes after is the script and its arguments even if it
+            // starts with a dash
+            "--" => {
+                argv.next();
                 break;
             }
+            _ if flag.len() > 1 && flag.starts_with('-') => {
+                eprintln!("ish: {}: invalid option\n{}", flag, USAGE);
+                exit_shell(2);
+            }
+            _ => break,
         }
-
-        // check if the user wants to run the command in the background
-        let mut wait = true;
-        if raw_input.trim().ends_with('&') {
-            wait = false;
-        }
-
-        // remove the trailing & if it exists
-        let input = raw_input.trim_end().trim_end_matches('&');
-
-        // split the input into commands separated by pipes
-        let mut commands = input.trim().split(" | ").peekable();
-        let mut previous_command: Option<Child> = None;
-        let mut first_launched = true;
-
-        // get the terminal settings so we can restore them later
-        let shell_terminal = STDIN_FILENO;
-        let mut shell_tmodes = libc::termios {
-            c_iflag: 0,
-            c_oflag: 0,
-            c_cflag: 0,
-            c_lflag: 0,
-            c_cc: [0; 32],
-            c_ispeed: 0,
-            c_ospeed: 0,
-            c_line: 0,
+        argv.next();
+    }
+    let mut script = None;
+    let mut input: Box<dyn ReadLine>;
+    let positional: Vec<OsString>;
+    if command_string {
+        let Some(command) = argv.next() else {
+            eprintln!("ish: -c: option requires an argument");
+            exit_shell(2);
         };
+        input = Box::new(io::Cursor::new(command.into_vec()));
+        // `ish -c command name args...` runs with name as $0
+        positional = [argv.next().unwrap_or(shell_name)]
+            .into_iter()
+            .chain(argv)
+            .collect();
+    } else if let Some(path) = argv.next_if(|_| !read_stdin) {
+        input = match File::open(&path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("ish: {}: {}", Path::new(&path).display(), error_message(&e));
+                exit_shell(127);
+            }
+        };
+        positional = [path.clone()].into_iter().chain(argv).collect();
+        script = Some(Path::new(&path).display().to_string());
+    } else {
+        // `ish -s args...` reads commands from stdin with args as $1, $2...
+        input = Box::new(stdin().lock());
+        positional = [shell_name].into_iter().chain(argv).collect();
+    }
 
-        unsafe {
-            libc::tcgetattr(shell_terminal, &mut shell_tmodes as *mut libc::termios);
-        }
-
-        // loop through each command
-        while let Some(command) = commands.next() {
-
-            // split the command into command and arguments
-            let mut parts = command.trim().split_whitespace();
-            let command = parts.next().unwrap_or_else(|| "");
-            let args: Vec<&str> = parts.collect();
-
-            match command {
-                "" => {} // Do nothing on empty input
-                "exit" => return, // Exit the shell
-
-                "fg" => {
-                    if let Some(child) = current_stopped {
-                        let pid = child.id() as i32;
-                        unsafe {
-                            // libc::tcsetpgrp(shell_terminal, pid);
-                            // libc::tcsetattr(shell_terminal, TCSADRAIN, &shell_tmodes);
-                            libc::kill(pid, SIGCONT);
-                            previous_command = Some(child);
-                            current_stopped = None;
-                            wait = true;
-                            break;
-                        }
-
-                    // TODO DOESNT WORK when background process is stopped and put to foreground
-                    } else if let Some(child) = backgound_tasks.lock().unwrap().pop() {
-                        let pid = child.id() as i32;
-                        unsafe {
-                            libc::tcsetpgrp(STDIN_FILENO, pid);
-                            // libc::tcsetattr(STDIN_FILENO, TCSADRAIN, &shell_tmodes);
-                            previous_command = Some(child);
-                            current_stopped = None;
-                            wait = true;
-                            libc::kill(pid, SIGCONT);
-                            break;
-                        }
-                    }
-                }
-
-                // TODO DOESNT WORK, Permission denied (os error 13) when setpgid :(
-                "bg" => {
-                    if let Some(child) = current_stopped {
-                        unsafe {
-                            let pid: i32 = child.id() as i32;
-
-                            if libc::setsid() < 0 {
-                                eprintln!("setsid: {}", std::io::Error::last_os_error());
-                                return;
-                            }
-                            if libc::setpgid(pid, pid) < 0 {
-                                eprintln!("setpgid: {}", std::io::Error::last_os_error());
-                                return;
-                            }
-
-                            if libc::kill(pid, libc::SIGCONT) < 0 {
-                                eprintln!(
-                                    "Error continuing process: {}",
-                                    std::io::Error::last_os_error()
-                                );
-                                // Additional information for debugging
-                                return;
-                            }
-
-                            backgound_tasks.lock().unwrap().push(child);
-                        }
-                        current_stopped = None;
-                        wait = false;
-                    }
-                }
-                
-                "jobs" => {
-                    for (i, child) in backgound_tasks.lock().unwrap().iter().enumerate() {
-                        println!("[{}] {}", i, child.id());
-                    }
-                    backgound_tasks
-                        .lock()
-                        .unwrap()
-                        .retain(|task| is_process_running(task.id() as i32));
-                }
-                "cd" => {
-                    if args.is_empty() {
-                        eprintln!("expected argument to \"cd\"");
-                        continue;
-                    }
-                    let path = args.first().unwrap();
-                    let root = Path::new(path);
-                    if let Err(e) = std::env::set_current_dir(&root) {
-                        eprintln!("{}", e);
-                    }
+    let interactive = force_interactive
+        || (!command_string
+            && script.is_none()
+            && unsafe {
+                libc::isatty(STDIN_FILENO) == 1 && libc::isatty(libc::STDERR_FILENO) == 1
+            });
+
+    // what the user types is read without BufRead, which hides Ctrl-C, and
+    // edited in the terminal when it can handle that
+    if interactive && !command_string && script.is_none() {
+        input = line_editor().unwrap_or_else(|| Box::new(Terminal::new()));
+    }
 
-                    previous_command = None;
-                }
-                mut command => {
-                    let stdin = if command.contains('<') {
-                        let c: Vec<&str> = command.split('<').collect();
-                        command = c[0];
-                        let file = c[1].trim();
-                        Stdio::from(std::fs::File::open(file).unwrap())
-                    } else {
-                        previous_command.map_or(Stdio::inherit(), |output: Child| {
-                            Stdio::from(output.stdout.unwrap())
-                        })
-                    };
-                    let stdout = if command.contains('>') && !command.contains("2>") {
-                        let c: Vec<&str> = command.split('>').collect();
-                        command = c[0];
-                        let file = c[1].trim();
-                        Stdio::from(std::fs::File::create(file).unwrap())
-                    } else {
-                        if commands.peek().is_some() {
-                            Stdio::piped()
-                        } else {
-                            Stdio::inherit()
-                        }
-                    };
-                    let stderr = if command.contains("2>") {
-                        let c: Vec<&str> = command.split("2>").collect();
-                        command = c[0];
-                        let file = c[1].trim();
-                        Stdio::from(std::fs::File::create(file).unwrap())
-                    } else {
-                        Stdio::inherit()
-                    };
+    export_shell_variables();
+    let mut shell = Shell::start(positional, interactive, script);
+    shell.options.posix = posix;
+    shell.restricted = restricted;
+    shell.options.login = login;
+    for (name, on) in preset {
+        shell.options.set(name, on);
+    }
 
+    // the startup files can't stop the shell from starting, only an exit in
+    // them can. A login shell reads its profile before the rc file
+    let mut started = if login { shell.source_profile() } else { None };
+    if started.is_none() && interactive && !norc {
+        started = shell.source_rc(rcfile);
+    }
 
-                    unsafe {
-                        let output: Result<Child, std::io::Error> = Command::new(command)
-                            .args(args)
-                            .stdin(stdin)
-                            .stdout(stdout)
-                            .stderr(stderr)
-                            .pre_exec(move || {
-                                if first_launched {
-                                    if !wait {
-                                        libc::setpgid(0, libc::getpid());
-                                    }
-                                    first_launched = false;
-                                }
-                                Ok(())
-                            })
-                            .spawn();
-                        // let pid = output.as_ref().unwrap().id() as i32;
-                        match output {
-                            Ok(output) => {
-                                previous_command = Some(output);
-                                if !wait {
-                                    let previous_command =
-                                        std::mem::replace(&mut previous_command, None);
-                                    backgound_tasks
-                                        .lock()
-                                        .unwrap()
-                                        .push(previous_command.unwrap());
-                                }
-                            }
-                            Err(e) => {
-                                previous_command = None;
-                                eprintln!("{}", e);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        if let Some(final_command) = previous_command {
-            // block until the final command has finished
-            if wait {
-                unsafe {
-                    libc::setsid();
-                    let fd = 0;
-                    let child_pgrp = libc::tcgetpgrp(fd);
-                    libc::tcsetpgrp(fd, child_pgrp);
+    let status = started.unwrap_or_else(|| {
+        shell
+            .run_lines(&mut *input, interactive)
+            .unwrap_or(shell.last_status)
+    });
+    if login {
+        shell.source_logout();
+    }
+    exit_shell(shell.run_exit_trap(status))
+}
 
-                    // Wait for the child process to change state
-                    let mut status = 0;
-                    let wpid = final_command.id() as i32;
-                    libc::waitpid(wpid, &mut status as *mut i32, WUNTRACED);
-                    // if WIFEXITED(status) {
-                    //     print!("0");
-                    //     print!("Child process exited with status {}\n", WEXITSTATUS(status));
-                    // } else if WIFSIGNALED(status) {
-                    //     print!("Child process terminated by signal {}\n", WTERMSIG(status));
-                    // } else if WIFSTOPPED(status) {
-                    //     print!("Child process stopped by signal {}\n", WSTOPSIG(status));
-                    //     current_stopped = Some(final_command);
-                    // } else if WIFCONTINUED(status) {
-                    //     print!("Child process continued\n");
-                    // }
-                    // libc::tcsetpgrp(shell_terminal, libc::getpid());
-                    // print!("3");
+// The line editor, when stdin is a terminal it can drive
+#[cfg(feature = "editor")]
+fn line_editor() -> Option<Box<dyn ReadLine>> {
+    let dumb = env::var_os("TERM").is_none_or(|term| term == "dumb");
+    let tty = unsafe { libc::isatty(STDIN_FILENO) } == 1;
+    (tty && !dumb).then(|| Box::new(Editor::new()) as Box<dyn ReadLine>)
+}
 
-                    libc::tcsetattr(shell_terminal, TCSADRAIN, &shell_tmodes);
-                    let og_pgrep = libc::tcgetpgrp(shell_terminal);
-                    libc::tcsetpgrp(shell_terminal, og_pgrep);
-                }
-            }
-        }
-    }
+// built without it, lines are read as the terminal gives them
+#[cfg(not(feature = "editor"))]
+fn line_editor() -> Option<Box<dyn ReadLine>> {
+    None
 }
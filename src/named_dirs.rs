@@ -0,0 +1,41 @@
+// Directories given a name with the namedir builtin, so ~name in a word
+// stands for them and the prompt shows the ones it's in that way
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Default)]
+pub struct NamedDirs {
+    table: BTreeMap<String, PathBuf>,
+}
+
+impl NamedDirs {
+    pub fn set(&mut self, name: &str, dir: PathBuf) {
+        self.table.insert(name.to_string(), dir);
+    }
+
+    // false when there's none by that name
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.table.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.table.get(name).map(PathBuf::as_path)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+        self.table
+            .iter()
+            .map(|(name, dir)| (name.as_str(), dir.as_path()))
+    }
+}
+
+// what a directory can be named, the characters of a ~name before its /
+pub fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
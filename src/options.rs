@@ -0,0 +1,193 @@
+// Shell options, changed with `set -o name` and `set +o name`, or `set -e`
+// and `set +e` for the ones with a letter
+pub struct Options {
+    // suggest similar commands when one isn't found
+    pub suggest: bool,
+    // keep the output of background jobs in a file instead of the terminal,
+    // it's shown with `jobs -o` and when the job finishes
+    pub bgcapture: bool,
+    // prefix each line background jobs write to the terminal with their number
+    pub bgtag: bool,
+    // don't leave an interactive shell on ^D, only on `exit`
+    pub ignoreeof: bool,
+    // Up and Down in the line editor only recall history entries starting
+    // with what was typed before
+    pub histprefix: bool,
+    // show the newest history entry starting with what's typed after it, the
+    // line editor takes it with Right
+    pub autosuggest: bool,
+    // color the command line in the line editor as it's typed
+    pub highlight: bool,
+    // a directory typed as a command in an interactive shell is gone into
+    pub autocd: bool,
+    // cd puts the directory it leaves on the directory stack, like pushd
+    pub autopushd: bool,
+    // cd in an interactive shell goes to the directory a name that doesn't
+    // exist is one typo away from
+    pub cdspell: bool,
+    // the line editor takes in what other sessions added to the history file
+    // before going through the history
+    pub sharehistory: bool,
+    // a command line edited in $VISUAL or $EDITOR with Ctrl-X Ctrl-E runs
+    // when the editor exits, instead of going back into the line editor
+    pub editexec: bool,
+    // the editing mode of the line editor, one of them is always on
+    pub emacs: bool,
+    pub vi: bool,
+    // a script stops at the first command that fails, with its status
+    pub errexit: bool,
+    // each command is printed to stderr as it runs, after expansion
+    pub xtrace: bool,
+    // patterns take ksh's groups like !(*.md) and @(a|b)
+    pub extglob: bool,
+    // the patterns of ${1/pattern/with} match letters in either case
+    pub nocasematch: bool,
+    // a script is only read and checked for syntax errors, nothing in it
+    // runs. An interactive shell doesn't take it
+    pub noexec: bool,
+    // whether ish started as a login shell, it can't be changed
+    pub login: bool,
+    // behave like sh where ish differs from it: unquoted parameters are split
+    // into fields, there's no history expansion, no timeout prefix and no
+    // autocd or cdspell. On when ish is run as sh or with --posix
+    pub posix: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            suggest: true,
+            bgcapture: false,
+            bgtag: false,
+            ignoreeof: false,
+            histprefix: false,
+            autosuggest: true,
+            highlight: true,
+            autocd: false,
+            autopushd: false,
+            cdspell: false,
+            sharehistory: false,
+            editexec: true,
+            emacs: true,
+            vi: false,
+            errexit: false,
+            xtrace: false,
+            extglob: false,
+            nocasematch: false,
+            noexec: false,
+            login: false,
+            posix: false,
+        }
+    }
+}
+
+impl Options {
+    // every option by name, in the order `set -o` lists them
+    pub const NAMES: &'static [&'static str] = &[
+        "autocd",
+        "autopushd",
+        "autosuggest",
+        "bgcapture",
+        "bgtag",
+        "cdspell",
+        "editexec",
+        "emacs",
+        "errexit",
+        "extglob",
+        "highlight",
+        "histprefix",
+        "ignoreeof",
+        "login",
+        "nocasematch",
+        "noexec",
+        "posix",
+        "sharehistory",
+        "suggest",
+        "vi",
+        "xtrace",
+    ];
+
+    // the options shopt turns on and off, the ones bash has there instead of
+    // under `set -o`. Both take them, shopt no others
+    pub const SHOPT_NAMES: &'static [&'static str] =
+        &["autocd", "cdspell", "extglob", "nocasematch"];
+
+    // the options that only say how the shell was started
+    pub const READ_ONLY: &'static [&'static str] = &["login"];
+
+    // the options that have a letter, for `set -e` and `ish -x`
+    pub const LETTERS: &'static [(&'static str, &'static str)] =
+        &[("e", "errexit"), ("n", "noexec"), ("x", "xtrace")];
+
+    // The option a flag like -e or +x stands for
+    pub fn by_letter(flag: &str) -> Option<&'static str> {
+        let letter = flag.strip_prefix(['-', '+'])?;
+        let (_, name) = Options::LETTERS.iter().find(|(l, _)| *l == letter)?;
+        Some(name)
+    }
+
+    // Turn an option on or off, None when there's none by that name. Turning
+    // one editing mode on turns the other off and the other way round
+    pub fn set(&mut self, name: &str, on: bool) -> Option<()> {
+        *self.get_mut(name)? = on;
+        match name {
+            "emacs" => self.vi = !on,
+            "vi" => self.emacs = !on,
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "autocd" => Some(&mut self.autocd),
+            "autopushd" => Some(&mut self.autopushd),
+            "autosuggest" => Some(&mut self.autosuggest),
+            "bgcapture" => Some(&mut self.bgcapture),
+            "bgtag" => Some(&mut self.bgtag),
+            "cdspell" => Some(&mut self.cdspell),
+            "editexec" => Some(&mut self.editexec),
+            "emacs" => Some(&mut self.emacs),
+            "errexit" => Some(&mut self.errexit),
+            "extglob" => Some(&mut self.extglob),
+            "highlight" => Some(&mut self.highlight),
+            "histprefix" => Some(&mut self.histprefix),
+            "ignoreeof" => Some(&mut self.ignoreeof),
+            "nocasematch" => Some(&mut self.nocasematch),
+            "noexec" => Some(&mut self.noexec),
+            "posix" => Some(&mut self.posix),
+            "sharehistory" => Some(&mut self.sharehistory),
+            "suggest" => Some(&mut self.suggest),
+            "vi" => Some(&mut self.vi),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "autocd" => Some(self.autocd),
+            "autopushd" => Some(self.autopushd),
+            "autosuggest" => Some(self.autosuggest),
+            "bgcapture" => Some(self.bgcapture),
+            "bgtag" => Some(self.bgtag),
+            "cdspell" => Some(self.cdspell),
+            "editexec" => Some(self.editexec),
+            "emacs" => Some(self.emacs),
+            "errexit" => Some(self.errexit),
+            "extglob" => Some(self.extglob),
+            "highlight" => Some(self.highlight),
+            "histprefix" => Some(self.histprefix),
+            "ignoreeof" => Some(self.ignoreeof),
+            "login" => Some(self.login),
+            "nocasematch" => Some(self.nocasematch),
+            "noexec" => Some(self.noexec),
+            "posix" => Some(self.posix),
+            "sharehistory" => Some(self.sharehistory),
+            "suggest" => Some(self.suggest),
+            "vi" => Some(self.vi),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::{borrow::Cow, iter::Peekable, str::CharIndices};
+
+use libc::c_int;
+
+use crate::error::ShellError;
+
+// Parsing of command lines into pipelines. Words are kept as typed, with their
+// quotes, because expansion needs to know what was quoted. They are slices of
+// the line unless a backslash and newline joined lines in the middle of one
+
+// what a redirection does with its file descriptor
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedirectOp {
+    // < file
+    Read,
+    // > file
+    Write,
+    // >> file
+    Append,
+    // >&n and <&n, the target is a file descriptor number or - to close it
+    Duplicate,
+}
+
+#[derive(Debug)]
+pub struct Redirect<'a> {
+    pub fd: c_int,
+    pub op: RedirectOp,
+    pub target: Cow<'a, str>,
+}
+
+// a command with its arguments and redirections, in the order they were given
+#[derive(Debug, Default)]
+pub struct SimpleCommand<'a> {
+    pub words: Vec<Cow<'a, str>>,
+    pub redirects: Vec<Redirect<'a>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Pipeline<'a> {
+    pub commands: Vec<SimpleCommand<'a>>,
+    pub background: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Word(Cow<'a, str>),
+    Pipe,
+    Ampersand,
+    Redirect(c_int, RedirectOp),
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => "newline".to_string(),
+        Some(Token::Word(word)) => word.to_string(),
+        Some(Token::Pipe) => "|".to_string(),
+        Some(Token::Ampersand) => "&".to_string(),
+        Some(Token::Redirect(_, op)) => match op {
+            RedirectOp::Read => "<",
+            RedirectOp::Write => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::Duplicate => ">&",
+        }
+        .to_string(),
+    }
+}
+
+// characters that end a word when they aren't quoted
+pub fn is_operator(c: char) -> bool {
+    matches!(c, '|' | '&' | '<' | '>')
+}
+
+// `word` the way it has to be typed for the parser to read it back as it
+// is: as it is when nothing in it is special, in single quotes otherwise
+pub fn quote(word: &str) -> Cow<'_, str> {
+    let plain = |c: char| c.is_alphanumeric() || "-_./=:,+@%^".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', "'\\''")))
+    }
+}
+
+// A word as it's read, a slice of the line until a backslash and newline in
+// it have to be left out
+struct WordText<'a> {
+    line: &'a str,
+    start: usize,
+    end: usize,
+    joined: Option<String>,
+}
+
+impl<'a> WordText<'a> {
+    fn new(line: &'a str, start: usize) -> WordText<'a> {
+        WordText {
+            line,
+            start,
+            end: start,
+            joined: None,
+        }
+    }
+
+    // add the character at `i`, which comes right after the last one unless
+    // lines were joined
+    fn push(&mut self, i: usize, c: char) {
+        match &mut self.joined {
+            Some(joined) => joined.push(c),
+            None => self.end = i + c.len_utf8(),
+        }
+    }
+
+    // what's read next doesn't follow on in the line
+    fn join(&mut self) {
+        if self.joined.is_none() {
+            self.joined = Some(self.text().to_string());
+        }
+    }
+
+    fn text(&self) -> &str {
+        match &self.joined {
+            Some(joined) => joined,
+            None => &self.line[self.start..self.end],
+        }
+    }
+
+    fn into_word(self) -> Cow<'a, str> {
+        match self.joined {
+            Some(joined) => Cow::Owned(joined),
+            None => Cow::Borrowed(&self.line[self.start..self.end]),
+        }
+    }
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, c)) = chars.peek() else {
+            break;
+        };
+
+        // a comment starts at a # at the beginning of a word and goes on to
+        // the end of the line
+        if c == '#' {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            continue;
+        }
+
+        if is_operator(c) {
+            chars.next();
+            tokens.push(operator(c, None, &mut chars));
+            continue;
+        }
+
+        let mut word = WordText::new(line, start);
+        // whether the word so far could be the fd number of a redirection
+        let mut digits = true;
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            if is_operator(c) {
+                if digits && matches!(c, '<' | '>') {
+                    if let Ok(fd) = word.text().parse::<c_int>() {
+                        chars.next();
+                        word = WordText::new(line, i);
+                        tokens.push(operator(c, Some(fd), &mut chars));
+                    }
+                }
+                break;
+            }
+            chars.next();
+            // a backslash before a newline joins the lines
+            if c == '\\' && chars.next_if(|&(_, c)| c == '\n').is_some() {
+                word.join();
+                continue;
+            }
+            digits &= c.is_ascii_digit();
+            word.push(i, c);
+            match c {
+                '\\' => {
+                    if let Some((i, c)) = chars.next() {
+                        word.push(i, c);
+                    }
+                }
+                '\'' | '"' => {
+                    let quote = c;
+                    loop {
+                        match chars.next() {
+                            Some((i, c)) if c == quote => {
+                                word.push(i, c);
+                                break;
+                            }
+                            Some((_, '\\'))
+                                if quote == '"' && chars.next_if(|&(_, c)| c == '\n').is_some() =>
+                            {
+                                word.join();
+                            }
+                            Some((i, '\\')) if quote == '"' => {
+                                word.push(i, '\\');
+                                if let Some((i, c)) = chars.next() {
+                                    word.push(i, c);
+                                }
+                            }
+                            Some((i, c)) => word.push(i, c),
+                            None => {
+                                return Err(format!(
+                                    "unexpected EOF while looking for matching `{}'",
+                                    quote
+                                ))
+                            }
+                        }
+                    }
+                }
+                // spaces and operators don't end the word inside a ${...} or
+                // a $(...)
+                '$' if chars.peek().is_some_and(|&(_, c)| c == '{' || c == '(') => {
+                    let (open, close) = match chars.peek() {
+                        Some((_, '{')) => ('{', '}'),
+                        _ => ('(', ')'),
+                    };
+                    let mut depth = 0;
+                    let mut last = c;
+                    while let Some((i, c)) = chars.next() {
+                        word.push(i, c);
+                        match c {
+                            // only a ${ opens braces, any ( parentheses
+                            _ if c == open && (last == '$' || open == '(') => depth += 1,
+                            _ if c == close => depth -= 1,
+                            '\\' => {
+                                if let Some((i, c)) = chars.next() {
+                                    word.push(i, c);
+                                }
+                            }
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                        last = c;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !word.text().is_empty() {
+            tokens.push(Token::Word(word.into_word()));
+        }
+    }
+    Ok(tokens)
+}
+
+// Read the rest of an operator that started with `c`. `fd` is the file
+// descriptor given before a redirection, `<` defaults to 0 and `>` to 1
+fn operator<'a>(c: char, fd: Option<c_int>, chars: &mut Peekable<CharIndices>) -> Token<'a> {
+    let mut next_is = |want: char| chars.next_if(|&(_, c)| c == want).is_some();
+    match c {
+        '|' => Token::Pipe,
+        '&' => Token::Ampersand,
+        '<' => {
+            let fd = fd.unwrap_or(0);
+            if next_is('&') {
+                Token::Redirect(fd, RedirectOp::Duplicate)
+            } else {
+                Token::Redirect(fd, RedirectOp::Read)
+            }
+        }
+        _ => {
+            let fd = fd.unwrap_or(1);
+            if next_is('>') {
+                Token::Redirect(fd, RedirectOp::Append)
+            } else if next_is('&') {
+                Token::Redirect(fd, RedirectOp::Duplicate)
+            } else {
+                Token::Redirect(fd, RedirectOp::Write)
+            }
+        }
+    }
+}
+
+// Parse a line into a pipeline. An empty line gives a pipeline without commands
+pub fn parse(line: &str) -> Result<Pipeline<'_>, ShellError> {
+    let tokens = tokenize(line).map_err(ShellError::Parse)?;
+    trace!("tokens", "{:?}", tokens);
+    let mut pipeline = Pipeline::default();
+    let mut command = SimpleCommand::default();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => command.words.push(word),
+            Token::Redirect(fd, op) => match tokens.next() {
+                Some(Token::Word(target)) => command.redirects.push(Redirect { fd, op, target }),
+                other => return Err(unexpected(other.as_ref())),
+            },
+            Token::Pipe => {
+                if is_empty(&command) || tokens.peek().is_none() {
+                    return Err(unexpected(Some(&Token::Pipe)));
+                }
+                pipeline.commands.push(std::mem::take(&mut command));
+            }
+            Token::Ampersand => {
+                if is_empty(&command) || tokens.peek().is_some() {
+                    return Err(unexpected(Some(&Token::Ampersand)));
+                }
+                pipeline.background = true;
+            }
+        }
+    }
+    if !is_empty(&command) {
+        pipeline.commands.push(command);
+    }
+    trace!("pipeline", "{:?}", pipeline);
+    Ok(pipeline)
+}
+
+// Whether `text` is a whole command, or it goes on on the next line: a quote
+// isn't closed yet, or it ends with a backslash or a pipe
+pub fn is_complete(text: &str) -> bool {
+    // only quotes that aren't closed make tokenizing fail
+    let Ok(tokens) = tokenize(text) else {
+        return false;
+    };
+    let backslashes = text.chars().rev().take_while(|&c| c == '\\').count();
+    tokens.last() != Some(&Token::Pipe) && backslashes % 2 == 0
+}
+
+// Split lines into the commands in them, a command is more than one line when
+// it goes on on the next
+pub fn split_commands(text: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    for line in text.split('\n') {
+        if !command.is_empty() {
+            command.push('\n');
+        }
+        command.push_str(line);
+        if is_complete(&command) {
+            commands.push(std::mem::take(&mut command));
+        }
+    }
+    if !command.is_empty() {
+        commands.push(command);
+    }
+    commands
+}
+
+// The last word of a line as it was typed, the target of a redirection counts
+// too. None when there's none or the line doesn't parse
+pub fn last_word(line: &str) -> Option<String> {
+    tokenize(line)
+        .ok()?
+        .into_iter()
+        .rev()
+        .find_map(|token| match token {
+            Token::Word(word) => Some(word.into_owned()),
+            _ => None,
+        })
+}
+
+fn is_empty(command: &SimpleCommand) -> bool {
+    command.words.is_empty() && command.redirects.is_empty()
+}
+
+fn unexpected(token: Option<&Token>) -> ShellError {
+    ShellError::Parse(format!(
+        "syntax error near unexpected token `{}'",
+        describe(token)
+    ))
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+// Table of the executables found on $PATH. It is built the first time all
+// the names are needed and only rebuilt when $PATH changes, so looking things
+// up in it never walks the directories again. Commands that are run are
+// remembered on their own, finding one doesn't need the whole table
+#[derive(Default)]
+pub struct PathCache {
+    // value of $PATH the tables were built for
+    path: Option<OsString>,
+    commands: Option<HashMap<String, PathBuf>>,
+    // commands found by name so far
+    found: HashMap<OsString, PathBuf>,
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+impl PathCache {
+    // forget everything if $PATH changed since the tables were built
+    fn check_path(&mut self) {
+        let path = env::var_os("PATH").unwrap_or_default();
+        if self.path.as_ref() != Some(&path) {
+            self.commands = None;
+            self.found.clear();
+            self.path = Some(path);
+        }
+    }
+
+    // names of all the commands on $PATH
+    pub fn names(&mut self) -> impl Iterator<Item = &str> {
+        self.check_path();
+        let path = self.path.as_deref().unwrap_or_default();
+        let commands = self.commands.get_or_insert_with(|| {
+            let mut commands = HashMap::new();
+            for dir in env::split_paths(path) {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let Ok(name) = entry.file_name().into_string() else {
+                        continue;
+                    };
+                    // the first directory on $PATH wins, like a real lookup
+                    if !commands.contains_key(&name) && is_executable(&entry.path()) {
+                        commands.insert(name, entry.path());
+                    }
+                }
+            }
+            commands
+        });
+        commands.keys().map(String::as_str)
+    }
+
+    // Whether there's a command called `name` on $PATH. Uses the table of
+    // all the names, so checking names that aren't there is cheap too
+    pub fn contains(&mut self, name: &str) -> bool {
+        // builds the table when there isn't one yet
+        let _ = self.names();
+        self.found.contains_key(OsStr::new(name))
+            || (self.commands.as_ref()).is_some_and(|commands| commands.contains_key(name))
+    }
+
+    // Find the file that running `name` would execute. A remembered path is
+    // checked before it's used, so a command that was removed or moved is
+    // looked for again
+    pub fn find(&mut self, name: &OsStr) -> Option<PathBuf> {
+        self.check_path();
+        if let Some(path) = self.found.get(name) {
+            if is_executable(path) {
+                return Some(path.clone());
+            }
+        }
+        let path = find_in_path(name)?;
+        self.found.insert(name.to_os_string(), path.clone());
+        Some(path)
+    }
+}
+
+// Find the file that running `name` would execute, the first executable
+// file with that name in the directories on $PATH
+pub fn find_in_path(name: &OsStr) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
@@ -0,0 +1,383 @@
+// Glob patterns like sh's, for the ${1#pattern} expansions: * matches any
+// text, ? any one character and [...] one of a set of them, [!...] or [^...]
+// one that isn't in it. A backslash makes the next character stand for itself.
+// With extglob there are ksh's groups of patterns too: ?(a|b) matches one of
+// them or nothing, *(a|b) any number of them, +(a|b) at least one, @(a|b)
+// exactly one and !(a|b) anything that none of them matches
+
+enum Part {
+    Char(char),
+    // a letter in either case, for nocasematch
+    Folded(char),
+    Any,
+    Star,
+    // (first, last) ranges, a single character is a range of one
+    Set {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+        // whether a letter matches when it's in the set in the other case
+        folded: bool,
+    },
+    Group {
+        kind: Group,
+        alternatives: Vec<Vec<Part>>,
+    },
+}
+
+// what the character in front of the ( of a group is
+#[derive(Clone, Copy)]
+enum Group {
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+    One,
+    Not,
+}
+
+pub struct Pattern {
+    parts: Vec<Part>,
+    // groups need the matcher that backtracks, the others don't
+    grouped: bool,
+}
+
+impl Pattern {
+    // A [ without a ] to close it is only a [, and a group without its ) is
+    // only the characters
+    pub fn new(text: &str, extglob: bool) -> Pattern {
+        let chars: Vec<char> = text.chars().collect();
+        let parts = parse(&chars, extglob);
+        let grouped = parts.iter().any(|part| matches!(part, Part::Group { .. }));
+        Pattern { parts, grouped }
+    }
+
+    // whether all of `text` matches
+    pub fn matches(&self, text: &[u8]) -> bool {
+        if self.grouped {
+            matches_parts(&self.parts, text)
+        } else {
+            matches_simple(&self.parts, text)
+        }
+    }
+
+    // The same pattern matching letters in either case
+    pub fn ignore_case(mut self) -> Pattern {
+        fold(&mut self.parts);
+        self
+    }
+
+    // `text` without the shortest or longest start that matches
+    pub fn strip_prefix<'a>(&self, text: &'a [u8], longest: bool) -> &'a [u8] {
+        let mut ends = boundaries(text);
+        if longest {
+            ends.reverse();
+        }
+        match ends.into_iter().find(|&end| self.matches(&text[..end])) {
+            Some(end) => &text[end..],
+            None => text,
+        }
+    }
+
+    // `text` without the shortest or longest end that matches
+    pub fn strip_suffix<'a>(&self, text: &'a [u8], longest: bool) -> &'a [u8] {
+        let mut starts = boundaries(text);
+        if !longest {
+            starts.reverse();
+        }
+        match starts
+            .into_iter()
+            .find(|&start| self.matches(&text[start..]))
+        {
+            Some(start) => &text[..start],
+            None => text,
+        }
+    }
+
+    // `text` with the longest match at the first place there is one replaced
+    // by `with`, every match with `all`. `anchor` only lets it match at the
+    // start or the end
+    pub fn replace(&self, text: &[u8], with: &[u8], all: bool, anchor: Anchor) -> Vec<u8> {
+        let bounds = boundaries(text);
+        let mut replaced = Vec::new();
+        // what's before this is in `replaced` already
+        let mut copied = 0;
+        let mut i = 0;
+        while i < bounds.len() {
+            let start = bounds[i];
+            let end = match anchor {
+                Anchor::Start if start > 0 => break,
+                Anchor::End => self.matches(&text[start..]).then_some(text.len()),
+                _ => {
+                    (bounds[i..].iter().rev().copied()).find(|&end| self.matches(&text[start..end]))
+                }
+            };
+            match end {
+                // an empty match is only replaced at an anchor
+                Some(end) if end > start || anchor != Anchor::None => {
+                    replaced.extend_from_slice(&text[copied..start]);
+                    replaced.extend_from_slice(with);
+                    copied = end;
+                    if !all || anchor != Anchor::None {
+                        break;
+                    }
+                    i = bounds.iter().position(|&b| b == end).unwrap();
+                }
+                _ => i += 1,
+            }
+        }
+        replaced.extend_from_slice(&text[copied..]);
+        replaced
+    }
+}
+
+// where ${1/pattern/with} may match
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    None,
+    Start,
+    End,
+}
+
+// The parts of a pattern
+fn parse(chars: &[char], extglob: bool) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let part = match chars[i] {
+            c @ ('?' | '*' | '+' | '@' | '!') if extglob && chars.get(i + 1) == Some(&'(') => {
+                match group(c, &chars[i + 2..]) {
+                    Some((part, len)) => {
+                        i += len + 1;
+                        part
+                    }
+                    None => Part::Char(c),
+                }
+            }
+            '*' => Part::Star,
+            '?' => Part::Any,
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                Part::Char(chars[i])
+            }
+            '[' => match set(&chars[i + 1..]) {
+                Some((part, len)) => {
+                    i += len;
+                    part
+                }
+                None => Part::Char('['),
+            },
+            c => Part::Char(c),
+        };
+        parts.push(part);
+        i += 1;
+    }
+    parts
+}
+
+// The group in `chars`, which come after its (, and how far it goes
+// including the ). The patterns in it are split at the | that aren't in a
+// group of their own
+fn group(c: char, chars: &[char]) -> Option<(Part, usize)> {
+    let kind = match c {
+        '?' => Group::ZeroOrOne,
+        '*' => Group::ZeroOrMore,
+        '+' => Group::OneOrMore,
+        '@' => Group::One,
+        _ => Group::Not,
+    };
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start, mut i) = (0, 0, 0);
+    loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            c @ ('|' | ')') if depth == 0 => {
+                alternatives.push(parse(&chars[start..i], true));
+                start = i + 1;
+                if *c == ')' {
+                    return Some((Part::Group { kind, alternatives }, i + 1));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+// whether all of `text` matches `parts` when there are no groups in them.
+// A * that fails to match only has to give up what it took
+fn matches_simple(parts: &[Part], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // where the last * was and how much of the text it has taken so far
+    let mut star = None;
+    loop {
+        if let Some(part) = parts.get(p) {
+            if let Part::Star = part {
+                star = Some((p, t));
+                p += 1;
+                continue;
+            }
+            if let Some(len) = part_matches(part, &text[t..]) {
+                p += 1;
+                t += len;
+                continue;
+            }
+        } else if t == text.len() {
+            return true;
+        }
+        // the * takes one more character and the rest is tried again
+        match star {
+            Some((star_p, star_t)) if star_t < text.len() => {
+                let t_next = star_t + char_len(&text[star_t..]);
+                star = Some((star_p, t_next));
+                p = star_p + 1;
+                t = t_next;
+            }
+            _ => return false,
+        }
+    }
+}
+
+// whether all of `text` matches `parts`, trying every length a * or a group
+// could take until the rest matches too
+fn matches_parts(parts: &[Part], text: &[u8]) -> bool {
+    let Some((part, rest)) = parts.split_first() else {
+        return text.is_empty();
+    };
+    match part {
+        Part::Star => (boundaries(text).into_iter()).any(|end| matches_parts(rest, &text[end..])),
+        Part::Group { kind, alternatives } => boundaries(text).into_iter().any(|end| {
+            group_matches(*kind, alternatives, &text[..end]) && matches_parts(rest, &text[end..])
+        }),
+        _ => part_matches(part, text).is_some_and(|len| matches_parts(rest, &text[len..])),
+    }
+}
+
+// whether all of `text` matches a group
+fn group_matches(kind: Group, alternatives: &[Vec<Part>], text: &[u8]) -> bool {
+    let one = |text: &[u8]| (alternatives.iter()).any(|parts| matches_parts(parts, text));
+    match kind {
+        Group::One => one(text),
+        Group::Not => !one(text),
+        Group::ZeroOrOne => text.is_empty() || one(text),
+        // one of them, and as many more after it as match. Each one has to
+        // take something or this would go round forever
+        Group::ZeroOrMore | Group::OneOrMore => {
+            if text.is_empty() {
+                return matches!(kind, Group::ZeroOrMore) || one(text);
+            }
+            one(text)
+                || boundaries(text)[1..].iter().any(|&end| {
+                    end < text.len()
+                        && one(&text[..end])
+                        && group_matches(Group::ZeroOrMore, alternatives, &text[end..])
+                })
+        }
+    }
+}
+
+// make the letters in `parts` match in either case
+fn fold(parts: &mut [Part]) {
+    for part in parts {
+        match part {
+            Part::Char(c) if c.to_lowercase().ne(c.to_uppercase()) => *part = Part::Folded(*c),
+            Part::Set { folded, .. } => *folded = true,
+            Part::Group { alternatives, .. } => {
+                alternatives.iter_mut().for_each(|parts| fold(parts))
+            }
+            _ => {}
+        }
+    }
+}
+
+// The set in `chars`, which come after a [, and how far it goes including
+// the ]. A ] right at the start is in the set
+fn set(chars: &[char]) -> Option<(Part, usize)> {
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    let mut i = usize::from(negated);
+    let mut ranges = Vec::new();
+    loop {
+        let &first = chars.get(i)?;
+        if first == ']' && !ranges.is_empty() {
+            return Some((
+                Part::Set {
+                    negated,
+                    ranges,
+                    folded: false,
+                },
+                i + 1,
+            ));
+        }
+        let last = match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&last)) if last != ']' => {
+                i += 2;
+                last
+            }
+            _ => first,
+        };
+        ranges.push((first, last));
+        i += 1;
+    }
+}
+
+// how many bytes of `text` a part matches at its start, None when it doesn't
+fn part_matches(part: &Part, text: &[u8]) -> Option<usize> {
+    if text.is_empty() {
+        return None;
+    }
+    let len = char_len(text);
+    let c = std::str::from_utf8(&text[..len])
+        .ok()
+        .and_then(|c| c.chars().next());
+    let matched = match part {
+        Part::Any => true,
+        Part::Char(want) => c == Some(*want),
+        Part::Folded(want) => {
+            c.is_some_and(|c| c == *want || c.to_lowercase().eq(want.to_lowercase()))
+        }
+        Part::Set {
+            negated,
+            ranges,
+            folded,
+        } => c.is_some_and(|c| {
+            let in_set = |c: char| ranges.iter().any(|&(first, last)| first <= c && c <= last);
+            let found =
+                in_set(c) || *folded && (c.to_lowercase().chain(c.to_uppercase())).any(in_set);
+            found != *negated
+        }),
+        Part::Star | Part::Group { .. } => unreachable!(),
+    };
+    matched.then_some(len)
+}
+
+// the length of the character `text` starts with, a byte that isn't UTF-8 is
+// a character of its own
+fn char_len(text: &[u8]) -> usize {
+    let len = match text[0] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    if len <= text.len() && std::str::from_utf8(&text[..len]).is_ok() {
+        len
+    } else {
+        1
+    }
+}
+
+// the offsets in `text` between characters, its start and end included
+pub fn boundaries(text: &[u8]) -> Vec<usize> {
+    let mut bounds = vec![0];
+    let mut i = 0;
+    while i < text.len() {
+        i += char_len(&text[i..]);
+        bounds.push(i);
+    }
+    bounds
+}
+
+// The number of characters in `text`
+pub fn char_count(text: &[u8]) -> usize {
+    boundaries(text).len() - 1
+}
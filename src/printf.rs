@@ -0,0 +1,312 @@
+// The formatting of the printf builtin: C's conversions %d %i %o %u %x %X %e
+// %E %f %F %g %G %c and %s with their flags, width and precision, %b for a
+// string with backslash escapes in it and %q for one quoted so the shell
+// reads it back as it is. The format is used again for as long as arguments
+// are left, a missing one is an empty string or 0
+
+use std::{ffi::OsString, os::unix::ffi::OsStrExt};
+
+use crate::{
+    parser,
+    sys::{self, Number},
+};
+
+// What the format made of the arguments, and what was wrong with them
+#[derive(Default)]
+pub struct Formatted {
+    pub output: Vec<u8>,
+    pub errors: Vec<String>,
+}
+
+pub fn format(format: &[u8], args: &[OsString]) -> Formatted {
+    let mut formatted = Formatted::default();
+    let mut args = args.iter().map(|arg| arg.as_bytes());
+    loop {
+        let left = args.len();
+        // a \c in a %b stops everything, a bad conversion too
+        if !format_once(format, &mut args, &mut formatted) {
+            break;
+        }
+        // a format without conversions is only printed once
+        if args.len() == 0 || args.len() == left {
+            break;
+        }
+    }
+    formatted
+}
+
+// one go through the format, false when it had to stop in the middle
+fn format_once<'a>(
+    format: &[u8],
+    args: &mut impl Iterator<Item = &'a [u8]>,
+    formatted: &mut Formatted,
+) -> bool {
+    let mut i = 0;
+    while i < format.len() {
+        match format[i] {
+            b'\\' => {
+                let (bytes, len) = escape(&format[i + 1..], false);
+                formatted.output.extend_from_slice(&bytes);
+                i += len + 1;
+            }
+            b'%' if format.get(i + 1) == Some(&b'%') => {
+                formatted.output.push(b'%');
+                i += 2;
+            }
+            b'%' => {
+                let Some((spec, len)) = spec(&format[i + 1..], args, formatted) else {
+                    let len = (format[i + 1..].iter())
+                        .position(|c| !b"-+ #0123456789.*".contains(c))
+                        .map_or(format.len() - i, |len| len + 2);
+                    let conversion =
+                        String::from_utf8_lossy(&format[i..(i + len).min(format.len())]);
+                    formatted
+                        .errors
+                        .push(format!("{}: invalid conversion", conversion));
+                    return false;
+                };
+                i += len + 1;
+                if !convert(&spec, args.next().unwrap_or_default(), formatted) {
+                    return false;
+                }
+            }
+            byte => {
+                formatted.output.push(byte);
+                i += 1;
+            }
+        }
+    }
+    true
+}
+
+// A conversion like %-8.3s with the * in it taken from the arguments
+struct Spec {
+    flags: String,
+    width: Option<i64>,
+    precision: Option<i64>,
+    conversion: u8,
+}
+
+// the conversion in `format`, which comes after the %, and its length
+fn spec<'a>(
+    format: &[u8],
+    args: &mut impl Iterator<Item = &'a [u8]>,
+    formatted: &mut Formatted,
+) -> Option<(Spec, usize)> {
+    let mut i = 0;
+    let mut flags = String::new();
+    while let Some(&flag @ (b'-' | b'+' | b' ' | b'#' | b'0')) = format.get(i) {
+        flags.push(char::from(flag));
+        i += 1;
+    }
+    let mut number = |i: &mut usize| {
+        if format.get(*i) == Some(&b'*') {
+            *i += 1;
+            return Some(integer(args.next().unwrap_or_default(), formatted));
+        }
+        let start = *i;
+        while format.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        let digits = std::str::from_utf8(&format[start..*i]).ok()?;
+        digits.parse().ok()
+    };
+    let width = number(&mut i);
+    let precision = match format.get(i) {
+        Some(b'.') => {
+            i += 1;
+            Some(number(&mut i).unwrap_or(0))
+        }
+        _ => None,
+    };
+    let &conversion = format.get(i)?;
+    b"diouxXeEfFgGcsbq".contains(&conversion).then_some((
+        Spec {
+            flags,
+            width,
+            precision,
+            conversion,
+        },
+        i + 1,
+    ))
+}
+
+// format one argument, false after a \c in a %b
+fn convert(spec: &Spec, arg: &[u8], formatted: &mut Formatted) -> bool {
+    let text = match spec.conversion {
+        b's' => arg.to_vec(),
+        b'b' => {
+            let (text, stopped) = escapes(arg);
+            pad(spec, &text, &mut formatted.output);
+            return !stopped;
+        }
+        b'q' => parser::quote(&String::from_utf8_lossy(arg))
+            .into_owned()
+            .into_bytes(),
+        b'c' => {
+            let len = std::str::from_utf8(arg)
+                .map_or(1, |text| text.chars().next().map_or(0, char::len_utf8));
+            arg[..len.min(arg.len())].to_vec()
+        }
+        conversion => {
+            let number = match conversion {
+                b'd' | b'i' => Number::Signed(integer(arg, formatted)),
+                b'o' | b'u' | b'x' | b'X' => Number::Unsigned(integer(arg, formatted) as u64),
+                _ => Number::Float(float(arg, formatted)),
+            };
+            let length = if matches!(number, Number::Float(_)) {
+                ""
+            } else {
+                "ll"
+            };
+            let mut c_spec = format!("%{}", spec.flags);
+            if let Some(width) = spec.width {
+                c_spec.push_str(&width.to_string());
+            }
+            if let Some(precision) = spec.precision {
+                c_spec.push_str(&format!(".{}", precision));
+            }
+            c_spec.push_str(length);
+            c_spec.push(char::from(conversion));
+            let text = sys::format_number(&c_spec, number);
+            formatted.output.extend_from_slice(&text);
+            return true;
+        }
+    };
+    pad(spec, &text, &mut formatted.output);
+    true
+}
+
+// `text` cut to the precision and padded to the width with spaces, on the
+// right with the - flag
+fn pad(spec: &Spec, text: &[u8], output: &mut Vec<u8>) {
+    let len = match spec.precision {
+        Some(precision) if precision >= 0 => text.len().min(precision as usize),
+        _ => text.len(),
+    };
+    let width = spec.width.unwrap_or(0);
+    let left = spec.flags.contains('-') || width < 0;
+    let padding = (width.unsigned_abs() as usize).saturating_sub(len);
+    if !left {
+        output.resize(output.len() + padding, b' ');
+    }
+    output.extend_from_slice(&text[..len]);
+    if left {
+        output.resize(output.len() + padding, b' ');
+    }
+}
+
+// The number an argument stands for: decimal, hex after 0x, octal after a 0,
+// or the code of the character after a ' or ". A bad one is 0, or what it
+// starts with
+fn integer(arg: &[u8], formatted: &mut Formatted) -> i64 {
+    let text = String::from_utf8_lossy(arg);
+    let text = text.trim_start();
+    if let Some(c) = text.strip_prefix(['\'', '"']) {
+        return c.chars().next().map_or(0, |c| i64::from(u32::from(c)));
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (radix, digits) = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        Some(digits) => (16, digits),
+        None if digits.len() > 1 && digits.starts_with('0') => (8, &digits[1..]),
+        None => (10, digits),
+    };
+    let end = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let value = i64::from_str_radix(&digits[..end], radix).unwrap_or(0);
+    if end == 0 && !text.is_empty() || end < digits.len() {
+        formatted
+            .errors
+            .push(format!("{}: invalid number", String::from_utf8_lossy(arg)));
+    }
+    if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    }
+}
+
+fn float(arg: &[u8], formatted: &mut Formatted) -> f64 {
+    let text = String::from_utf8_lossy(arg);
+    if let Some(c) = text.trim_start().strip_prefix(['\'', '"']) {
+        return c.chars().next().map_or(0.0, |c| f64::from(u32::from(c)));
+    }
+    match text.trim() {
+        "" => 0.0,
+        number => number.parse().unwrap_or_else(|_| {
+            formatted.errors.push(format!("{}: invalid number", text));
+            0.0
+        }),
+    }
+}
+
+// What the escape after a backslash in `text` stands for and how long it is.
+// In a %b an octal number starts with a 0 and \c stops the output, which is
+// left to `escapes`
+fn escape(text: &[u8], in_b: bool) -> (Vec<u8>, usize) {
+    let Some(&c) = text.first() else {
+        return (b"\\".to_vec(), 0);
+    };
+    let byte = match c {
+        b'a' => 0x07,
+        b'b' => 0x08,
+        b'e' => 0x1b,
+        b'f' => 0x0c,
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'v' => 0x0b,
+        b'\\' | b'"' | b'\'' => c,
+        b'x' => {
+            let digits = hex_digits(&text[1..]);
+            if digits == 0 {
+                return (b"\\x".to_vec(), 1);
+            }
+            let value = std::str::from_utf8(&text[1..1 + digits]).unwrap();
+            return (vec![u8::from_str_radix(value, 16).unwrap()], digits + 1);
+        }
+        b'0'..=b'7' => {
+            // %b's octal escapes are \0NNN, the format's \NNN
+            let start = usize::from(in_b && c == b'0');
+            let digits = (text[start..].iter().take(3))
+                .take_while(|&&c| (b'0'..=b'7').contains(&c))
+                .count();
+            let value = std::str::from_utf8(&text[start..start + digits]).unwrap();
+            let value = u32::from_str_radix(value, 8).unwrap_or(0);
+            return (vec![value as u8], start + digits);
+        }
+        _ => return (vec![b'\\', c], 1),
+    };
+    (vec![byte], 1)
+}
+
+fn hex_digits(text: &[u8]) -> usize {
+    (text.iter().take(2))
+        .take_while(|c| c.is_ascii_hexdigit())
+        .count()
+}
+
+// The argument of a %b with its escapes replaced, and whether a \c in it
+// stops the output
+fn escapes(arg: &[u8]) -> (Vec<u8>, bool) {
+    let mut text = Vec::new();
+    let mut i = 0;
+    while i < arg.len() {
+        if arg[i] != b'\\' {
+            text.push(arg[i]);
+            i += 1;
+            continue;
+        }
+        if arg.get(i + 1) == Some(&b'c') {
+            return (text, true);
+        }
+        let (bytes, len) = escape(&arg[i + 1..], true);
+        text.extend_from_slice(&bytes);
+        i += len + 1;
+    }
+    (text, false)
+}
@@ -0,0 +1,252 @@
+use std::env;
+
+use crate::{git::Git, named_dirs::NamedDirs, sys};
+
+// Expanded prompts mark the parts that take no room on the screen, like
+// terminal escape sequences, with these. Readline uses the same ones
+pub const HIDDEN_START: char = '\x01';
+pub const HIDDEN_END: char = '\x02';
+
+// what the escapes of a prompt show about the shell
+pub struct Info<'a> {
+    // exit status of the last command
+    pub status: i32,
+    // number of jobs in the job table
+    pub jobs: usize,
+    pub git: &'a mut Git,
+    // for \w to show the directories in them as ~name
+    pub named_dirs: &'a NamedDirs,
+}
+
+// The prompt shown before a command, $PS1 expanded again every time so it's
+// up to date
+pub fn primary(info: &mut Info) -> String {
+    from_variable("PS1", info)
+}
+
+// The prompt shown before the lines a command goes on on, $PS2
+pub fn secondary(info: &mut Info) -> String {
+    from_variable("PS2", info)
+}
+
+// The prompt shown at the right end of the row the command is typed in,
+// $RPS1 or $RPROMPT like zsh has it. Empty when neither is set
+pub fn right(info: &mut Info) -> String {
+    match env::var_os("RPS1").or_else(|| env::var_os("RPROMPT")) {
+        Some(template) => expand(&template.to_string_lossy(), info),
+        None => String::new(),
+    }
+}
+
+fn from_variable(name: &str, info: &mut Info) -> String {
+    match env::var_os(name) {
+        Some(template) => expand(&template.to_string_lossy(), info),
+        None => "> ".to_string(),
+    }
+}
+
+// Colors and the like in \[ \] are left out when the prompt doesn't go to a
+// terminal or $NO_COLOR is set
+fn colors() -> bool {
+    let terminal = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+    terminal && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+}
+
+// Expand the backslash escapes of a prompt like $PS1. Besides the ones of
+// bash there's \? for the exit status and \( \) around a part only shown
+// when it isn't 0, with \| in between for what's shown when it is. \g is the
+// git branch with a * when there are changes, and \{ \} go around a part
+// that's left out when none of the escapes in it show anything, or a \j of
+// 0 jobs. The escapes that aren't known are left as they are
+pub fn expand(template: &str, info: &mut Info) -> String {
+    let status = info.status;
+    let colors = colors();
+    let mut out = String::new();
+    // for each \( and \[ that's still open, whether its text is shown
+    let mut open: Vec<bool> = Vec::new();
+    // for each \{ that's still open, where its text starts and whether an
+    // escape in it showed something
+    let mut groups: Vec<(usize, bool)> = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        let shown = open.iter().all(|&shown| shown);
+        if c != '\\' {
+            if shown {
+                out.push(c);
+            }
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            if shown {
+                out.push('\\');
+            }
+            break;
+        };
+        match escape {
+            '(' => open.push(status != 0),
+            '|' => {
+                if let Some(last) = open.last_mut() {
+                    *last = !*last;
+                }
+            }
+            ')' => {
+                open.pop();
+            }
+            '[' => {
+                if shown && colors {
+                    out.push(HIDDEN_START);
+                }
+                open.push(colors);
+            }
+            ']' => {
+                if shown && colors {
+                    out.push(HIDDEN_END);
+                }
+                open.pop();
+            }
+            // a character by its octal code, like \033 for escape
+            '{' => groups.push((out.len(), false)),
+            '}' => {
+                if let Some((start, false)) = groups.pop() {
+                    out.truncate(start);
+                }
+            }
+            '0'..='7' => {
+                let mut code = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.next_if(|c| c.is_digit(8)) {
+                        Some(digit) => code = code * 8 + digit.to_digit(8).unwrap_or(0),
+                        None => break,
+                    }
+                }
+                if let (true, Some(c)) = (shown, char::from_u32(code)) {
+                    out.push(c);
+                }
+            }
+            _ if !shown => {}
+            'g' => {
+                let dir = env::current_dir().unwrap_or_default();
+                if let Some(status) = info.git.status(&dir) {
+                    out.push_str(&status);
+                    groups.iter_mut().for_each(|group| group.1 = true);
+                }
+            }
+            'j' => {
+                out.push_str(&info.jobs.to_string());
+                if info.jobs > 0 {
+                    groups.iter_mut().for_each(|group| group.1 = true);
+                }
+            }
+            'u' => out.push_str(&sys::user_name()),
+            'h' => out.push_str(sys::host_name().split('.').next().unwrap_or_default()),
+            'H' => out.push_str(&sys::host_name()),
+            'w' => out.push_str(&trimmed_directory(info.named_dirs)),
+            'W' => {
+                let dir = working_directory(info.named_dirs);
+                match dir.rsplit_once('/') {
+                    Some(("", "")) => out.push('/'),
+                    Some((_, name)) => out.push_str(name),
+                    None => out.push_str(&dir),
+                }
+            }
+            '$' => out.push(if unsafe { libc::geteuid() } == 0 {
+                '#'
+            } else {
+                '$'
+            }),
+            '?' => out.push_str(&status.to_string()),
+            't' => out.push_str(&sys::local_time("%H:%M:%S")),
+            'd' => out.push_str(&sys::local_time("%a %b %d")),
+            'n' => out.push('\n'),
+            'e' => out.push('\x1b'),
+            'a' => out.push('\x07'),
+            '\\' => out.push('\\'),
+            c => {
+                out.push('\\');
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+// $PWD keeps the path the user went through symlinks by, and is still there
+// when the directory was deleted
+fn working_directory(named_dirs: &NamedDirs) -> String {
+    let dir = match env::var_os("PWD") {
+        Some(pwd) => pwd.to_string_lossy().into_owned(),
+        None => match env::current_dir() {
+            Ok(dir) => dir.to_string_lossy().into_owned(),
+            Err(_) => return ".".to_string(),
+        },
+    };
+    abbreviated(dir, named_dirs)
+}
+
+// A directory inside $HOME starting with ~ instead, and one inside a named
+// directory with ~name, the deepest of them when it's in more than one
+pub fn abbreviated(dir: String, named_dirs: &NamedDirs) -> String {
+    let home = env::var("HOME").unwrap_or_default();
+    let named = (named_dirs.iter()).filter_map(|(name, path)| Some((name, path.to_str()?)));
+    // the length of the directory it's in and how it's shown
+    let mut deepest: Option<(usize, String)> = None;
+    for (name, base) in std::iter::once(("", home.as_str())).chain(named) {
+        let base = base.trim_end_matches('/');
+        let inside = |rest: &str| rest.is_empty() || rest.starts_with('/');
+        let deeper = deepest.as_ref().is_none_or(|(len, _)| base.len() > *len);
+        match dir.strip_prefix(base) {
+            Some(rest) if !base.is_empty() && inside(rest) && deeper => {
+                deepest = Some((base.len(), format!("~{}{}", name, rest)));
+            }
+            _ => {}
+        }
+    }
+    deepest.map_or(dir, |(_, shown)| shown)
+}
+
+// \w, with only the last $PROMPT_DIRTRIM parts of a deep directory
+fn trimmed_directory(named_dirs: &NamedDirs) -> String {
+    let dir = working_directory(named_dirs);
+    let keep = env::var("PROMPT_DIRTRIM")
+        .ok()
+        .and_then(|keep| keep.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    let (start, rest) = match dir.strip_prefix('~') {
+        Some(rest) => {
+            let name_len = rest.find('/').unwrap_or(rest.len());
+            (format!("~{}/", &rest[..name_len]), &rest[name_len..])
+        }
+        None => (String::new(), dir.as_str()),
+    };
+    let parts: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    if keep == 0 || parts.len() <= keep {
+        return dir;
+    }
+    format!("{}.../{}", start, parts[parts.len() - keep..].join("/"))
+}
+
+// what to write to the terminal for an expanded prompt
+pub fn printed(prompt: &str) -> String {
+    prompt
+        .chars()
+        .filter(|&c| c != HIDDEN_START && c != HIDDEN_END)
+        .collect()
+}
+
+// the characters of an expanded prompt that show up on the screen, for the
+// line editor to know where the cursor is
+#[cfg(feature = "editor")]
+pub fn visible(prompt: &str) -> impl Iterator<Item = char> + '_ {
+    let mut hidden = false;
+    prompt.chars().filter(move |&c| match c {
+        HIDDEN_START => {
+            hidden = true;
+            false
+        }
+        HIDDEN_END => {
+            hidden = false;
+            false
+        }
+        _ => !hidden,
+    })
+}
@@ -0,0 +1,152 @@
+use std::{
+    ffi::OsStr,
+    fs::OpenOptions,
+    io::{self, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::Path,
+};
+
+use crate::{error::ShellError, parser::RedirectOp, sys};
+
+// where a redirected file descriptor gets pointed
+enum Source {
+    File(OwnedFd),
+    Fd(RawFd),
+    Close,
+}
+
+// A redirection with its file already opened, so putting it in place can't
+// fail for any reason the user could have avoided
+pub struct Redirection {
+    fd: RawFd,
+    source: Source,
+}
+
+// Open the target of a redirection. `earlier` are the redirections of the
+// same command before it, a file descriptor they set up can be duplicated
+pub fn open(
+    fd: RawFd,
+    op: RedirectOp,
+    target: &OsStr,
+    earlier: &[Redirection],
+) -> Result<Redirection, ShellError> {
+    let error = |source| ShellError::Redirect {
+        path: Path::new(target).to_path_buf(),
+        source,
+    };
+    let file = match op {
+        RedirectOp::Read => OpenOptions::new().read(true).open(target),
+        RedirectOp::Write => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(target),
+        RedirectOp::Append => OpenOptions::new().append(true).create(true).open(target),
+        RedirectOp::Duplicate => {
+            if target == "-" {
+                return Ok(Redirection {
+                    fd,
+                    source: Source::Close,
+                });
+            }
+            let Some(source) = target.to_str().and_then(|t| t.parse::<RawFd>().ok()) else {
+                let name = Path::new(target).display().to_string();
+                return Err(ShellError::AmbiguousRedirect(name));
+            };
+            let open = earlier.iter().any(|r| r.fd == source)
+                || unsafe { libc::fcntl(source, libc::F_GETFD) } >= 0;
+            if !open {
+                return Err(error(io::Error::from_raw_os_error(libc::EBADF)));
+            }
+            return Ok(Redirection {
+                fd,
+                source: Source::Fd(source),
+            });
+        }
+    };
+    let file = file.map_err(error)?;
+    // keep it out of the way of the descriptors scripts use themselves, it's
+    // only open in the child until the exec
+    let moved = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 10) };
+    if moved < 0 {
+        return Err(error(io::Error::last_os_error()));
+    }
+    Ok(Redirection {
+        fd,
+        source: Source::File(unsafe { OwnedFd::from_raw_fd(moved) }),
+    })
+}
+
+// Point `fd` at a file that's already open
+pub fn to(fd: RawFd, file: OwnedFd) -> Redirection {
+    Redirection {
+        fd,
+        source: Source::File(file),
+    }
+}
+
+// Put the redirections in place in the current process, in order. Only makes
+// syscalls so it can run between fork and exec
+pub fn apply(redirections: &[Redirection]) -> io::Result<()> {
+    for redirection in redirections {
+        let fd = redirection.fd;
+        let result = unsafe {
+            match &redirection.source {
+                Source::File(file) => sys::retry(|| libc::dup2(file.as_raw_fd(), fd)),
+                // dup2 onto itself would leave close-on-exec set
+                Source::Fd(source) if *source == fd => libc::fcntl(fd, libc::F_SETFD, 0),
+                Source::Fd(source) => sys::retry(|| libc::dup2(*source, fd)),
+                Source::Close => {
+                    libc::close(fd);
+                    0
+                }
+            }
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// The shell's own file descriptors from before a builtin was redirected, they
+// are put back when this is dropped
+pub struct Saved(Vec<(RawFd, Option<OwnedFd>)>);
+
+// Redirect the shell itself, for a builtin that runs in it
+pub fn redirect_shell(redirections: &[Redirection]) -> io::Result<Saved> {
+    let _ = io::stdout().flush();
+    let mut saved = Saved(Vec::new());
+    for redirection in redirections {
+        if saved.0.iter().any(|(fd, _)| *fd == redirection.fd) {
+            continue;
+        }
+        let copy = unsafe { libc::fcntl(redirection.fd, libc::F_DUPFD_CLOEXEC, 10) };
+        let copy = (copy >= 0).then(|| unsafe { OwnedFd::from_raw_fd(copy) });
+        saved.0.push((redirection.fd, copy));
+    }
+    apply(redirections)?;
+    Ok(saved)
+}
+
+impl Saved {
+    // leave the redirections in place
+    pub fn keep(mut self) {
+        self.0.clear();
+    }
+}
+
+impl Drop for Saved {
+    fn drop(&mut self) {
+        // output of the builtin still buffered goes where it was redirected
+        let _ = io::stdout().flush();
+        for (fd, copy) in self.0.drain(..).rev() {
+            unsafe {
+                match copy {
+                    Some(copy) => sys::retry(|| libc::dup2(copy.as_raw_fd(), fd)),
+                    None => libc::close(fd),
+                };
+            }
+        }
+    }
+}
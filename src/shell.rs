@@ -0,0 +1,735 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::Ordering,
+};
+
+use libc::{c_int, STDIN_FILENO};
+
+use crate::{
+    builtins::Builtins,
+    compspec::Specs,
+    error::ShellError,
+    error_location, error_message,
+    exec::ExitStatus,
+    git::Git,
+    history::{self, History},
+    input::{Context, ReadLine, INTERRUPTED},
+    jobs::Jobs,
+    keymap::Keymaps,
+    named_dirs::NamedDirs,
+    options::Options,
+    parser,
+    path_cache::PathCache,
+    prompt,
+    sys::{self, Termios},
+    term::export_terminal_size,
+    trap::{Trap, Traps},
+};
+
+// Export the variables programs expect from the shell that started them.
+// Done before any threads exist since it changes the environment
+pub fn export_shell_variables() {
+    if let Ok(exe) = env::current_exe() {
+        env::set_var("SHELL", exe);
+    }
+    let level = env::var("SHLVL")
+        .ok()
+        .and_then(|level| level.trim().parse::<i64>().ok())
+        .unwrap_or(0);
+    env::set_var("SHLVL", (level.max(0) + 1).to_string());
+    // an inherited $PWD is kept as long as it's still the current directory,
+    // so a path through a symlink isn't replaced by the resolved one
+    let pwd_current = env::var_os("PWD").is_some_and(|pwd| {
+        let pwd = Path::new(&pwd);
+        match (fs::metadata(pwd), fs::metadata(".")) {
+            (Ok(a), Ok(b)) => pwd.is_absolute() && a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    });
+    if !pwd_current {
+        if let Ok(cwd) = env::current_dir() {
+            env::set_var("PWD", cwd);
+        }
+    }
+}
+
+// Take the environment an sh profile sets up, like /etc/profile's $PATH.
+// ish can't run sh's if and for, so sh runs the profile and prints the
+// environment it leaves behind; what the profile itself prints goes to stderr
+fn import_profile(profile: &Path) {
+    if !profile.exists() {
+        return;
+    }
+    let output = Command::new("/bin/sh")
+        .args(["-c", ". \"$1\" >&2; env", "sh"])
+        .arg(profile)
+        .stderr(Stdio::inherit())
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output.stdout,
+        Ok(_) => return,
+        Err(e) => {
+            eprintln!("ish: {}: {}", profile.display(), error_message(&e));
+            return;
+        }
+    };
+    // a value can go on over several lines, the next variable starts a line
+    // with NAME=
+    let mut variables: Vec<(&[u8], Vec<u8>)> = Vec::new();
+    for line in output.split(|&b| b == b'\n') {
+        let name_end = line.iter().position(|&b| b == b'=').filter(|&end| {
+            let name = &line[..end];
+            !name.is_empty()
+                && !name[0].is_ascii_digit()
+                && name.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_')
+        });
+        match (name_end, variables.last_mut()) {
+            (Some(end), _) => variables.push((&line[..end], line[end + 1..].to_vec())),
+            (None, Some((_, value))) => {
+                value.push(b'\n');
+                value.extend_from_slice(line);
+            }
+            (None, None) => {}
+        }
+    }
+    for (name, value) in variables {
+        // what sh keeps for itself is left as ish has it
+        let name = OsStr::from_bytes(name);
+        if !["_", "PWD", "OLDPWD", "SHLVL"]
+            .iter()
+            .any(|own| name == *own)
+            && env::var_os(name).as_deref() != Some(OsStr::from_bytes(&value))
+        {
+            env::set_var(name, OsStr::from_bytes(&value));
+        }
+    }
+}
+
+// Size limit from a variable like $HISTSIZE, the default when it's unset or
+// not a number
+fn history_size(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+// Load the history of an interactive shell from $HISTFILE or ~/.ish_history,
+// keeping $HISTSIZE entries in memory and $HISTFILESIZE in the file
+fn load_history() -> History {
+    let Some(file) = env::var_os("HISTFILE")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".ish_history")))
+    else {
+        return History::default();
+    };
+    let max_entries = history_size("HISTSIZE", history::DEFAULT_SIZE);
+    let max_file_entries = history_size("HISTFILESIZE", max_entries);
+    History::load(file.clone(), max_entries, max_file_entries).unwrap_or_else(|e| {
+        eprintln!("ish: {}: {}", file.display(), error_message(&e));
+        History::default()
+    })
+}
+
+// Read a line of input. Only a read interrupted by Ctrl-C fails with
+// ErrorKind::Interrupted, one interrupted by any other signal is retried
+pub fn read_line(
+    input: &mut dyn ReadLine,
+    line: &mut String,
+    context: &mut Context,
+) -> io::Result<usize> {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    loop {
+        match input.read_line(line, context) {
+            Err(e)
+                if e.kind() == io::ErrorKind::Interrupted
+                    && !INTERRUPTED.load(Ordering::SeqCst) => {}
+            result => return result,
+        }
+    }
+}
+
+// State of the shell that outlives a single command line
+pub struct Shell {
+    // background and stopped jobs
+    pub jobs: Jobs,
+    // exit status of the last command, the value of $?
+    pub last_status: i32,
+    pub options: Options,
+    pub path_cache: PathCache,
+    pub history: History,
+    // what the keys do in the line editor
+    pub keymaps: Keymaps,
+    // what the complete builtin set up for the arguments of commands
+    pub completions: Specs,
+    // commands run by the shell itself, looked up before $PATH
+    pub builtins: Builtins,
+    // what the next line starts out with in the line editor
+    pub next_line: String,
+    // for the git branch in the prompt
+    pub git: Git,
+    // the hooks like $PROMPT_COMMAND that were found not to parse, by name
+    pub broken_hooks: HashMap<&'static str, String>,
+    // what the trap builtin set up, and whether one of them is running;
+    // what it runs doesn't set traps off
+    pub traps: Traps,
+    pub running_trap: bool,
+    // the directories namedir gave names to
+    pub named_dirs: NamedDirs,
+    // the directories pushd left, the last one first. The current directory
+    // isn't on it
+    pub dir_stack: Vec<PathBuf>,
+    // $0 followed by $1, $2...: the script and its arguments, or the shell itself
+    pub positional: Vec<OsString>,
+    // the last word of the last command that ran, the value of $_
+    pub last_argument: OsString,
+    // path of the ish binary itself, to run scripts that the kernel can't execute
+    pub shell_exe: PathBuf,
+    // Everything that depends on a user sitting at a terminal (prompts, job
+    // control, suggestions) checks this, it's decided once at startup
+    pub interactive: bool,
+    pub job_control: bool,
+    // a restricted shell doesn't change directory, run commands by path,
+    // redirect output to files or replace itself. Set at startup, nothing
+    // turns it off
+    pub restricted: bool,
+    // private copy of the terminal, it stays open in children until they exec
+    // so they can take the terminal themselves
+    pub shell_terminal: c_int,
+    // modes of the terminal when the shell started, and the ones it's put back
+    // in after every foreground job. None without a terminal
+    pub startup_modes: Option<Termios>,
+    pub terminal_modes: Option<Termios>,
+    // file commands are being read from and the line in it, for error messages
+    pub source_name: Option<String>,
+    pub line_number: usize,
+}
+
+impl Shell {
+    // A shell that isn't interactive and doesn't touch the terminal, for a
+    // program that runs commands in it with run_line and run_script
+    pub fn new(options: Options) -> Shell {
+        Shell {
+            jobs: Jobs::start(),
+            last_status: 0,
+            options,
+            path_cache: PathCache::default(),
+            keymaps: Keymaps::default(),
+            completions: Specs::default(),
+            builtins: Builtins::default(),
+            next_line: String::new(),
+            git: Git::default(),
+            broken_hooks: HashMap::new(),
+            traps: Traps::default(),
+            running_trap: false,
+            named_dirs: NamedDirs::default(),
+            dir_stack: Vec::new(),
+            history: History::default(),
+            positional: vec!["ish".into()],
+            last_argument: OsString::new(),
+            shell_exe: env::current_exe().unwrap_or_else(|_| PathBuf::from("ish")),
+            interactive: false,
+            job_control: false,
+            restricted: false,
+            shell_terminal: -1,
+            startup_modes: None,
+            terminal_modes: None,
+            source_name: None,
+            line_number: 0,
+        }
+    }
+
+    // The shell of the ish binary. An interactive one loads the history and
+    // takes the terminal on stdin, if there is one, for job control
+    pub fn start(
+        positional: Vec<OsString>,
+        interactive: bool,
+        source_name: Option<String>,
+    ) -> Shell {
+        let mut shell = Shell::new(Options::default());
+        // $_ starts out as the shell or script that's run
+        shell.last_argument = positional[0].clone();
+        shell.positional = positional;
+        shell.source_name = source_name;
+        if !interactive {
+            return shell;
+        }
+        shell.interactive = true;
+        shell.history = load_history();
+        shell.shell_terminal = unsafe { libc::fcntl(STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 10) };
+        // there's no job control without a terminal, or without its modes to
+        // put back after each job
+        if let Ok(modes) = Termios::current(shell.shell_terminal) {
+            shell.job_control = true;
+            shell.startup_modes = Some(modes);
+            shell.terminal_modes = Some(modes);
+        }
+        shell
+    }
+
+    // Prefix for error messages at the current line
+    pub fn location(&self) -> String {
+        error_location(self.source_name.as_deref(), self.line_number)
+    }
+
+    // Show an error as `location: message`, the one place errors of running
+    // a line are printed
+    pub fn warn(&self, err: ShellError) {
+        eprintln!("{}: {}", self.location(), err);
+    }
+
+    // Show an error and make its status the status of the line
+    pub fn report(&mut self, err: ShellError) {
+        self.last_status = err.status();
+        self.warn(err);
+    }
+
+    // Run a line of input, showing the error that ended it if one did.
+    // Returns the exit status if the shell should exit
+    fn run_and_report(&mut self, line: &str) -> Option<i32> {
+        match self.run_line(line) {
+            Ok(ExitStatus::Done(_)) => None,
+            Ok(ExitStatus::Exit(status)) => Some(status),
+            Err(e) => {
+                self.warn(e);
+                None
+            }
+        }
+    }
+
+    // Read and run commands until the end of the input. When `prompt` is set
+    // the input is the user's: a prompt is shown before each line and lines
+    // are added to the history. Returns the exit status if the shell should exit
+    pub fn run_lines(&mut self, input: &mut dyn ReadLine, prompt: bool) -> Option<i32> {
+        // ^D on an empty line in a row, with ignoreeof the shell gives in after 10
+        let mut eofs = 0;
+        // read into, again for each line
+        let mut line = String::new();
+        'lines: loop {
+            if prompt {
+                self.recover_terminal();
+                if let Some(status) = self.run_prompt_command() {
+                    return Some(status);
+                }
+                // jobs that just finished are reported before the prompt,
+                // and aren't counted in it
+                self.jobs.reap();
+                export_terminal_size();
+                self.start_prompt_line();
+                input.show_prompt(&prompt::primary(&mut self.prompt_info()));
+            }
+            line.clear();
+
+            // exit when ^D is pressed
+            let mut context = self.context();
+            let read = read_line(input, &mut line, &mut context);
+            let edit = context.edit;
+            match read {
+                Ok(0) if !prompt => return None,
+                Ok(0) => {
+                    eofs += 1;
+                    if self.options.ignoreeof && eofs < 10 {
+                        eprintln!("Use \"exit\" to leave the shell.");
+                        continue;
+                    }
+                    eprintln!("exit");
+                    return None;
+                }
+                Ok(_) => eofs = 0,
+                // Ctrl-C at the prompt throws away what was typed
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                    input.interrupted();
+                    self.last_status = 130;
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Error reading input: {}", err);
+                    return None;
+                }
+            }
+            self.line_number += 1;
+            let first_line = self.line_number;
+            if prompt {
+                // it can have changed while the line was typed
+                export_terminal_size();
+            }
+            line.truncate(line.trim_end_matches('\n').len());
+            if edit {
+                match self.edit_line(&line) {
+                    // shown like the expanded history references, so it's
+                    // clear what runs
+                    Some(edited) if self.options.editexec => {
+                        println!("{}", edited);
+                        line = edited;
+                    }
+                    Some(edited) => {
+                        self.next_line = edited;
+                        continue;
+                    }
+                    None => continue,
+                }
+            }
+            // a command that isn't finished goes on on the next line, at the
+            // end of the input it's run as it is and fails to parse
+            while !parser::is_complete(&line) {
+                if prompt {
+                    input.show_prompt(&prompt::secondary(&mut self.prompt_info()));
+                }
+                let mut more = String::new();
+                match read_line(input, &mut more, &mut self.context()) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                        input.interrupted();
+                        self.last_status = 130;
+                        continue 'lines;
+                    }
+                    Err(err) => {
+                        eprintln!("Error reading input: {}", err);
+                        return None;
+                    }
+                }
+                self.line_number += 1;
+                line.push('\n');
+                line.push_str(more.trim_end_matches('\n'));
+            }
+            if prompt && !self.options.posix {
+                // history references are only expanded in what the user types,
+                // the expanded line is echoed so it's clear what runs
+                match self.history.expand(&line) {
+                    Ok(Some(expanded)) => {
+                        trace!("history", "{:?} -> {:?}", line, expanded);
+                        println!("{}", expanded);
+                        line = expanded;
+                    }
+                    Ok(None) => {}
+                    Err(event) => {
+                        eprintln!("ish: {}: event not found", event);
+                        continue;
+                    }
+                }
+            }
+            if prompt && !line.trim().is_empty() {
+                let file = self.history.file().cloned().unwrap_or_default();
+                if let Err(e) = self.history.add(&line) {
+                    eprintln!("ish: {}: {}", file.display(), error_message(&e));
+                }
+            }
+            if prompt && !line.trim().is_empty() {
+                if let Some(status) = self.run_preexec_command(&line) {
+                    return Some(status);
+                }
+            }
+            // what was typed or pasted into the line editor can be several
+            // commands, each is run at the line it starts on
+            let last_line = self.line_number;
+            let mut command_line = first_line;
+            for command in parser::split_commands(&line) {
+                self.line_number = command_line;
+                command_line += command.matches('\n').count() + 1;
+                // with noexec what's read is only parsed, a script that has
+                // a syntax error ends with status 2
+                if self.options.noexec && !self.interactive {
+                    if let Err(e) = parser::parse(&command) {
+                        self.report(e);
+                    }
+                    continue;
+                }
+                // blank lines and comments don't set off the traps
+                let trapped = !self.traps.is_empty()
+                    && parser::parse(&command).is_ok_and(|pipeline| !pipeline.commands.is_empty());
+                if trapped {
+                    if let Some(status) = self.run_trap(Trap::Debug) {
+                        return Some(status);
+                    }
+                }
+                if let Some(status) = self.run_and_report(&command) {
+                    return Some(status);
+                }
+                if trapped && self.last_status != 0 {
+                    if let Some(status) = self.run_trap(Trap::Err) {
+                        return Some(status);
+                    }
+                }
+                if self.options.errexit && self.last_status != 0 {
+                    return Some(self.last_status);
+                }
+            }
+            self.line_number = last_line;
+        }
+    }
+
+    // Ctrl-X Ctrl-E: edit `line` in $VISUAL or $EDITOR, or vi when neither is
+    // set. The editor runs like a command typed at the prompt and gets the
+    // line in a file. What's in the file when it exits is returned, None when
+    // it couldn't be run or it failed
+    fn edit_line(&mut self, line: &str) -> Option<String> {
+        let prefix = format!("ish-{}-edit-", std::process::id());
+        let (mut file, path) = match sys::temporary_file(&prefix, ".sh") {
+            Ok(created) => created,
+            Err(e) => {
+                eprintln!("ish: {}: {}", env::temp_dir().display(), error_message(&e));
+                return None;
+            }
+        };
+        let written = writeln!(file, "{}", line);
+        drop(file);
+        if let Err(e) = written {
+            eprintln!("ish: {}: {}", path.display(), error_message(&e));
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let editor = (env::var("VISUAL").ok())
+            .filter(|editor| !editor.is_empty())
+            .or_else(|| env::var("EDITOR").ok().filter(|editor| !editor.is_empty()))
+            .unwrap_or_else(|| "vi".to_string());
+        let quoted = parser::quote(&path.to_string_lossy()).into_owned();
+        self.run_and_report(&format!("{} {}", editor, quoted));
+        let edited = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+        if self.last_status != 0 {
+            return None;
+        }
+        match edited {
+            Ok(edited) => Some(edited.trim_end_matches('\n').to_string()),
+            Err(e) => {
+                eprintln!("ish: {}: {}", path.display(), error_message(&e));
+                None
+            }
+        }
+    }
+
+    // Run $PROMPT_COMMAND before the prompt. Returns the exit status if the
+    // shell should exit
+    fn run_prompt_command(&mut self) -> Option<i32> {
+        self.run_hook("PROMPT_COMMAND", self.positional.clone())
+    }
+
+    // Run $PREEXEC_COMMAND before a line typed at the prompt, with the line as
+    // $1. Returns the exit status if the shell should exit
+    fn run_preexec_command(&mut self, line: &str) -> Option<i32> {
+        let args = vec![self.positional[0].clone(), line.into()];
+        self.run_hook("PREEXEC_COMMAND", args)
+    }
+
+    // Run the commands in the variable `name` with `args` as $0, $1...; $?
+    // stays the status of the command typed last, and whatever the hook does
+    // the line runs. A hook only runs from run_lines, what it runs itself
+    // doesn't set hooks off. One that doesn't parse is only reported the
+    // first time, it's left alone until it's changed
+    fn run_hook(&mut self, name: &'static str, args: Vec<OsString>) -> Option<i32> {
+        let command = env::var(name).ok()?;
+        if self.broken_hooks.get(name) == Some(&command) {
+            return None;
+        }
+        let commands = parser::split_commands(&command);
+        if let Some(Err(message)) = commands
+            .iter()
+            .map(|line| parser::parse(line))
+            .find(Result::is_err)
+        {
+            eprintln!("ish: {}: {}", name, message);
+            self.broken_hooks.insert(name, command);
+            return None;
+        }
+        let status = self.last_status;
+        let positional = std::mem::replace(&mut self.positional, args);
+        let exit = commands.iter().find_map(|line| self.run_and_report(line));
+        self.positional = positional;
+        self.last_status = status;
+        exit
+    }
+
+    // Run the command set for `trap`, seeing the $? of the command that set
+    // it off and leaving it as it was. Returns the exit status if the shell
+    // should exit
+    fn run_trap(&mut self, trap: Trap) -> Option<i32> {
+        if self.running_trap {
+            return None;
+        }
+        let command = self.traps.get(trap)?.to_string();
+        let status = self.last_status;
+        self.running_trap = true;
+        let exit = parser::split_commands(&command)
+            .iter()
+            .find_map(|line| self.run_and_report(line));
+        self.running_trap = false;
+        self.last_status = status;
+        exit
+    }
+
+    // The status the shell exits with after the EXIT trap, which only runs
+    // the first time. An exit in it changes the status
+    pub fn run_exit_trap(&mut self, status: i32) -> i32 {
+        self.last_status = status;
+        let exit = self.run_trap(Trap::Exit);
+        self.traps.remove(Trap::Exit);
+        exit.unwrap_or(status)
+    }
+
+    fn prompt_info(&mut self) -> prompt::Info<'_> {
+        prompt::Info {
+            status: self.last_status,
+            jobs: self.jobs.lock().len(),
+            git: &mut self.git,
+            named_dirs: &self.named_dirs,
+        }
+    }
+
+    // what the line editor gets to work with for the next line
+    fn context(&mut self) -> Context<'_> {
+        let continuation_prompt = prompt::secondary(&mut self.prompt_info());
+        let right_prompt = prompt::right(&mut self.prompt_info());
+        Context {
+            history: &mut self.history,
+            share_history: self.options.sharehistory,
+            history_prefix: self.options.histprefix,
+            autosuggest: self.options.autosuggest,
+            highlight: self.options.highlight
+                && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty()),
+            keymaps: &self.keymaps,
+            terminal_modes: self.terminal_modes.filter(|_| self.job_control),
+            completions: &self.completions,
+            vi: self.options.vi,
+            builtins: &self.builtins,
+            path_cache: &mut self.path_cache,
+            continuation_prompt,
+            right_prompt,
+            jobs: (self.jobs.lock()).iter().map(|job| job.number).collect(),
+            line: std::mem::take(&mut self.next_line),
+            edit: false,
+        }
+    }
+
+    // Put the terminal back in the shell's modes if something in the background
+    // broke it, so the prompt is always usable. Foreground jobs get theirs
+    // undone as they finish, and background ones are stopped by SIGTTOU when
+    // they try, so with no jobs the modes aren't read again
+    fn recover_terminal(&self) {
+        let modes = self.terminal_modes.filter(|_| self.job_control);
+        let Some(modes) = modes.filter(Termios::usable) else {
+            return;
+        };
+        if self.jobs.lock().is_empty() {
+            return;
+        }
+        if Termios::current(self.shell_terminal).is_ok_and(|current| !current.usable()) {
+            let _ = modes.apply(self.shell_terminal);
+        }
+    }
+
+    // The terminal and the modes the shell keeps it in, for waiting on a
+    // job in the foreground. None without job control
+    pub fn foreground_terminal(&mut self) -> Option<(c_int, &mut Termios)> {
+        let fd = self.shell_terminal;
+        match &mut self.terminal_modes {
+            Some(modes) if self.job_control => Some((fd, modes)),
+            _ => None,
+        }
+    }
+
+    // Move to a new line if the cursor isn't at the start of one, so output
+    // without a newline at the end doesn't run into the prompt. Like zsh does
+    // it: a marker and then enough spaces to wrap to the next line only when
+    // something was already on this one, then back to its start. The marker
+    // stays behind to show the output had no newline
+    fn start_prompt_line(&self) {
+        if !self.job_control || !sys::is_terminal(libc::STDOUT_FILENO) {
+            return;
+        }
+        let Some(columns) = sys::terminal_columns() else {
+            return;
+        };
+        let spaces = " ".repeat(columns - 1);
+        print!("\x1b[7m%\x1b[0m{}\r", spaces);
+    }
+
+    // Run the commands in a file, like the source builtin does.
+    // Returns the exit status if the shell should exit
+    pub fn source(&mut self, path: &Path) -> io::Result<Option<i32>> {
+        let file = File::open(path)?;
+        Ok(self.run_file(path, file))
+    }
+
+    // Run a script with `args` as $1, $2..., like `ish path args...` would
+    // but in this shell. The positional parameters are put back after it
+    pub fn run_script(&mut self, path: &Path, args: &[OsString]) -> Result<ExitStatus, ShellError> {
+        let file = File::open(path).map_err(|source| ShellError::Script {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let script = [path.into()].into_iter().chain(args.iter().cloned());
+        let positional = std::mem::replace(&mut self.positional, script.collect());
+        let exit = self.run_file(path, file);
+        self.positional = positional;
+        Ok(match exit {
+            Some(status) => ExitStatus::Exit(status),
+            None => ExitStatus::Done(self.last_status),
+        })
+    }
+
+    // Run the commands in `file`, with errors given at lines of `path`
+    fn run_file(&mut self, path: &Path, file: File) -> Option<i32> {
+        let source_name = self.source_name.replace(path.display().to_string());
+        let line_number = std::mem::take(&mut self.line_number);
+        let exit = self.run_lines(&mut BufReader::new(file), false);
+        self.source_name = source_name;
+        self.line_number = line_number;
+        exit
+    }
+
+    // Source the startup file of an interactive shell: `rcfile` if given, then
+    // $ISH_RC, then ~/.ishrc. Only an explicitly chosen file has to exist
+    pub fn source_rc(&mut self, rcfile: Option<OsString>) -> Option<i32> {
+        let (path, explicit) = match rcfile.or_else(|| env::var_os("ISH_RC")) {
+            Some(path) => (PathBuf::from(path), true),
+            None => match env::var_os("HOME") {
+                Some(home) => (Path::new(&home).join(".ishrc"), false),
+                None => return None,
+            },
+        };
+        self.source_startup_file(&path, explicit)
+    }
+
+    // Read the startup files of a login shell: /etc/profile, then the first
+    // of ~/.ish_profile and ~/.profile there is. Returns the exit status if
+    // ~/.ish_profile exits
+    pub fn source_profile(&mut self) -> Option<i32> {
+        import_profile(Path::new("/etc/profile"));
+        let home = PathBuf::from(env::var_os("HOME")?);
+        let ish_profile = home.join(".ish_profile");
+        if ish_profile.exists() {
+            self.source_startup_file(&ish_profile, false)
+        } else {
+            import_profile(&home.join(".profile"));
+            None
+        }
+    }
+
+    // Source ~/.ish_logout as a login shell exits
+    pub fn source_logout(&mut self) {
+        if let Some(home) = env::var_os("HOME") {
+            self.source_startup_file(&Path::new(&home).join(".ish_logout"), false);
+        }
+    }
+
+    // a startup file that isn't there is only an error when it was asked for
+    fn source_startup_file(&mut self, path: &Path, required: bool) -> Option<i32> {
+        match self.source(path) {
+            Ok(exit) => exit,
+            Err(e) => {
+                if required || e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("ish: {}: {}", path.display(), error_message(&e));
+                }
+                None
+            }
+        }
+    }
+}
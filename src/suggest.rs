@@ -0,0 +1,143 @@
+// Suggestions for mistyped command names and directories
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+// at most this many names are suggested
+const MAX_SUGGESTIONS: usize = 3;
+
+// Edit distance between two names where swapping two adjacent characters
+// counts as a single edit, so "gti" is one edit away from "git"
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+// Names from `candidates` that are close to `name`, closest first
+pub fn similar<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let name: Vec<char> = name.chars().collect();
+    // short names allow fewer edits, otherwise everything would match
+    let max_distance = if name.len() <= 4 { 1 } else { 2 };
+
+    let mut matches: Vec<(usize, &str)> = Vec::new();
+    for candidate in candidates {
+        // cheap length check first so most names are skipped without any work
+        let len = candidate.chars().count();
+        if len.abs_diff(name.len()) > max_distance {
+            continue;
+        }
+        let candidate_chars: Vec<char> = candidate.chars().collect();
+        let distance = edit_distance(&name, &candidate_chars);
+        if distance > 0 && distance <= max_distance {
+            matches.push((distance, candidate));
+        }
+    }
+
+    matches.sort();
+    matches.dedup_by(|a, b| a.1 == b.1);
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+// `path` with each directory in it that doesn't exist replaced by the one
+// next to it that's a single typo away, like a swapped, missing or extra
+// character. None when there's nothing to correct or a name has no such
+// directory, or more than one
+pub fn correct_path(path: &Path) -> Option<PathBuf> {
+    let mut corrected = PathBuf::new();
+    let mut changed = false;
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+        if corrected.join(name).is_dir() {
+            corrected.push(name);
+            continue;
+        }
+        let name: Vec<char> = name.to_str()?.chars().collect();
+        let dir = if corrected.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &corrected
+        };
+        let mut matches = fs::read_dir(dir).ok()?.flatten().filter_map(|entry| {
+            let candidate = entry.file_name().into_string().ok()?;
+            let chars: Vec<char> = candidate.chars().collect();
+            let close = chars.len().abs_diff(name.len()) <= 1 && edit_distance(&name, &chars) == 1;
+            (close && entry.path().is_dir()).then_some(candidate)
+        });
+        let candidate = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        corrected.push(candidate);
+        changed = true;
+    }
+    changed.then_some(corrected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b)
+    }
+
+    #[test]
+    fn a_swap_is_one_edit() {
+        assert_eq!(distance("gti", "git"), 1);
+        assert_eq!(distance("git", "git"), 0);
+        assert_eq!(distance("gt", "git"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn the_closest_names_come_first() {
+        let names = ["mkdirs", "mkdir", "make", "rmdir"];
+        assert_eq!(similar("mkdri", names.into_iter()), ["mkdir", "mkdirs"]);
+    }
+
+    #[test]
+    fn short_names_allow_a_single_edit() {
+        let names = ["git", "gitk", "grep"];
+        assert_eq!(similar("gti", names.into_iter()), ["git"]);
+        assert!(similar("gr", names.into_iter()).is_empty());
+    }
+
+    #[test]
+    fn the_name_itself_and_repeats_arent_suggested() {
+        let names = ["sort", "port", "port"];
+        assert_eq!(similar("sort", names.into_iter()), ["port"]);
+        // the ones as close are in the order of their names, three at most
+        let names = ["cut", "car", "cat", "can", "cab"];
+        assert_eq!(similar("cax", names.into_iter()), ["cab", "can", "car"]);
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    env,
+    ffi::{CStr, CString, OsStr, OsString},
+    fs::File,
+    io,
+    mem::{self, MaybeUninit},
+    os::{
+        fd::{FromRawFd, OwnedFd},
+        unix::ffi::{OsStrExt, OsStringExt},
+    },
+    path::PathBuf,
+    ptr, thread,
+};
+
+use libc::{c_int, pid_t, TCSADRAIN};
+
+// Safe wrappers for the system calls the shell makes, each checks for errors
+// and retries when a signal interrupts it. The ones for signals, process
+// groups and waiting don't allocate, so they are fine between fork and exec
+
+// Run a syscall again for as long as a signal interrupts it. `call` returns -1
+// with errno set when it fails, like the libc functions. Doesn't allocate, so
+// it's also fine between fork and exec
+pub fn retry<T>(mut call: impl FnMut() -> T) -> T
+where
+    T: Copy + PartialEq + From<i8>,
+{
+    loop {
+        let result = call();
+        if result != T::from(-1) || io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            return result;
+        }
+    }
+}
+
+// Spawn a thread that never handles any of the shell's signals, so they always
+// interrupt what the main thread is doing
+pub fn spawn_thread<F>(f: F) -> thread::JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    unsafe {
+        // the thread starts with the mask of the thread spawning it
+        let mut all = mem::zeroed();
+        let mut old = mem::zeroed();
+        libc::sigfillset(&mut all);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &all, &mut old);
+        let handle = thread::spawn(f);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, ptr::null_mut());
+        handle
+    }
+}
+
+// width of the terminal on stdout, None when it isn't one or doesn't say
+pub fn terminal_columns() -> Option<usize> {
+    terminal_size().map(|(columns, _)| columns)
+}
+
+// columns and rows of the terminal on stdout
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = unsafe { mem::zeroed::<libc::winsize>() };
+    let read = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (read == 0 && size.ws_col > 0).then_some((size.ws_col as usize, size.ws_row as usize))
+}
+
+// name of the user the shell runs as, from the password database or $USER
+pub fn user_name() -> String {
+    let entry = unsafe { libc::getpwuid(libc::geteuid()) };
+    if entry.is_null() {
+        return std::env::var("USER").unwrap_or_default();
+    }
+    unsafe { CStr::from_ptr((*entry).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// home directory of a user in the password database, for ~user
+pub fn user_home(name: &str) -> Option<OsString> {
+    let name = CString::new(name).ok()?;
+    let entry = unsafe { libc::getpwnam(name.as_ptr()) };
+    if entry.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr((*entry).pw_dir) };
+    Some(OsStr::from_bytes(dir.to_bytes()).to_os_string())
+}
+
+pub fn host_name() -> String {
+    let mut name = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr(), name.len() - 1) } < 0 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// the current local time formatted by strftime(3)
+pub fn local_time(format: &str) -> String {
+    format_time(unsafe { libc::time(ptr::null_mut()) }, format)
+}
+
+// seconds since the epoch as local time, formatted by strftime(3)
+pub fn format_time(seconds: libc::time_t, format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut time = unsafe { mem::zeroed::<libc::tm>() };
+    let mut out = [0 as libc::c_char; 256];
+    let written = unsafe {
+        libc::localtime_r(&seconds, &mut time);
+        libc::strftime(out.as_mut_ptr(), out.len(), format.as_ptr(), &time)
+    };
+    if written == 0 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(out.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// a number for format_number, in the type its conversion takes
+#[derive(Clone, Copy)]
+pub enum Number {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+// a number formatted by snprintf(3) with a conversion like %-8lld or %.3f
+pub fn format_number(conversion: &str, number: Number) -> Vec<u8> {
+    let Ok(conversion) = CString::new(conversion) else {
+        return Vec::new();
+    };
+    let format = |out: *mut libc::c_char, len: usize| unsafe {
+        match number {
+            Number::Signed(n) => libc::snprintf(out, len, conversion.as_ptr(), n),
+            Number::Unsigned(n) => libc::snprintf(out, len, conversion.as_ptr(), n),
+            Number::Float(n) => libc::snprintf(out, len, conversion.as_ptr(), n),
+        }
+    };
+    // the first call only says how long it is
+    let Ok(len) = usize::try_from(format(ptr::null_mut(), 0)) else {
+        return Vec::new();
+    };
+    let mut out = vec![0u8; len + 1];
+    format(out.as_mut_ptr().cast(), out.len());
+    out.truncate(len);
+    out
+}
+
+// the signals the shell sends, catches or ignores
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Signal {
+    Int,
+    Term,
+    Kill,
+    Cont,
+    Tstp,
+    Ttin,
+    Ttou,
+    Pipe,
+    Winch,
+    Hup,
+}
+
+impl Signal {
+    pub fn number(self) -> c_int {
+        match self {
+            Signal::Int => libc::SIGINT,
+            Signal::Term => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Tstp => libc::SIGTSTP,
+            Signal::Ttin => libc::SIGTTIN,
+            Signal::Ttou => libc::SIGTTOU,
+            Signal::Pipe => libc::SIGPIPE,
+            Signal::Winch => libc::SIGWINCH,
+            Signal::Hup => libc::SIGHUP,
+        }
+    }
+}
+
+// what happens when a signal arrives
+#[derive(Clone, Copy)]
+pub enum Disposition {
+    Default,
+    Ignore,
+    // the handler runs and the system call it interrupted is restarted
+    Catch(extern "C" fn(c_int)),
+    // the handler runs and the system call it interrupted fails with EINTR
+    Interrupt(extern "C" fn(c_int)),
+}
+
+pub fn set_signal(signal: Signal, disposition: Disposition) -> io::Result<()> {
+    let mut action = unsafe { mem::zeroed::<libc::sigaction>() };
+    action.sa_sigaction = match disposition {
+        Disposition::Default => libc::SIG_DFL,
+        Disposition::Ignore => libc::SIG_IGN,
+        Disposition::Catch(handler) | Disposition::Interrupt(handler) => {
+            handler as *const () as usize
+        }
+    };
+    if let Disposition::Catch(_) = disposition {
+        action.sa_flags = libc::SA_RESTART;
+    }
+    let set = unsafe {
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal.number(), &action, ptr::null_mut())
+    };
+    check(set)
+}
+
+// Send a signal to every process in the process group `pgid`
+pub fn kill_group(pgid: pid_t, signal: Signal) -> io::Result<()> {
+    check(unsafe { libc::kill(-pgid, signal.number()) })
+}
+
+// Send a signal to the process `pid`
+pub fn kill(pid: pid_t, signal: Signal) -> io::Result<()> {
+    check(unsafe { libc::kill(pid, signal.number()) })
+}
+
+// Wait for a child, or any process in the group -pid, to change state.
+// Returns its pid and wait status, None with WNOHANG when none has
+pub fn wait_pid(pid: pid_t, flags: c_int) -> io::Result<Option<(pid_t, c_int)>> {
+    let mut status = 0;
+    let waited = retry(|| unsafe { libc::waitpid(pid, &mut status, flags) });
+    check(waited)?;
+    Ok((waited > 0).then_some((waited, status)))
+}
+
+// Wait for any child to change state, like wait_pid(-1, flags) except that a
+// signal interrupts it with an Interrupted error instead of it going on
+pub fn wait_child(flags: c_int) -> io::Result<Option<(pid_t, c_int)>> {
+    let mut status = 0;
+    let waited = unsafe { libc::waitpid(-1, &mut status, flags) };
+    check(waited)?;
+    Ok((waited > 0).then_some((waited, status)))
+}
+
+// Put `pid` in the process group `pgid`, a new one of its own when it's 0
+pub fn set_process_group(pid: pid_t, pgid: pid_t) -> io::Result<()> {
+    check(unsafe { libc::setpgid(pid, pgid) })
+}
+
+// the process group of the shell
+pub fn process_group() -> pid_t {
+    unsafe { libc::getpgrp() }
+}
+
+// Make `pgid` the foreground process group of the terminal `fd`
+pub fn set_foreground_pgrp(fd: c_int, pgid: pid_t) -> io::Result<()> {
+    check(retry(|| unsafe { libc::tcsetpgrp(fd, pgid) }))
+}
+
+pub fn is_terminal(fd: c_int) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+// The modes of a terminal
+#[derive(Clone, Copy)]
+pub struct Termios(libc::termios);
+
+impl Termios {
+    // filled in by tcgetattr, the fields differ between systems
+    pub fn current(fd: c_int) -> io::Result<Termios> {
+        let mut modes = MaybeUninit::<libc::termios>::uninit();
+        check(unsafe { libc::tcgetattr(fd, modes.as_mut_ptr()) })?;
+        Ok(Termios(unsafe { modes.assume_init() }))
+    }
+
+    // Put the terminal `fd` in these modes once what was written to it is out
+    pub fn apply(&self, fd: c_int) -> io::Result<()> {
+        check(retry(|| unsafe { libc::tcsetattr(fd, TCSADRAIN, &self.0) }))
+    }
+
+    // whether commands can be typed with the terminal in these modes: what's
+    // typed is echoed, lines can be edited and output newlines start a new line
+    pub fn usable(&self) -> bool {
+        let local = libc::ECHO | libc::ICANON;
+        let output = libc::OPOST | libc::ONLCR;
+        self.0.c_lflag & local == local && self.0.c_oflag & output == output
+    }
+
+    // these modes with echo, line editing, signal keys and newline
+    // translation on, whatever they were
+    pub fn sane(mut self) -> Termios {
+        self.0.c_lflag |= libc::ECHO | libc::ECHOE | libc::ICANON | libc::ISIG;
+        self.0.c_oflag |= libc::OPOST | libc::ONLCR;
+        self.0.c_iflag |= libc::ICRNL;
+        self
+    }
+
+    // these modes for a line editor: keys come one at a time and aren't
+    // echoed, Ctrl-C still sends SIGINT
+    pub fn raw(mut self) -> Termios {
+        self.0.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN);
+        self.0.c_iflag &= !(libc::ICRNL | libc::IXON);
+        self.0.c_cc[libc::VMIN] = 1;
+        self.0.c_cc[libc::VTIME] = 0;
+        self
+    }
+
+    // whether the terminal echoes control characters like ^C itself
+    pub fn echoes_control(&self) -> bool {
+        self.0.c_lflag & libc::ECHOCTL != 0
+    }
+}
+
+// Create a file in the temporary directory named `prefix`, random
+// characters and `suffix`, that only the user can read. It's always a new
+// file, never one that was there already or a link left in its place, and
+// it's closed on exec
+pub fn temporary_file(prefix: &str, suffix: &str) -> io::Result<(File, PathBuf)> {
+    let template = env::temp_dir().join(format!("{}XXXXXX{}", prefix, suffix));
+    let mut template = CString::new(template.into_os_string().into_vec())?.into_bytes_with_nul();
+    let fd = unsafe { libc::mkstemps(template.as_mut_ptr().cast(), suffix.len() as c_int) };
+    check(fd)?;
+    let file = unsafe { File::from_raw_fd(fd) };
+    check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    template.pop();
+    Ok((file, PathBuf::from(OsString::from_vec(template))))
+}
+
+// A pipe whose ends are closed on exec, returns (read end, write end)
+#[cfg(not(target_vendor = "apple"))]
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    check(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) })?;
+    unsafe { Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))) }
+}
+
+// macOS has no pipe2, the ends are marked right after. Only the main thread
+// starts processes, none can be started in between
+#[cfg(target_vendor = "apple")]
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    check(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    for fd in fds {
+        check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    }
+    Ok((read, write))
+}
+
+// the error of a libc call that returned -1
+fn check(result: c_int) -> io::Result<()> {
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::{
+    env,
+    io::{stdout, Write},
+    sync::atomic::Ordering,
+};
+
+use libc::c_int;
+
+use crate::{
+    input::{INTERRUPTED, RESIZED},
+    sys::{self, Disposition, Signal},
+};
+
+// Empty signal handler so we don't exit on signals
+extern "C" fn handle_signal(_: c_int) {}
+
+extern "C" fn handle_sigint(_: c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_sigwinch(_: c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+// $COLUMNS and $LINES for the commands run from a terminal, kept up to date
+// with its size
+pub fn export_terminal_size() {
+    if let Some((columns, lines)) = sys::terminal_size() {
+        env::set_var("COLUMNS", columns.to_string());
+        env::set_var("LINES", lines.to_string());
+    }
+}
+
+// Leave the shell, every way of terminating goes through here
+pub fn exit_shell(status: i32) -> ! {
+    let _ = stdout().flush();
+    std::process::exit(status)
+}
+
+// Ignore signals so they don't kill the shell
+pub fn install_signal_handlers() {
+    let dispositions = [
+        // without SA_RESTART, so Ctrl-C interrupts reading a line at the prompt
+        (Signal::Int, Disposition::Interrupt(handle_sigint)),
+        // the line editor redraws the line when the terminal is resized
+        (Signal::Winch, Disposition::Interrupt(handle_sigwinch)),
+        (Signal::Tstp, Disposition::Catch(handle_signal)),
+        // needed to hand the terminal back and forth between jobs
+        (Signal::Ttou, Disposition::Ignore),
+        (Signal::Ttin, Disposition::Ignore),
+    ];
+    for (signal, disposition) in dispositions {
+        // this only fails for signals that can't be caught
+        let _ = sys::set_signal(signal, disposition);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use libc::pid_t;
+
+use crate::sys::{self, Signal};
+
+// grace period between SIGTERM and SIGKILL when none is given
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    fired: bool,
+    // the job is stopped, its time doesn't run meanwhile
+    paused: bool,
+}
+
+// Time limit on a job. A thread sleeps until the limit and then kills the
+// job's process group, so waiting for the job doesn't change at all. Only the
+// time the job spends running counts
+pub struct Timeout {
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+// parse a duration like 10, 1.5s, 5m, 2h or 1d
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    Duration::try_from_secs_f64(number * seconds).ok()
+}
+
+impl Timeout {
+    // Send SIGTERM to process group `pgid` once `limit` has passed, and SIGKILL
+    // if it's still there `grace` later
+    pub fn start(pgid: pid_t, limit: Duration, grace: Duration) -> Timeout {
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let timer = Arc::clone(&state);
+        sys::spawn_thread(move || {
+            let (lock, condvar) = &*timer;
+            let mut left = limit;
+            for signal in [Signal::Term, Signal::Kill] {
+                let mut state = lock.lock().unwrap_or_else(PoisonError::into_inner);
+                let mut since = Instant::now();
+                let mut running = false;
+                while !state.cancelled {
+                    // the time since the last look only counts if the job
+                    // was running all along
+                    if running {
+                        left = left.saturating_sub(since.elapsed());
+                    }
+                    since = Instant::now();
+                    running = !state.paused;
+                    if running && left.is_zero() {
+                        break;
+                    }
+                    state = if running {
+                        condvar
+                            .wait_timeout(state, left)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0
+                    } else {
+                        condvar.wait(state).unwrap_or_else(PoisonError::into_inner)
+                    };
+                }
+                if state.cancelled {
+                    return;
+                }
+                state.fired = true;
+                let _ = sys::kill_group(pgid, signal);
+                left = grace;
+            }
+        });
+        Timeout { state }
+    }
+
+    // stop the clock while the job is stopped, and start it again once it's
+    // continued
+    pub fn pause(&self, paused: bool) {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        state.paused = paused;
+        condvar.notify_one();
+    }
+
+    // Stop the timer, returns whether the job was already killed because of it
+    pub fn cancel(self) -> bool {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        state.cancelled = true;
+        condvar.notify_one();
+        state.fired
+    }
+}
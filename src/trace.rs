@@ -0,0 +1,36 @@
+use std::{
+    fmt,
+    io::{stderr, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Traces of what the shell does inside, for debugging it: the tokens and the
+// pipeline a line parses to, what words expand to, the commands it runs and
+// how its jobs change state. Turned on with ISH_DEBUG or --debug, off it costs
+// a load and a branch at each trace point
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Write a trace line to stderr when tracing is on. The arguments aren't even
+// formatted otherwise
+macro_rules! trace {
+    ($kind:expr, $($arg:tt)*) => {
+        if $crate::trace::enabled() {
+            $crate::trace::write($kind, format_args!($($arg)*));
+        }
+    };
+}
+
+pub fn set_enabled(on: bool) {
+    ENABLED.store(on, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// one line per trace, with the pid since subshells trace too
+pub fn write(kind: &str, message: fmt::Arguments) {
+    let line = format!("ish-debug[{}] {}: {}\n", std::process::id(), kind, message);
+    let _ = stderr().lock().write_all(line.as_bytes());
+}
@@ -0,0 +1,71 @@
+// The commands the trap builtin sets up to run when something happens in the
+// shell itself, rather than when a signal comes
+
+use std::collections::BTreeMap;
+
+use crate::parser;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trap {
+    // the shell exiting, however it comes to that
+    Exit,
+    // a command failing
+    Err,
+    // a command about to run
+    Debug,
+}
+
+impl Trap {
+    // EXIT can be given as 0 too, and all of them with SIG in front
+    pub fn from_name(name: &str) -> Option<Trap> {
+        match name.strip_prefix("SIG").unwrap_or(name) {
+            "EXIT" | "0" => Some(Trap::Exit),
+            "ERR" => Some(Trap::Err),
+            "DEBUG" => Some(Trap::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Trap::Exit => "EXIT",
+            Trap::Err => "ERR",
+            Trap::Debug => "DEBUG",
+        }
+    }
+}
+
+// The command set for each trap
+#[derive(Default)]
+pub struct Traps {
+    table: BTreeMap<Trap, String>,
+}
+
+impl Traps {
+    pub fn set(&mut self, trap: Trap, command: &str) {
+        self.table.insert(trap, command.to_string());
+    }
+
+    pub fn remove(&mut self, trap: Trap) -> Option<String> {
+        self.table.remove(&trap)
+    }
+
+    pub fn get(&self, trap: Trap) -> Option<&str> {
+        self.table.get(&trap).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Trap, &str)> {
+        self.table
+            .iter()
+            .map(|(&trap, command)| (trap, command.as_str()))
+    }
+}
+
+// The trap command that sets `command` for `trap`, quoted to be read back in
+pub fn show(trap: Trap, command: &str) -> String {
+    format!("trap -- {} {}", parser::quote(command), trap.name())
+}
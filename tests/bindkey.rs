@@ -0,0 +1,77 @@
+mod common;
+
+use common::{errors, run};
+
+#[test]
+fn bindings_are_listed_as_the_commands_that_make_them() {
+    let listed = run("bindkey\n");
+    assert!(
+        listed.starts_with("bindkey '\\C-m' accept-line\n"),
+        "{}",
+        listed
+    );
+    assert!(listed.contains("bindkey '\\e[H' beginning-of-line\n"));
+    assert!(listed.contains("bindkey '\\C-?' backward-delete-char\n"));
+    // rebinding replaces the binding, new ones come last
+    let listed = run(concat!(
+        "bindkey '\\C-a' end-of-line\n",
+        "bindkey jj backward-kill-word\n",
+        "bindkey -s '^Xl' 'ls -l'\n",
+        "bindkey\n"
+    ));
+    assert!(listed.contains("bindkey '\\C-a' end-of-line\n"));
+    assert!(listed.ends_with("bindkey 'jj' backward-kill-word\nbindkey -s '\\C-xl' 'ls -l'\n"));
+    // the listing binds the same keys again
+    assert_eq!(run(&format!("{}bindkey\n", listed)), listed);
+    let listed = run("bindkey -r '\\e[H'\nbindkey\n");
+    assert!(!listed.contains("\\e[H"));
+}
+
+#[test]
+fn bad_bindings_are_refused() {
+    assert_eq!(
+        errors("bindkey '\\C-g' no-such-action\n"),
+        "ish: bindkey: no-such-action: unknown action\n"
+    );
+    assert_eq!(
+        errors("bindkey '\\C-' complete\nbindkey '' complete\n"),
+        "ish: bindkey: \\C-: invalid key sequence\nish: bindkey: : invalid key sequence\n"
+    );
+    assert_eq!(
+        errors("bindkey -r '\\C-g'\n"),
+        "ish: bindkey: \\C-g: not bound\n"
+    );
+    assert_eq!(run("bindkey x\necho $?\n"), "2\n");
+}
+
+#[test]
+fn vi_mode_has_keymaps_of_its_own() {
+    let listed = run("bindkey -M vicmd\n");
+    assert!(listed.contains("bindkey 'x' delete-char\n"), "{}", listed);
+    assert!(listed.contains("bindkey 'd' vi-delete-to\n"));
+    // in vi mode bindkey changes the keymap for inserting text
+    let listed = run("set -o vi\nbindkey\n");
+    assert!(
+        listed.contains("bindkey '\\e' vi-movement-mode\n"),
+        "{}",
+        listed
+    );
+    let listed = run("set -o vi\nbindkey -s '\\C-g' hi\nbindkey -M viins\nbindkey -M emacs\n");
+    assert_eq!(listed.matches("bindkey -s '\\C-g' 'hi'\n").count(), 1);
+    assert_eq!(
+        errors("bindkey -M vim x complete\n"),
+        "ish: bindkey: vim: no such keymap\n"
+    );
+}
+
+#[test]
+fn one_editing_mode_is_on() {
+    assert_eq!(
+        run("set -o vi\nset -o | grep -E '^(emacs|vi) '\nset -o emacs\nset -o | grep -E '^(emacs|vi) '\n"),
+        "emacs           off\nvi              on\nemacs           on\nvi              off\n"
+    );
+    assert_eq!(
+        run("set +o emacs\nset -o | grep '^vi '\n"),
+        "vi              on\n"
+    );
+}
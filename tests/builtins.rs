@@ -0,0 +1,237 @@
+mod common;
+
+use std::{cell::RefCell, ffi::OsString, fs, rc::Rc};
+
+use common::{errors, run, run_shell, test_dir};
+use ish::{
+    builtins::{Builtin, Io},
+    exec::ExitStatus,
+    options::Options,
+    shell::Shell,
+};
+
+// a builtin that keeps the arguments it was given
+struct Record {
+    name: &'static str,
+    calls: Rc<RefCell<Vec<Vec<OsString>>>>,
+}
+
+impl Builtin for Record {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn help(&self) -> &str {
+        "record ARGS: remember the arguments"
+    }
+
+    fn execute(&self, _: &mut Shell, args: &[OsString], _: &mut Io) -> ExitStatus {
+        self.calls.borrow_mut().push(args.to_vec());
+        ExitStatus::Done(3)
+    }
+}
+
+// a builtin that writes what it reads in capitals, and exits the shell when
+// it's told to
+struct Upper;
+
+impl Builtin for Upper {
+    fn name(&self) -> &str {
+        "upper"
+    }
+
+    fn help(&self) -> &str {
+        "upper [exit]: copy the input in capitals"
+    }
+
+    fn execute(&self, _: &mut Shell, args: &[OsString], io: &mut Io) -> ExitStatus {
+        let mut input = String::new();
+        if io.stdin.read_to_string(&mut input).is_err() {
+            let _ = writeln!(io.stderr, "upper: can't read the input");
+            return ExitStatus::Done(1);
+        }
+        let _ = write!(io.stdout, "{}", input.to_uppercase());
+        match args.first() {
+            Some(arg) if arg == "exit" => ExitStatus::Exit(4),
+            _ => ExitStatus::Done(0),
+        }
+    }
+}
+
+#[test]
+fn registered_builtins_are_run() {
+    let calls = Rc::default();
+    let mut shell = Shell::new(Options::default());
+    shell.register_builtin(Record {
+        name: "record",
+        calls: Rc::clone(&calls),
+    });
+    assert_eq!(
+        shell.run_line("record a 'b c'").unwrap(),
+        ExitStatus::Done(3)
+    );
+    assert_eq!(*calls.borrow(), [vec![OsString::from("a"), "b c".into()]]);
+}
+
+#[test]
+fn registered_builtins_replace_commands() {
+    let calls = Rc::default();
+    let mut shell = Shell::new(Options::default());
+    shell.register_builtin(Record {
+        name: "cd",
+        calls: Rc::clone(&calls),
+    });
+    shell.register_builtin(Record {
+        name: "true",
+        calls: Rc::clone(&calls),
+    });
+    assert_eq!(shell.run_line("cd /").unwrap(), ExitStatus::Done(3));
+    assert_eq!(shell.run_line("true").unwrap(), ExitStatus::Done(3));
+    assert_eq!(calls.borrow().len(), 2);
+}
+
+#[test]
+fn registered_builtins_read_and_write_their_redirections() {
+    let input = test_dir().join("upper_in");
+    let output = test_dir().join("upper_out");
+    fs::write(&input, "some text\n").unwrap();
+    let mut shell = Shell::new(Options::default());
+    shell.register_builtin(Upper);
+
+    let line = format!("upper < {} > {}", input.display(), output.display());
+    assert_eq!(shell.run_line(&line).unwrap(), ExitStatus::Done(0));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "SOME TEXT\n");
+    let line = format!(
+        "cat {} | upper | tr T t > {}",
+        input.display(),
+        output.display()
+    );
+    assert_eq!(shell.run_line(&line).unwrap(), ExitStatus::Done(0));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "SOME tEXt\n");
+    let line = format!("upper exit < {} > {}", input.display(), output.display());
+    assert_eq!(shell.run_line(&line).unwrap(), ExitStatus::Exit(4));
+}
+
+#[test]
+fn help_describes_the_builtins() {
+    assert_eq!(
+        run("help cd exit\n"),
+        "cd DIR | cd -N: change the current directory, or go to the Nth on the directory stack\n\
+         exit [N]: leave the shell with status N, or the status of the last command\n"
+    );
+    assert!(run("help\n").lines().any(|line| line.starts_with("jobs ")));
+    assert_eq!(errors("help nope\n"), "ish: help: nope: no such builtin\n");
+    assert_eq!(run("help nope\necho $?\n"), "1\n");
+}
+
+#[test]
+fn complete_registers_completions() {
+    assert_eq!(
+        run("complete -W 'start stop  status' svc other\ncomplete -C 'ls -d' ls\ncomplete -r other\ncomplete\n"),
+        "complete -C 'ls -d' ls\ncomplete -W 'start stop status' svc\n"
+    );
+    assert_eq!(
+        run("complete -C \"echo 'it'\" x\ncomplete -p x\n"),
+        "complete -C 'echo '\\''it'\\''' x\n"
+    );
+    assert_eq!(
+        errors("complete -p nope\ncomplete -r nope\ncomplete -W words\n"),
+        "ish: complete: nope: no completion specification\n\
+         ish: complete: nope: no completion specification\n\
+         ish: complete: usage: complete -W WORDS|-C COMMAND NAME... | -r NAME... | -p [NAME...]\n"
+    );
+}
+
+#[test]
+fn trap_runs_commands_on_exit_and_errors() {
+    assert_eq!(
+        run("trap 'echo failed $?' ERR\ntrap 'echo exiting $?' EXIT\nfalse\n\ntrue\nexit 3\n"),
+        "failed 1\nexiting 3\n"
+    );
+    assert_eq!(
+        run("trap 'echo before' DEBUG\necho one\n# comment\ntrap - DEBUG\necho two\n"),
+        "before\none\nbefore\ntwo\n"
+    );
+    assert_eq!(
+        run("trap 'echo it'\\''s' ERR\ntrap 0\ntrap 'exit 5' EXIT\ntrap -p\n"),
+        "trap -- 'exit 5' EXIT\ntrap -- 'echo it'\\''s' ERR\n"
+    );
+    let output = run_shell("trap 'echo bye\nexit 5' EXIT\ntrap 'false' ERR\nfalse\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "bye\n");
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        errors("trap 'echo' INT\ntrap -\n"),
+        "ish: trap: INT: only EXIT, ERR and DEBUG can be trapped\n\
+         ish: trap: -: only EXIT, ERR and DEBUG can be trapped\n"
+    );
+}
+
+#[test]
+fn shopt_sets_and_queries_options() {
+    assert_eq!(
+        run("shopt | grep autocd\nshopt -s autocd\nshopt -s | grep autocd\nset -o | grep autocd\nshopt -q autocd\necho $?\nshopt cdspell\necho $?\n"),
+        "autocd          off\nautocd          on\nautocd          on\n0\ncdspell         off\n1\n"
+    );
+    assert_eq!(
+        run("shopt -q nullglob\necho $?\nshopt -s errexit\necho $?\n"),
+        "1\n1\n"
+    );
+    assert_eq!(
+        errors("shopt -q nullglob\nshopt -su autocd\n"),
+        "ish: shopt: nullglob: invalid shell option name\n\
+         ish: shopt: cannot set and unset options at once\n"
+    );
+}
+
+#[test]
+fn printf_formats_its_arguments() {
+    assert_eq!(
+        run("printf '%s|%5s|%-4s|%.2s|\\n' a b c def\n"),
+        "a|    b|c   |de|\n"
+    );
+    assert_eq!(
+        run("printf '%d %05d %x %o %.2f %c\\n' 42 -3 255 8 3.14159 word\n"),
+        "42 -0003 ff 10 3.14 w\n"
+    );
+    assert_eq!(
+        run("printf '<%s>' a b c\nprintf '%b\\n' 'a\\tb'\n"),
+        "<a><b><c>a\tb\n"
+    );
+    let output = run_shell("printf '%d\\n' 12x\necho $?\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "12\n1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "ish: printf: 12x: invalid number\n"
+    );
+}
+
+#[test]
+fn printf_q_quotes_words_to_be_read_back() {
+    assert_eq!(
+        run("printf '%q ' plain 'a b' \"it's\" '' '$1|x'\n"),
+        "plain 'a b' 'it'\\''s' '' '$1|x' "
+    );
+    for word in ["a b", "it's", "$1 \"x\" \\", "*?[a]", "x|y&z>w"] {
+        let quoted = run(&format!("printf %q '{}'\n", word.replace('\'', "'\\''")));
+        assert_eq!(run(&format!("printf '%s' {}\n", quoted)), word);
+    }
+}
+
+#[test]
+fn namedir_names_directories_for_tilde() {
+    assert_eq!(
+        run(
+            "namedir proj=/work/big 'docs=/a b'\nprintf '%s\\n' ~proj/src ~proj '~proj'\nnamedir\n"
+        ),
+        "/work/big/src\n/work/big\n~proj\nnamedir docs='/a b'\nnamedir proj=/work/big\n"
+    );
+    assert_eq!(
+        run("namedir proj=/work\nnamedir -r proj\nprintf '%s\\n' ~proj\n"),
+        "~proj\n"
+    );
+    assert_eq!(
+        errors("namedir -r proj\nnamedir /tmp\n"),
+        "ish: namedir: proj: no such named directory\n\
+         ish: namedir: usage: namedir [NAME=DIR...] | namedir -r NAME...\n"
+    );
+}
@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+
+// macOS doesn't show what a process waits for, the pty tests need that
+#[cfg(not(target_vendor = "apple"))]
+pub mod pty;
+
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+// Feed `input` to the shell on stdin and return what it printed
+pub fn run(input: &str) -> String {
+    String::from_utf8(run_shell(input).stdout).unwrap()
+}
+
+// Feed `input` to the shell on stdin and return the status it exited with
+pub fn shell_status(input: &str) -> i32 {
+    run_shell(input).status.code().unwrap()
+}
+
+// Feed `input` to the shell on stdin and return what it printed to stderr
+pub fn errors(input: &str) -> String {
+    String::from_utf8(run_shell(input).stderr).unwrap()
+}
+
+pub fn run_shell(input: &str) -> Output {
+    run_shell_with_args(&[], input)
+}
+
+// Run the shell with `args` on its command line and `input` on stdin
+pub fn run_shell_with_args(args: &[&str], input: &str) -> Output {
+    run_shell_with_env(args, &[], input)
+}
+
+// Run the shell with `args` on its command line, `env` added to its
+// environment and `input` on stdin
+pub fn run_shell_with_env(args: &[&str], env: &[(&str, &str)], input: &str) -> Output {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .args(args)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    shell.wait_with_output().unwrap()
+}
+
+// Run the shell with `args` on its command line and nothing on stdin
+pub fn run_args(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ish"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+// Directory for the files a test needs, unique to the test binary
+pub fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ish-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Write a helper script for commands that can't be expressed without quoting
+pub fn script(name: &str, contents: &str) -> PathBuf {
+    let path = test_dir().join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+// Write an executable file for the shell to run
+pub fn executable(name: &str, contents: &[u8]) -> PathBuf {
+    let path = script(name, "");
+    fs::write(&path, contents).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
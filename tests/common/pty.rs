@@ -0,0 +1,344 @@
+use std::{
+    ffi::CStr,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::{fs::OpenOptionsExt, process::CommandExt},
+    },
+    process::{Child, Command, ExitStatus},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::test_dir;
+
+// how long to wait for the shell to print something before failing the test
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// tests run side by side, each shell keeps its history in a file of its own
+static SHELLS: AtomicUsize = AtomicUsize::new(0);
+
+// A shell running on a pseudo-terminal, as if someone were typing at it
+pub struct Pty {
+    master: File,
+    child: Child,
+    // everything read that an expect hasn't consumed yet
+    output: Vec<u8>,
+}
+
+impl Pty {
+    // Start an interactive shell without a startup file, showing
+    // `$ ` as its prompt. TERM is dumb so lines are read without the editor
+    pub fn spawn() -> Pty {
+        Pty::spawn_with_env(&[("TERM", "dumb")])
+    }
+
+    // Start the shell with `env` added to its environment
+    pub fn spawn_with_env(env: &[(&str, &str)]) -> Pty {
+        let (master, terminal) = open_pty().unwrap();
+        let shell = SHELLS.fetch_add(1, Ordering::Relaxed);
+        let histfile = test_dir().join(format!("pty_history_{}", shell));
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ish"));
+        command
+            .arg("--norc")
+            .env("PS1", "$ ")
+            .env("HISTFILE", histfile)
+            .envs(env.iter().copied())
+            .current_dir(test_dir())
+            .stdin(terminal.try_clone().unwrap())
+            .stdout(terminal.try_clone().unwrap())
+            .stderr(terminal);
+        // the shell leads a session of its own with the pty as its terminal,
+        // so job control and Ctrl-C work as they do for a login
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+        let mut pty = Pty {
+            master,
+            child,
+            output: Vec::new(),
+        };
+        pty.prompt();
+        pty
+    }
+
+    // Wait for the prompt and for the shell to be reading what's typed at
+    // it. A Ctrl-C sent before that comes too early to throw a line away
+    pub fn prompt(&mut self) -> String {
+        let before = self.expect("$ ");
+        let pid = self.child.id();
+        wait_until(&format!("shell {} to read the terminal", pid), || {
+            process::reads_terminal(pid)
+        });
+        before
+    }
+
+    // Wait for the shell to hand the terminal to a job, and for the job to
+    // be running its program. Returns the job's process group
+    pub fn foreground_job(&mut self) -> u32 {
+        let mut pgid = 0;
+        wait_until("a job in the foreground", || {
+            pgid = unsafe { libc::tcgetpgrp(self.master.as_raw_fd()) } as u32;
+            pgid != self.child.id() && process::name(pgid).is_some_and(|name| name != "ish")
+        });
+        pgid
+    }
+
+    // Wait for the shell to be asleep waiting for its children, like the
+    // wait builtin does. A Ctrl-C sent before that doesn't interrupt the wait
+    pub fn waiting(&mut self) {
+        let pid = self.child.id();
+        wait_until(&format!("shell {} to wait for its children", pid), || {
+            process::waits_for_children(pid)
+        });
+    }
+
+    // Type `input`, control characters like "\x03" for Ctrl-C included
+    pub fn send(&mut self, input: &str) {
+        self.master.write_all(input.as_bytes()).unwrap();
+    }
+
+    // Make the terminal `columns` wide and `rows` high, the shell gets a
+    // SIGWINCH like it does when a terminal window is resized
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+            panic!("can't resize the terminal: {}", io::Error::last_os_error());
+        }
+    }
+
+    // Type `line` and Enter, then wait for the next prompt. Returns what was
+    // printed in between, the echoed line left out
+    pub fn run(&mut self, line: &str) -> String {
+        self.send(line);
+        self.send("\n");
+        self.expect(&format!("{}\n", line));
+        self.prompt()
+    }
+
+    // Wait until the shell prints `text`. Returns what it printed before it.
+    // The terminal ends lines with "\r\n", both are written as "\n"
+    pub fn expect(&mut self, text: &str) -> String {
+        let text = text.replace('\n', "\r\n");
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let found = self
+                .output
+                .windows(text.len())
+                .position(|window| window == text.as_bytes());
+            if let Some(start) = found {
+                let before = String::from_utf8_lossy(&self.output[..start]).replace("\r\n", "\n");
+                self.output.drain(..start + text.len());
+                return before;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !self.read(left) {
+                panic!(
+                    "expected {:?}, the shell printed {:?}",
+                    text,
+                    String::from_utf8_lossy(&self.output)
+                );
+            }
+        }
+    }
+
+    // read what's there, waiting at most `timeout`. False once nothing is
+    // left to read, when the shell and everything it started have exited
+    fn read(&mut self, timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: self.master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as i32) } <= 0 {
+            return false;
+        }
+        let mut chunk = [0; 4096];
+        match self.master.read(&mut chunk) {
+            Ok(n) if n > 0 => {
+                self.output.extend_from_slice(&chunk[..n]);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Wait for the shell to exit, after an `exit` or Ctrl-D
+    pub fn wait(mut self) -> ExitStatus {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status;
+            }
+            if Instant::now() > deadline {
+                self.child.kill().unwrap();
+                panic!("the shell didn't exit");
+            }
+            self.read(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        // a test that failed leaves the shell and its jobs behind otherwise
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+// The state a process is in, as the letter ps shows: 'S' for sleeping, 'T'
+// for stopped. None once it's gone
+pub fn process_state(pid: u32) -> Option<char> {
+    process::state(pid)
+}
+
+// Wait for a process to be in `state`, None for it to be gone
+pub fn wait_for_state(pid: u32, state: Option<char>) {
+    wait_until(&format!("process {} to be {:?}", pid, state), || {
+        process_state(pid) == state
+    });
+}
+
+// check `done` until it's true, failing the test if that takes too long
+fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !done() {
+        if Instant::now() > deadline {
+            panic!("timed out waiting for {}", what);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn open_pty() -> io::Result<(File, File)> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(master);
+        if libc::grantpt(master.as_raw_fd()) < 0 || libc::unlockpt(master.as_raw_fd()) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let name = terminal_name(&master)?;
+        let terminal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(name)?;
+        Ok((master, terminal))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn terminal_name(master: &File) -> io::Result<String> {
+    let mut name = [0; 64];
+    let err = unsafe { libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+// ptsname_r is Linux's, ptsname's buffer is shared by the tests running
+// side by side
+#[cfg(not(target_os = "linux"))]
+fn terminal_name(master: &File) -> io::Result<String> {
+    static PTSNAME: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _lock = PTSNAME.lock().unwrap();
+    let name = unsafe { libc::ptsname(master.as_raw_fd()) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+// What the tests need to know about a process, from /proc on Linux
+#[cfg(target_os = "linux")]
+mod process {
+    use std::fs;
+
+    pub fn state(pid: u32) -> Option<char> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // the name in parentheses can have spaces, the state comes after it
+        let (_, rest) = stat.rsplit_once(')')?;
+        rest.trim_start().chars().next()
+    }
+
+    pub fn name(pid: u32) -> Option<String> {
+        let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        Some(comm.trim_end().to_string())
+    }
+
+    // in a read of its stdin, or polling it like the line editor does
+    pub fn reads_terminal(pid: u32) -> bool {
+        let Ok(syscall) = fs::read_to_string(format!("/proc/{}/syscall", pid)) else {
+            return false;
+        };
+        let polls = [
+            libc::SYS_ppoll,
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_poll,
+        ];
+        syscall.starts_with(&format!("{} 0x0 ", libc::SYS_read))
+            || polls
+                .iter()
+                .any(|poll| syscall.starts_with(&format!("{} ", poll)))
+    }
+
+    // in waitpid, which is wait4 underneath
+    pub fn waits_for_children(pid: u32) -> bool {
+        fs::read_to_string(format!("/proc/{}/syscall", pid))
+            .is_ok_and(|syscall| syscall.starts_with(&format!("{} ", libc::SYS_wait4)))
+    }
+}
+
+// and from ps on the BSDs, which have no /proc by default
+#[cfg(not(target_os = "linux"))]
+mod process {
+    use std::process::Command;
+
+    fn ps(pid: u32, field: &str) -> Option<String> {
+        let output = Command::new("ps")
+            .args(["-o", &format!("{}=", field), "-p", &pid.to_string()])
+            .output()
+            .ok()?;
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !value.is_empty()).then_some(value)
+    }
+
+    pub fn state(pid: u32) -> Option<char> {
+        ps(pid, "stat")?.chars().next()
+    }
+
+    pub fn name(pid: u32) -> Option<String> {
+        ps(pid, "comm")
+    }
+
+    // waiting on the terminal for input, or in the line editor's poll
+    pub fn reads_terminal(pid: u32) -> bool {
+        ps(pid, "wchan").is_some_and(|wchan| wchan == "ttyin" || wchan == "select")
+    }
+
+    pub fn waits_for_children(pid: u32) -> bool {
+        ps(pid, "wchan").is_some_and(|wchan| wchan == "wait")
+    }
+}
@@ -0,0 +1,232 @@
+#![cfg(all(feature = "completion", not(target_vendor = "apple")))]
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{executable, pty::Pty, test_dir};
+
+// At a shell with the line editor, run `setup`, then type `line`, Tab and
+// Enter and wait for `output`. What the editor draws is full of escape
+// sequences, only what the line prints is looked for
+fn tab(setup: &str, line: &str, output: &str) {
+    tab_with_env(&[], setup, line, output);
+}
+
+// tab with `env` added to the shell's environment
+fn tab_with_env(env: &[(&str, &str)], setup: &str, line: &str, output: &str) {
+    let env = [&[("TERM", "xterm")], env].concat();
+    let mut pty = Pty::spawn_with_env(&env);
+    pty.send(&format!("{}\n{}\t\n", setup, line));
+    pty.expect(output);
+}
+
+// A directory of files to complete the names of
+fn files() -> PathBuf {
+    let dir = test_dir().join("complete_files");
+    for sub in ["some dir", "subdir"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    for file in ["apple_one", "apple_two", "unique_file", "subnote"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    dir
+}
+
+// A directory for $PATH, with a command named like a builtin
+fn commands() -> PathBuf {
+    let dir = test_dir().join("complete_path");
+    fs::create_dir_all(&dir).unwrap();
+    for name in ["dirs", "diff_tool", "dizzy", "exit_tool"] {
+        executable(&format!("complete_path/{}", name), b"#!/bin/sh\n");
+    }
+    dir
+}
+
+#[test]
+fn words_complete_the_arguments() {
+    tab("complete -W 'alpha beta' echo", "echo al", "alpha\n");
+}
+
+#[test]
+fn a_command_completes_the_arguments() {
+    let completer = executable(
+        "completer",
+        b"#!/bin/sh\necho \"$2:$1:$3:$COMP_CWORD\"\necho other\n",
+    );
+    let setup = format!("complete -C {} echo", completer.display());
+    tab(&setup, "echo one tw", "one tw:echo:one:2\n");
+}
+
+#[test]
+fn j_completes_the_directories_it_knows() {
+    let dir = test_dir().canonicalize().unwrap().join("jump_completion");
+    fs::create_dir_all(dir.join("zebra_stripes")).unwrap();
+    let file = dir.join("jumps");
+    let _ = fs::remove_file(&file);
+    let env = [("TERM", "xterm"), ("ISH_JUMPFILE", file.to_str().unwrap())];
+    let mut pty = Pty::spawn_with_env(&env);
+    pty.send(&format!(
+        "cd {}/zebra_stripes\ncd /\nj zeb\t",
+        dir.display()
+    ));
+    // what the Tab put in after what was typed
+    pty.expect("ra_stripes ");
+    pty.send("\npwd\n");
+    pty.expect(&format!("{}/zebra_stripes\n", dir.display()));
+}
+
+#[test]
+fn a_unique_directory_gets_a_slash_and_a_file_a_space() {
+    let setup = format!("cd '{}'", files().display());
+    // what's typed after the Tab goes on with the word or starts the next one
+    tab(&setup, "printf '<%s>\\n' subd\tX", "<subdir/X>\n");
+    tab(&setup, "printf '<%s>\\n' uni\tX", "<unique_file>\n<X>\n");
+}
+
+#[test]
+fn the_common_prefix_of_the_matches_is_completed() {
+    let setup = format!("cd '{}'", files().display());
+    tab(&setup, "printf '<%s>\\n' app\tX", "<apple_X>\n");
+}
+
+#[test]
+fn tilde_and_variables_are_expanded_to_look_for_matches() {
+    let dir = files();
+    let expected = format!("<{}/unique_file>\n<X>\n", dir.display());
+    let home = test_dir();
+    let env = [
+        ("HOME", home.to_str().unwrap()),
+        ("FILES", dir.to_str().unwrap()),
+    ];
+    tab_with_env(
+        &env,
+        "",
+        "printf '<%s>\\n' ~/complete_files/uni\tX",
+        &expected,
+    );
+    // the names are looked up where $FILES is, it stays in the line as typed
+    let line = "printf '<%s>\\n' $FILES/uni\tX";
+    tab_with_env(&env, "", line, "<$FILES/unique_file>\n<X>\n");
+}
+
+#[test]
+fn spaces_in_names_are_escaped() {
+    let setup = format!("cd '{}'", files().display());
+    tab(&setup, "printf '<%s>\\n' som\tX", "<some dir/X>\n");
+}
+
+#[test]
+fn command_names_are_builtins_and_commands_on_the_path() {
+    let path = commands();
+    let env = [("PATH", path.to_str().unwrap())];
+    // the terminal's size is unknown, so there's a list instead of a menu. Its
+    // rows end in "\r\n", which the terminal makes "\r\r\n"
+    tab_with_env(&env, "", "ex", "exec       exit       exit_tool\r\n");
+    // sorted, and dirs is there once for the builtin and the command
+    tab_with_env(&env, "", "di", "diff_tool  dirs       dizzy\r\n");
+}
+
+#[test]
+fn command_names_with_a_slash_are_executable_files() {
+    let dir = files();
+    executable("complete_files/local_tool", b"#!/bin/sh\necho ran local\n");
+    fs::write(dir.join("locale_data"), "").unwrap();
+    let setup = format!("cd '{}'", dir.display());
+    tab(&setup, "./loc", "ran local\n");
+}
+
+// Type `line` and Tab, then Ctrl-A and echo so what the Tab put in is printed
+fn tab_and_echo(setup: &str, line: &str, output: &str) {
+    let mut pty = Pty::spawn_with_env(&[("TERM", "xterm")]);
+    pty.send(&format!("{}\n{}\t\x01echo \n", setup, line));
+    pty.expect(output);
+}
+
+#[test]
+fn cd_completes_only_directories() {
+    // subnote is there too
+    let setup = format!("cd '{}'", files().display());
+    tab_and_echo(&setup, "cd sub", "cd subdir/\n");
+}
+
+#[test]
+fn fg_completes_job_specs() {
+    tab_and_echo("sleep 5 &", "fg %", "fg %1\n");
+}
+
+#[test]
+fn kill_completes_signal_names() {
+    tab_and_echo("", "kill -TER", "kill -TERM\n");
+}
+
+// A directory with files to pick from a menu, more of them than fit in a
+// small terminal
+fn menu_files() -> PathBuf {
+    let dir = test_dir().join("menu_files");
+    fs::create_dir_all(&dir).unwrap();
+    let apples = ["apple_one", "apple_three", "apple_two"].map(String::from);
+    let picks = (1..=12).map(|n| format!("pick_{:02}", n));
+    for name in apples.into_iter().chain(picks) {
+        fs::write(dir.join(name), "").unwrap();
+    }
+    dir
+}
+
+// A shell at a terminal `columns` wide and `rows` high, in the menu_files
+// directory. Colors and suggestions are off so the line is drawn as it is
+fn menu_shell(columns: u16, rows: u16) -> Pty {
+    let mut pty = Pty::spawn_with_env(&[("TERM", "xterm")]);
+    pty.send(&format!("cd '{}'\n", menu_files().display()));
+    pty.send("set +o highlight\nset +o autosuggest\n");
+    pty.expect("$ set +o autosuggest\r\x1b[20C\r\n");
+    pty.prompt();
+    pty.resize(columns, rows);
+    pty
+}
+
+#[test]
+fn tab_and_shift_tab_go_through_the_menu() {
+    let mut pty = menu_shell(80, 24);
+    // the first Tab completes what the matches agree on, the second shows them
+    pty.send("printf '<%s>\\n' app\t\t\t\t\x1b[Z\r\r");
+    pty.expect("<apple_three>\n");
+    // Shift-Tab on the first goes round to the last
+    pty.send("printf '<%s>\\n' app\t\t\x1b[Z\r\r");
+    pty.expect("<apple_two>\n");
+}
+
+#[test]
+fn escape_puts_back_the_line_from_before_the_menu() {
+    let mut pty = menu_shell(80, 24);
+    pty.send("printf '<%s>\\n' app\t\t\t");
+    pty.expect("\x1b[7mapple_three\x1b[0m");
+    // on its own, Escape followed by a key would be Alt and the key
+    pty.send("\x1b");
+    let line = "printf '<%s>\\n' apple_";
+    pty.expect(&format!("\r\x1b[J$ {}\r\x1b[{}C", line, line.len() + 2));
+    pty.send("X\r");
+    pty.expect("<apple_X>\n");
+}
+
+#[test]
+fn a_menu_too_long_for_the_terminal_is_shown_a_page_at_a_time() {
+    // two columns of names and three rows of them under the line
+    let mut pty = menu_shell(20, 5);
+    pty.send("echo pick_\t");
+    pty.expect("rows 1 to 3 of 6");
+    pty.send("\t\t\t");
+    pty.expect("rows 4 to 6 of 6");
+    pty.send("\r\r");
+    pty.expect("pick_04\n");
+}
+
+#[test]
+fn without_the_terminal_size_the_matches_are_listed() {
+    let setup = format!("cd '{}'", menu_files().display());
+    tab(
+        &setup,
+        "printf '<%s>\\n' apple_",
+        "apple_one    apple_three  apple_two\r\n",
+    );
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{run_shell_with_args, run_shell_with_env};
+
+// the kinds of the trace lines, in order
+fn kinds(stderr: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .map(|line| {
+            assert!(line.starts_with("ish-debug["), "{}", line);
+            let (_, rest) = line.split_once("] ").unwrap();
+            rest.split(':').next().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn nothing_is_traced_by_default() {
+    let output = run_shell_with_env(&[], &[("ISH_DEBUG", "")], "echo hi | cat\n");
+    assert_eq!(output.stdout, b"hi\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn ish_debug_traces_parsing_and_running() {
+    let output = run_shell_with_env(&[], &[("ISH_DEBUG", "1")], "true > /dev/null\n");
+    assert_eq!(
+        kinds(&output.stderr),
+        [
+            "tokens", "pipeline", "expand", "command", "expand", "redirect", "spawn", "job",
+            "wait", "job"
+        ]
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(r#"expand: "true" -> ["true"]"#),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Running -> Done"), "{}", stderr);
+}
+
+#[test]
+fn debug_flag_turns_tracing_on() {
+    let output = run_shell_with_args(&["--debug"], "help help\n");
+    assert_eq!(
+        kinds(&output.stderr),
+        ["tokens", "pipeline", "expand", "expand", "command"]
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(r#"command: ["help", "help"], builtin: true"#),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,359 @@
+#![cfg(all(feature = "editor", not(target_vendor = "apple")))]
+
+mod common;
+
+use common::{executable, pty::Pty};
+
+// A shell with the line editor, without the colors and suggestions it would
+// draw the line with otherwise, so the line is in what it prints as it is
+fn editor(env: &[(&str, &str)]) -> Pty {
+    let env = [&[("TERM", "xterm")], env].concat();
+    let mut pty = Pty::spawn_with_env(&env);
+    pty.send("set +o highlight\r");
+    pty.send("set +o autosuggest\r");
+    pty.expect("$ set +o autosuggest\r\x1b[20C\r\n");
+    pty.prompt();
+    pty
+}
+
+// Type `keys` and wait for the editor to finish the line, drawn as `line`
+// with the cursor after it, and its own "\r\n" made "\r\r\n" by the terminal.
+// Returns what was printed until the next prompt, the modes the editor sets
+// the terminal to left out
+fn edit(pty: &mut Pty, keys: &str, line: &str) -> String {
+    pty.send(keys);
+    pty.expect(&format!(
+        "$ {}\r\x1b[{}C\r\n",
+        line,
+        line.chars().count() + 2
+    ));
+    pty.prompt()
+        .replace("\x1b[?2004l", "")
+        .replace("\x1b[?2004h", "")
+        .replace("\r\x1b[J", "")
+}
+
+// an editor that puts a command of its own in the file, after checking the
+// line is in it
+fn text_editor() -> String {
+    let editor = executable(
+        "text_editor",
+        b"#!/bin/sh\n[ -L \"$1\" ] && exit 1\ngrep -q 'echo typed' \"$1\" || exit 1\n\
+          echo 'echo edited-line' > \"$1\"\n",
+    );
+    editor.to_str().unwrap().to_string()
+}
+
+#[test]
+fn ctrl_x_ctrl_e_runs_what_the_editor_left() {
+    let visual = text_editor();
+    let mut pty = editor(&[("VISUAL", &visual)]);
+    // it's shown before it runs
+    assert_eq!(
+        edit(&mut pty, "echo typed\x18\x05", "echo typed"),
+        "echo edited-line\nedited-line\n"
+    );
+}
+
+#[test]
+fn a_failed_editor_runs_nothing() {
+    let failing = executable("failing_editor", b"#!/bin/sh\nexit 1\n");
+    let mut pty = editor(&[("VISUAL", failing.to_str().unwrap())]);
+    assert_eq!(edit(&mut pty, "echo typed\x18\x05", "echo typed"), "");
+    assert_eq!(edit(&mut pty, "echo after\r", "echo after"), "after\n");
+}
+
+#[test]
+fn without_editexec_the_edited_line_is_typed_again() {
+    let visual = text_editor();
+    let mut pty = editor(&[("VISUAL", &visual)]);
+    assert_eq!(edit(&mut pty, "set +o editexec\r", "set +o editexec"), "");
+    assert_eq!(edit(&mut pty, "echo typed\x18\x05", "echo typed"), "");
+    // the next prompt has it, for Enter to run it
+    assert_eq!(edit(&mut pty, "\r", "echo edited-line"), "edited-line\n");
+}
+
+#[test]
+fn arrows_move_the_cursor_to_insert_in_the_middle() {
+    let mut pty = editor(&[]);
+    assert_eq!(edit(&mut pty, "echo ac\x1b[Db\r", "echo abc"), "abc\n");
+    assert_eq!(
+        edit(&mut pty, "echo ac\x1b[D\x1b[D\x1b[Cb\r", "echo abc"),
+        "abc\n"
+    );
+}
+
+#[test]
+fn home_and_end_go_to_the_ends_of_the_line() {
+    let mut pty = editor(&[]);
+    assert_eq!(
+        edit(&mut pty, "cho x\x1b[He\x1b[F y\r", "echo x y"),
+        "x y\n"
+    );
+}
+
+#[test]
+fn delete_removes_the_character_under_the_cursor() {
+    let mut pty = editor(&[]);
+    assert_eq!(
+        edit(&mut pty, "echo abxc\x1b[D\x1b[D\x1b[3~\r", "echo abc"),
+        "abc\n"
+    );
+    // nothing to delete at the end of the line
+    assert_eq!(edit(&mut pty, "echo abc\x1b[3~\r", "echo abc"), "abc\n");
+}
+
+#[test]
+fn commands_run_with_the_terminal_in_cooked_mode() {
+    let mut pty = editor(&[]);
+    let line = "stty -a | tr ' ;' '\\n\\n' | grep -x -e icanon -e -icanon -e echo -e -echo";
+    assert_eq!(
+        edit(&mut pty, &format!("{}\r", line), line),
+        "icanon\necho\n"
+    );
+}
+
+// a shell with some history to search
+fn searched() -> Pty {
+    let mut pty = editor(&[]);
+    for line in ["echo apple-one", "echo apple-two", "echo banana"] {
+        edit(&mut pty, &format!("{}\r", line), line);
+    }
+    pty
+}
+
+#[test]
+fn reverse_search_narrows_the_match_as_the_query_grows() {
+    let mut pty = searched();
+    pty.send("\x12ap");
+    pty.expect("(reverse-i-search)`ap': echo apple-two");
+    assert_eq!(edit(&mut pty, "ple-o\r", "echo apple-one"), "apple-one\n");
+}
+
+#[test]
+fn ctrl_r_again_finds_older_matches() {
+    let mut pty = searched();
+    assert_eq!(
+        edit(&mut pty, "\x12apple\x12\r", "echo apple-one"),
+        "apple-one\n"
+    );
+}
+
+#[test]
+fn backspace_widens_the_query() {
+    let mut pty = searched();
+    pty.send("\x12apple-o");
+    pty.expect("(reverse-i-search)`apple-o': echo apple-one");
+    assert_eq!(
+        edit(&mut pty, "\x7f\x7f\r", "echo apple-two"),
+        "apple-two\n"
+    );
+}
+
+#[test]
+fn a_failed_search_says_so_and_keeps_the_last_match() {
+    let mut pty = searched();
+    pty.send("\x12banx");
+    pty.expect("(failed reverse-i-search)`banx': echo banana");
+    // Ctrl-G gives up and brings back the empty line
+    assert_eq!(edit(&mut pty, "\x07echo done\r", "echo done"), "done\n");
+}
+
+#[test]
+fn ctrl_a_and_ctrl_e_go_to_the_ends_of_the_line() {
+    let mut pty = editor(&[]);
+    assert_eq!(edit(&mut pty, "cho x\x01e\x05 y\r", "echo x y"), "x y\n");
+}
+
+#[test]
+fn ctrl_u_and_ctrl_k_kill_to_the_ends_of_the_line() {
+    let mut pty = editor(&[]);
+    assert_eq!(edit(&mut pty, "junk echo\x15echo ok\r", "echo ok"), "ok\n");
+    let keys = "echo ok junk\x1b[D\x1b[D\x1b[D\x1b[D\x1b[D\x0b\r";
+    assert_eq!(edit(&mut pty, keys, "echo ok"), "ok\n");
+}
+
+#[test]
+fn ctrl_w_kills_back_to_whitespace() {
+    let mut pty = editor(&[]);
+    // unlike the word motions it doesn't stop at the punctuation
+    let keys = "echo one two/three-four\x17five\r";
+    assert_eq!(edit(&mut pty, keys, "echo one five"), "one five\n");
+}
+
+#[test]
+fn ctrl_t_swaps_the_characters_around_the_cursor() {
+    let mut pty = editor(&[]);
+    // at the end of the line the last two, and the cursor stays after them
+    assert_eq!(edit(&mut pty, "echo ab\x14\r", "echo ba"), "ba\n");
+    assert_eq!(
+        edit(&mut pty, "echo acb\x1b[D\x14d\r", "echo abcd"),
+        "abcd\n"
+    );
+}
+
+#[test]
+fn ctrl_d_deletes_on_a_line_and_ends_the_input_on_an_empty_one() {
+    let mut pty = editor(&[]);
+    let keys = "echo abxc\x1b[D\x1b[D\x04\r";
+    assert_eq!(edit(&mut pty, keys, "echo abc"), "abc\n");
+    // at the end of a line there's nothing to delete, and it doesn't exit
+    assert_eq!(edit(&mut pty, "echo abc\x04\r", "echo abc"), "abc\n");
+    pty.send("\x04");
+    assert!(pty.wait().success());
+}
+
+#[test]
+fn word_motions_stop_at_slashes_and_dashes() {
+    let mut pty = editor(&[]);
+    let line = "printf '<%s>\\n' one/two-three";
+    let keys = format!("{}\x1bbX\x1bb\x1bbY\r", line);
+    assert_eq!(
+        edit(&mut pty, &keys, "printf '<%s>\\n' one/Ytwo-Xthree"),
+        "<one/Ytwo-Xthree>\n"
+    );
+    let keys = "echo one/two-three\x01\x1bf\x1bfX\r";
+    assert_eq!(
+        edit(&mut pty, keys, "echo oneX/two-three"),
+        "oneX/two-three\n"
+    );
+    // Alt-d kills to the end of the word, the space before it included
+    let keys = "echo one/two-three\x01\x1bf\x1bf\x1bd\r";
+    assert_eq!(edit(&mut pty, keys, "echo one-three"), "one-three\n");
+}
+
+#[test]
+fn alt_dot_cycles_through_the_last_arguments() {
+    let mut pty = editor(&[]);
+    edit(&mut pty, "echo first-arg\r", "echo first-arg");
+    edit(&mut pty, "echo second-arg\r", "echo second-arg");
+    assert_eq!(
+        edit(&mut pty, "echo \x1b.\r", "echo second-arg"),
+        "second-arg\n"
+    );
+    // pressed again, it's replaced by the one of the command before
+    assert_eq!(
+        edit(&mut pty, "echo \x1b.\x1b.\r", "echo first-arg"),
+        "first-arg\n"
+    );
+}
+
+#[test]
+fn ctrl_l_clears_the_screen_and_draws_the_line_again() {
+    let mut pty = editor(&[]);
+    pty.send("echo kept\x0c");
+    pty.expect("\x1b[H\x1b[2J\r\x1b[J$ echo kept\r\x1b[11C");
+    assert_eq!(edit(&mut pty, "\r", "echo kept"), "kept\n");
+}
+
+#[test]
+fn pasted_lines_only_run_after_enter() {
+    let mut pty = editor(&[]);
+    pty.send("\x1b[200~printf '=%s\\n' a\rprintf '=%s\\n' b\x1b[201~");
+    let before = pty.expect("printf '=%s\\n' b");
+    assert!(!before.contains("=a"), "{:?}", before);
+    pty.send("\r");
+    pty.expect("=a\n=b\n");
+}
+
+#[test]
+fn ctrl_y_yanks_what_was_killed_last() {
+    let mut pty = editor(&[]);
+    assert_eq!(
+        edit(&mut pty, "echo kept\x17\x19\x19\r", "echo keptkept"),
+        "keptkept\n"
+    );
+}
+
+#[test]
+fn alt_y_goes_round_the_kill_ring() {
+    let mut pty = editor(&[]);
+    let keys = "echo first\x17second\x17\x19\x1by\r";
+    assert_eq!(edit(&mut pty, keys, "echo first"), "first\n");
+    // the ring is kept from one line to the next
+    let keys = "echo \x19\x1by\x1by\r";
+    assert_eq!(edit(&mut pty, keys, "echo second"), "second\n");
+}
+
+#[test]
+fn kills_right_after_each_other_join() {
+    let mut pty = editor(&[]);
+    let keys = "echo one two\x17\x17\x19\r";
+    assert_eq!(edit(&mut pty, keys, "echo one two"), "one two\n");
+    // text killed forward goes after, backward in front
+    let keys = "echo a b c\x1b[D\x1b[D\x0b\x17\x19\x05\x19\r";
+    assert_eq!(edit(&mut pty, keys, "echo a b cb c"), "a b cb c\n");
+}
+
+#[test]
+fn backspace_removes_a_whole_character() {
+    let mut pty = editor(&[]);
+    // an e with a combining accent, and an emoji made of three characters
+    assert_eq!(edit(&mut pty, "echo ae\u{301}\x7fb\r", "echo ab"), "ab\n");
+    let keys = "echo a\u{1f469}\u{200d}\u{1f4bb}\x7fb\r";
+    assert_eq!(edit(&mut pty, keys, "echo ab"), "ab\n");
+}
+
+// press Ctrl-C and wait for the next prompt
+fn interrupt(pty: &mut Pty) {
+    pty.send("\x03");
+    pty.expect("^C\n");
+    pty.prompt();
+}
+
+#[test]
+fn ctrl_c_starts_the_next_line_afresh() {
+    let mut pty = editor(&[]);
+    // a yank can't be rotated on the next line. What's typed before Ctrl-C
+    // has to be read first, the terminal throws it away otherwise
+    pty.send("echo kept\x17\x19");
+    pty.expect("$ echo \r\x1b[7C\r\x1b[J$ echo kept\r\x1b[11C");
+    interrupt(&mut pty);
+    assert_eq!(edit(&mut pty, "\x1byecho fresh\r", "echo fresh"), "fresh\n");
+    // a search is given up
+    pty.send("\x12fres");
+    pty.expect("(reverse-i-search)`fres': echo fresh");
+    interrupt(&mut pty);
+    assert_eq!(edit(&mut pty, "echo ok\r", "echo ok"), "ok\n");
+    // and so is a command that wasn't finished
+    pty.send("echo 'open\r");
+    pty.expect("> ");
+    interrupt(&mut pty);
+    assert_eq!(edit(&mut pty, "echo closed\r", "echo closed"), "closed\n");
+}
+
+#[test]
+fn a_resize_draws_the_line_again_and_sets_columns() {
+    let mut pty = editor(&[]);
+    pty.send("sh -c 'echo $COLUMNS'");
+    pty.expect("sh -c 'echo $COLUMNS'");
+    pty.resize(50, 20);
+    pty.expect("\r\x1b[J$ sh -c 'echo $COLUMNS'\r\x1b[23C");
+    pty.send("\r");
+    pty.expect("50\n");
+    // for a line too long for the new width the cursor ends up a row down
+    pty.prompt();
+    pty.send("echo 123456789");
+    pty.expect("echo 123456789");
+    pty.resize(10, 20);
+    pty.expect("\r\x1b[J$ echo 123456789\r\x1b[6C");
+}
+
+#[test]
+fn the_right_prompt_goes_at_the_right_end_of_the_row() {
+    let mut pty = editor(&[("RPS1", "<rp>")]);
+    pty.resize(20, 10);
+    pty.send("echo hi");
+    // with a column free after it
+    pty.expect("\r\x1b[J$ echo hi\r\x1b[15C<rp>\r\x1b[9C");
+    // it's left out once the line would run into it, and back when it doesn't
+    pty.send(" 12345\x7f");
+    pty.expect(concat!(
+        "\r\x1b[J$ echo hi 12345\r\x1b[15C",
+        "\r\x1b[J$ echo hi 1234\r\x1b[15C<rp>\r\x1b[14C"
+    ));
+    // and it doesn't stay behind when the line is run. With the size of the
+    // terminal known the output is followed by the partial line marker
+    let printed = edit(&mut pty, "\r", "echo hi 1234");
+    assert!(printed.starts_with("hi 1234\n"), "{:?}", printed);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::fs;
+
+use common::{script, test_dir};
+use ish::{error::ShellError, exec::ExitStatus, options::Options, shell::Shell};
+
+#[test]
+fn lines_give_their_status() {
+    let mut shell = Shell::new(Options::default());
+    assert_eq!(shell.run_line("true").unwrap(), ExitStatus::Done(0));
+    assert_eq!(shell.run_line("false | cat").unwrap(), ExitStatus::Done(0));
+    assert_eq!(
+        shell.run_line("sh -c 'exit 4'").unwrap(),
+        ExitStatus::Done(4)
+    );
+    assert_eq!(shell.last_status, 4);
+    assert_eq!(shell.run_line("exit 5").unwrap(), ExitStatus::Exit(5));
+    // the shell is still usable after a line that would have ended it
+    assert_eq!(shell.run_line("true").unwrap(), ExitStatus::Done(0));
+}
+
+#[test]
+fn errors_are_returned() {
+    let mut shell = Shell::new(Options::default());
+    let err = shell.run_line("| cat").unwrap_err();
+    assert!(matches!(err, ShellError::Parse(_)));
+    assert_eq!(shell.last_status, 2);
+    let err = shell.run_line("timeout x true").unwrap_err();
+    assert_eq!(err.to_string(), "timeout: x: invalid time interval");
+    assert_eq!(shell.last_status, 125);
+}
+
+#[test]
+fn scripts_get_their_arguments() {
+    let out = test_dir().join("embedded_script_out");
+    let path = script(
+        "embedded.sh",
+        &format!("echo $# $1 \"$2\" > {}\nexit 3\n", out.display()),
+    );
+    let mut shell = Shell::new(Options::default());
+    let status = shell.run_script(&path, &["a".into(), "b c".into()]);
+    assert_eq!(status.unwrap(), ExitStatus::Exit(3));
+    assert_eq!(fs::read_to_string(&out).unwrap(), "2 a b c\n");
+    // the shell's own parameters are back
+    assert_eq!(shell.positional, ["ish"]);
+
+    let missing = test_dir().join("no_such_script.sh");
+    let err = shell.run_script(&missing, &[]).unwrap_err();
+    assert_eq!(err.status(), 127);
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::fs;
+
+use common::{run_shell_with_env, script, test_dir};
+
+fn stdout(env: &[(&str, &str)], input: &str) -> String {
+    String::from_utf8(run_shell_with_env(&[], env, input).stdout).unwrap()
+}
+
+#[test]
+fn shell_points_at_ish() {
+    assert_eq!(
+        stdout(&[("SHELL", "/bin/false")], "printenv SHELL\n"),
+        format!("{}\n", env!("CARGO_BIN_EXE_ish"))
+    );
+}
+
+#[test]
+fn shlvl_is_incremented() {
+    assert_eq!(stdout(&[("SHLVL", "3")], "printenv SHLVL\n"), "4\n");
+    assert_eq!(stdout(&[("SHLVL", "junk")], "printenv SHLVL\n"), "1\n");
+    // a nested shell sees the level of the one that started it
+    let nested = script("shlvl", "printenv SHLVL\n");
+    let input = format!("{} {}\n", env!("CARGO_BIN_EXE_ish"), nested.display());
+    assert_eq!(stdout(&[("SHLVL", "1")], &input), "3\n");
+}
+
+#[test]
+fn cd_updates_pwd_and_oldpwd() {
+    let dir = test_dir().canonicalize().unwrap();
+    let dir = dir.to_str().unwrap();
+    assert_eq!(
+        stdout(
+            &[],
+            &format!("cd /\ncd {}\nprintenv PWD\nprintenv OLDPWD\n", dir)
+        ),
+        format!("{}\n/\n", dir)
+    );
+}
+
+#[test]
+fn stale_pwd_is_replaced() {
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        stdout(&[("PWD", "/nonexistent")], "printenv PWD\n"),
+        format!("{}\n", cwd.display())
+    );
+}
+
+#[test]
+fn autocd_goes_into_directories_typed_as_commands() {
+    let dir = test_dir().canonicalize().unwrap().join("autocd");
+    fs::create_dir_all(dir.join("sub/true")).unwrap();
+    let input = format!("cd {}\nsub\npwd\ntrue\npwd\n", dir.display());
+    let interactive = |options: &str| {
+        let input = format!("{}\n{}", options, input);
+        let output = run_shell_with_env(&["-i"], &[("PS1", "")], &input);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // a command by the same name still runs
+    assert_eq!(
+        interactive("set -o autocd"),
+        format!("{0}/sub\n{0}/sub\n", dir.display())
+    );
+    assert_eq!(
+        interactive("set +o autocd"),
+        format!("{0}\n{0}\n", dir.display())
+    );
+    // scripts never do it
+    assert_eq!(
+        stdout(&[], &format!("set -o autocd\n{}", input)),
+        format!("{0}\n{0}\n", dir.display())
+    );
+}
+
+#[test]
+fn cdspell_corrects_a_typo_in_a_directory() {
+    let dir = test_dir().canonicalize().unwrap().join("cdspell");
+    fs::create_dir_all(dir.join("local/bin")).unwrap();
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    let input = format!(
+        "set -o cdspell\ncd {0}/lcoal/bni\npwd\ncd {0}/lx\n",
+        dir.display()
+    );
+    let output = run_shell_with_env(&["-i"], &[("PS1", "")], &input);
+    let corrected = format!("{}/local/bin", dir.display());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{0}\n{0}\n", corrected)
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "ish: cd: {}/lx: No such file or directory\nexit\n",
+            dir.display()
+        )
+    );
+    let output = run_shell_with_env(&[], &[], &input);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("lcoal/bni: No such file or directory"));
+}
+
+#[test]
+fn tilde_is_the_home_directory() {
+    assert_eq!(
+        stdout(
+            &[("HOME", "/home/someone")],
+            "printf '%s\\n' ~ ~/bin '~' \\~ a~ ~root/x ~no_such_user_here\n"
+        ),
+        "/home/someone\n/home/someone/bin\n~\n~\na~\n/root/x\n~no_such_user_here\n"
+    );
+}
+
+#[test]
+fn j_goes_to_the_directory_cd_went_to_most() {
+    let dir = test_dir().canonicalize().unwrap().join("jump");
+    for sub in ["alpha/project", "beta/project", "other"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let file = dir.join("jumps");
+    let _ = fs::remove_file(&file);
+    let env = [("PS1", ""), ("ISH_JUMPFILE", file.to_str().unwrap())];
+    let visits = format!(
+        "cd {0}/alpha/project\ncd {0}/other\ncd {0}/alpha/project\ncd {0}/beta/project\ncd /\n",
+        dir.display()
+    );
+    let input = format!(
+        "{}j proj\npwd\nj BETA\nj bet proj\npwd\nj -l proj\n",
+        visits
+    );
+    let output = run_shell_with_env(&["-i"], &env, &input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[..2],
+        [
+            format!("{}/alpha/project", dir.display()),
+            format!("{}/beta/project", dir.display())
+        ]
+    );
+    assert!(lines[2].ends_with(&format!("{}/alpha/project", dir.display())));
+    assert!(lines[3].ends_with(&format!("{}/beta/project", dir.display())));
+    assert_eq!(lines.len(), 4);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("ish: j: BETA: no directory matches\n"));
+    // scripts don't count where they go
+    let output = run_shell_with_env(&[], &env, &format!("cd {}\nj -l\n", dir.display()));
+    let listed = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(listed.lines().count(), 4);
+    assert!(!listed.lines().any(|line| line.ends_with("/jump")));
+}
+
+#[test]
+fn autopushd_keeps_the_directories_cd_left() {
+    let dir = test_dir().canonicalize().unwrap().join("autopushd");
+    for sub in ["a", "b", "c"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let env = [("HOME", "/nonexistent")];
+    let input = format!(
+        "cd /\nset -o autopushd\ncd {0}/a\ncd {0}/b\ncd {0}/c\ncd {0}/a\ndirs -v\n\
+         cd -2\npushd {0}/c\npopd\npwd\npopd +5\ncd -9\ndirs -c\npopd\n",
+        dir.display()
+    );
+    let output = run_shell_with_env(&[], &env, &input);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            " 0  {0}/a\n 1  {0}/c\n 2  {0}/b\n 3  /\n\
+             {0}/c {0}/b {0}/a /\n{0}/b {0}/a /\n{0}/b\n",
+            dir.display()
+        )
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: popd: +5: no such entry in the directory stack\n\
+         ish: cd: -9: no such entry in the directory stack\n\
+         ish: popd: directory stack empty\n"
+    );
+    // plain cd leaves the stack alone, and it only goes as deep as
+    // $DIRSTACKSIZE
+    let input = format!("cd {0}/a\ncd {0}/b\ndirs\n", dir.display());
+    assert_eq!(stdout(&env, &input), format!("{}/b\n", dir.display()));
+    let input = format!(
+        "set -o autopushd\ncd {0}/a\ncd {0}/b\ncd {0}/c\ndirs\n",
+        dir.display()
+    );
+    assert_eq!(
+        stdout(&[("HOME", "/nonexistent"), ("DIRSTACKSIZE", "1")], &input),
+        format!("{0}/c {0}/b\n", dir.display())
+    );
+}
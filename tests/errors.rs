@@ -0,0 +1,114 @@
+mod common;
+
+use std::fs;
+
+use common::{errors, run, run_shell_with_env, test_dir};
+
+#[test]
+fn errors_name_what_failed() {
+    assert_eq!(errors("bg\n"), "ish: bg: no stopped job\n");
+    assert_eq!(errors("fg\n"), "ish: fg: no current job\n");
+    assert_eq!(errors("jobs -o %3\n"), "ish: jobs: %3: no such job\n");
+    assert_eq!(errors("jobs -x\n"), "ish: jobs: -x: invalid option\n");
+    assert_eq!(errors("echo > $@\n"), "ish: $@: ambiguous redirect\n");
+    assert_eq!(errors("echo 2>&7\n"), "ish: 7: Bad file descriptor\n");
+    assert_eq!(
+        errors("exec ish-no-such-command\n"),
+        "ish: exec: ish-no-such-command: command not found\n"
+    );
+}
+
+#[test]
+fn errors_set_the_status() {
+    assert_eq!(run("| cat\necho $?\n"), "2\n");
+    assert_eq!(run("jobs -o\necho $?\n"), "1\n");
+    assert_eq!(run("jobs -x\necho $?\n"), "2\n");
+    assert_eq!(run("echo > $@\necho $?\n"), "1\n");
+    assert_eq!(run("timeout x true\necho $?\n"), "125\n");
+}
+
+#[test]
+fn malformed_input_doesnt_stop_the_shell() {
+    // everything is closed by the end, a quote left open would take the
+    // last line in
+    let input = r#"|
+| |
+& &
+echo &&
+echo &|
+>
+>&
+echo >&x
+echo 2>&99999999999
+echo < /
+cat < /nonexistent | cat > /
+$99999999999999999999
+$#$@$*$?
+fg %999
+jobs -o %
+jobs -o %-1
+set -o nonsense
+set +x
+history -d
+history -d 0-
+history -d 99999999999999999999
+source /
+timeout
+timeout -k
+timeout 1e400 true
+timeout nan true
+bindkey -M x a b
+bindkey -r ^
+exec -a
+cd ''
+ttyreset
+!
+!-0
+echo 'é
+'
+echo "
+"
+echo alive
+"#;
+    let histfile = test_dir().join("malformed_history");
+    let histfile = histfile.to_str().unwrap();
+    let output = run_shell_with_env(&["-i", "--norc"], &[("HISTFILE", histfile)], input);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(stdout.ends_with("alive\n> "), "{}", stdout);
+    assert!(output.status.success());
+}
+
+// What an interactive shell without a startup file prints on stderr for
+// `input`, with nothing on $PATH so only builtins are suggested
+fn interactive_errors(input: &str) -> String {
+    let path = test_dir().join("empty_path");
+    fs::create_dir_all(&path).unwrap();
+    let histfile = test_dir().join("suggest_history");
+    let env = [
+        ("PATH", path.to_str().unwrap()),
+        ("HISTFILE", histfile.to_str().unwrap()),
+        ("PS1", ""),
+    ];
+    let output = run_shell_with_env(&["-i", "--norc"], &env, input);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn similar_commands_are_suggested_at_an_interactive_shell() {
+    let errors = interactive_errors("prnitf hi\n");
+    assert!(errors.contains("Did you mean: printf?\n"), "{}", errors);
+}
+
+#[test]
+fn scripts_get_no_suggestions() {
+    assert!(!errors("prnitf hi\n").contains("Did you mean"));
+}
+
+#[test]
+fn set_o_suggest_turns_them_off() {
+    let errors = interactive_errors("set +o suggest\nprnitf hi\n");
+    assert!(errors.contains("prnitf: command not found\n"), "{}", errors);
+    assert!(!errors.contains("Did you mean"), "{}", errors);
+}
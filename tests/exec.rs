@@ -0,0 +1,109 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use common::{errors, run, shell_status, test_dir};
+
+#[test]
+fn exec_replaces_the_shell() {
+    assert_eq!(run("exec echo replaced\necho not reached\n"), "replaced\n");
+    assert_eq!(shell_status("exec sh -c 'exit 3'\n"), 3);
+}
+
+#[test]
+fn exec_a_sets_argv0() {
+    // only Linux shows the arguments in /proc
+    if cfg!(target_os = "linux") {
+        assert_eq!(
+            run("exec -a custom cat /proc/self/cmdline\n"),
+            "custom\0/proc/self/cmdline\0"
+        );
+    }
+    assert_eq!(shell_status("exec -a\n"), 2);
+    assert_eq!(
+        errors("exec -a\n"),
+        "ish: exec: -a: option requires an argument\n"
+    );
+}
+
+#[test]
+fn failed_exec_ends_a_script() {
+    assert_eq!(
+        shell_status("exec ish-no-such-command\necho not reached\n"),
+        127
+    );
+    assert_eq!(
+        errors("exec ish-no-such-command\n"),
+        "ish: exec: ish-no-such-command: command not found\n"
+    );
+}
+
+#[test]
+fn exec_without_a_command_keeps_redirections() {
+    let file = test_dir().join("exec-redirect");
+    let file = file.display();
+    assert_eq!(
+        run(&format!(
+            "exec > {file}\necho into the file\ncat {file} >&2\n"
+        )),
+        ""
+    );
+    assert_eq!(
+        errors(&format!(
+            "exec > {file}\necho into the file\ncat {file} >&2\n"
+        )),
+        "into the file\n"
+    );
+}
+
+#[test]
+fn signals_dont_make_commands_fail() {
+    let file = test_dir().join("signalled_output");
+    let input = format!(
+        "sleep 1\necho slept $?\necho written > {0}\ncat < {0}\n",
+        file.display()
+    );
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    // the handler for SIGWINCH interrupts whatever system call the shell is
+    // in, until it has exited. Not after, the pid could be reused
+    let pid = shell.id() as libc::pid_t;
+    while shell.try_wait().unwrap().is_none() {
+        unsafe {
+            libc::kill(pid, libc::SIGWINCH);
+            libc::kill(pid, libc::SIGCHLD);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    let mut stdout = String::new();
+    shell
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+    let mut stderr = String::new();
+    shell
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert_eq!(stderr, "");
+    assert_eq!(stdout, "slept 0\nwritten\n");
+}
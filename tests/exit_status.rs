@@ -0,0 +1,154 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use common::{errors, run, script, shell_status};
+
+#[test]
+fn status_starts_at_zero() {
+    assert_eq!(run("echo $?\n"), "0\n");
+}
+
+#[test]
+fn status_of_external_commands() {
+    assert_eq!(run("true\necho $?\n"), "0\n");
+    assert_eq!(run("false\necho $?\n"), "1\n");
+    assert_eq!(run("ls /nonexistent\necho $?\n"), "2\n");
+    let exit3 = script("exit3.sh", "exit 3\n");
+    assert_eq!(run(&format!("sh {}\necho $?\n", exit3.display())), "3\n");
+}
+
+#[test]
+fn status_of_pipeline_is_the_last_stage() {
+    assert_eq!(run("false | true\necho $?\n"), "0\n");
+    assert_eq!(run("true | false\necho $?\n"), "1\n");
+}
+
+#[test]
+fn status_of_builtins() {
+    assert_eq!(run("false\ncd /\necho $?\n"), "0\n");
+    assert_eq!(run("cd /nonexistent\necho $?\n"), "1\n");
+    assert_eq!(run("cd\necho $?\n"), "1\n");
+    assert_eq!(run("false\njobs\necho $?\n"), "0\n");
+    assert_eq!(run("fg\necho $?\n"), "1\n");
+}
+
+#[test]
+fn status_of_command_not_found() {
+    assert_eq!(run("ish-no-such-command\necho $?\n"), "127\n");
+}
+
+#[test]
+fn status_of_command_not_executable() {
+    let noexec = script("noexec.sh", "echo hi\n");
+    let dir = noexec.parent().unwrap();
+    assert_eq!(run(&format!("{}\necho $?\n", noexec.display())), "126\n");
+    assert_eq!(run(&format!("{}\necho $?\n", dir.display())), "126\n");
+    assert_eq!(
+        run(&format!("{}/missing\necho $?\n", dir.display())),
+        "127\n"
+    );
+}
+
+#[test]
+fn spawn_error_messages() {
+    assert_eq!(
+        errors("ish-no-such-command\n"),
+        "ish: ish-no-such-command: command not found\n"
+    );
+    let noexec = script("noexec.sh", "echo hi\n");
+    assert_eq!(
+        errors(&format!("{}\n", noexec.display())),
+        format!("ish: {}: Permission denied\n", noexec.display())
+    );
+    let dir = noexec.parent().unwrap();
+    assert_eq!(
+        errors(&format!("{}\n", dir.display())),
+        format!("ish: {}: Is a directory\n", dir.display())
+    );
+}
+
+#[test]
+fn status_of_signal_death() {
+    let kill9 = script("kill9.sh", "kill -9 $$\n");
+    assert_eq!(run(&format!("sh {}\necho $?\n", kill9.display())), "137\n");
+    let term = script("term.sh", "kill $$\n");
+    assert_eq!(run(&format!("sh {}\necho $?\n", term.display())), "143\n");
+}
+
+#[test]
+fn signal_deaths_are_reported() {
+    let kill9 = script("kill9.sh", "kill -9 $$\n");
+    assert_eq!(errors(&format!("sh {}\n", kill9.display())), "Killed\n");
+    let term = script("term.sh", "kill $$\n");
+    assert_eq!(errors(&format!("sh {}\n", term.display())), "Terminated\n");
+    // the ones that happen all the time stay quiet
+    let pipe = script("pipe.sh", "kill -PIPE $$\n");
+    assert_eq!(errors(&format!("sh {}\n", pipe.display())), "");
+    let int = script("int.sh", "kill -INT $$\n");
+    assert_eq!(errors(&format!("sh {}\n", int.display())), "");
+}
+
+#[test]
+fn interrupt_at_the_prompt() {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .args(["-i", "--norc"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = shell.stdin.take().unwrap();
+    let mut stdout = shell.stdout.take().unwrap();
+    let mut output = Vec::new();
+    let mut read_until = |end: &[u8]| {
+        while !output.ends_with(end) {
+            let mut buf = [0; 64];
+            let n = stdout.read(&mut buf).unwrap();
+            assert!(n > 0);
+            output.extend_from_slice(&buf[..n]);
+        }
+    };
+    stdin.write_all(b"true\n").unwrap();
+    // the signal has to arrive while the shell waits at the second prompt
+    read_until(b"> > ");
+    thread::sleep(Duration::from_millis(100));
+    let kill = Command::new("kill")
+        .args(["-INT", &shell.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(kill.success());
+    read_until(b"^C\n> ");
+    stdin.write_all(b"echo $?\n").unwrap();
+    drop(stdin);
+    stdout.read_to_end(&mut output).unwrap();
+    assert!(shell.wait().unwrap().success());
+    assert_eq!(String::from_utf8(output).unwrap(), "> > ^C\n> 130\n> ");
+}
+
+#[test]
+fn status_is_expanded_inside_words() {
+    assert_eq!(run("false\necho status=$?\n"), "status=1\n");
+}
+
+#[test]
+fn shell_exits_with_status_of_last_command() {
+    assert_eq!(shell_status(""), 0);
+    assert_eq!(shell_status("true\n"), 0);
+    assert_eq!(shell_status("false\n"), 1);
+    assert_eq!(shell_status("ish-no-such-command\n"), 127);
+}
+
+#[test]
+fn exit_builtin_status() {
+    assert_eq!(shell_status("false\nexit\n"), 1);
+    assert_eq!(shell_status("exit 5\n"), 5);
+    assert_eq!(shell_status("exit 300\n"), 44);
+    assert_eq!(shell_status("exit nope\n"), 2);
+    assert_eq!(shell_status("exit 3\ntrue\n"), 3);
+}
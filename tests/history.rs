@@ -0,0 +1,238 @@
+mod common;
+
+use std::{fs, thread};
+
+use common::{run_shell_with_env, script, test_dir};
+
+// run an interactive shell without a startup file, saving history to `histfile`
+fn session(histfile: &str, input: &str) -> String {
+    let output = run_shell_with_env(&["-i", "--norc"], &[("HISTFILE", histfile)], input);
+    String::from_utf8(output.stdout).unwrap().replace("> ", "")
+}
+
+#[test]
+fn history_lists_entered_commands() {
+    let histfile = test_dir().join("list_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    assert_eq!(
+        session(histfile, "echo a\n\necho b\nhistory\n"),
+        "a\nb\n    1  echo a\n    2  echo b\n    3  history\n"
+    );
+    assert_eq!(
+        session(histfile, "history 2\n"),
+        "    3  history\n    4  history 2\n"
+    );
+}
+
+#[test]
+fn history_is_saved_after_each_command() {
+    let histfile = test_dir().join("saved_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    session(histfile, "echo a\necho b\n");
+    assert_eq!(saved(histfile), "echo a\necho b\n");
+    session(histfile, "echo c\n");
+    assert_eq!(saved(histfile), "echo a\necho b\necho c\n");
+}
+
+#[test]
+fn multi_line_entries_round_trip() {
+    let histfile = test_dir().join("escaped_history");
+    let histfile = histfile.to_str().unwrap();
+    fs::write(histfile, "echo one\\necho two\nback\\\\slash\n").unwrap();
+    assert_eq!(
+        session(histfile, "history\n"),
+        "    1  echo one\necho two\n    2  back\\slash\n    3  history\n"
+    );
+}
+
+#[test]
+fn non_interactive_shells_keep_no_history() {
+    let histfile = test_dir().join("script_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    run_shell_with_env(&[], &[("HISTFILE", histfile)], "echo a\n");
+    assert!(fs::metadata(histfile).is_err());
+}
+
+// the entries saved in `histfile`, without the lines with their times
+fn saved(histfile: &str) -> String {
+    let contents = fs::read_to_string(histfile).unwrap();
+    let entries = contents.lines().filter(|line| !line.starts_with('#'));
+    entries.map(|line| format!("{}\n", line)).collect()
+}
+
+// like session, with HISTSIZE and HISTFILESIZE set
+fn limited_session(histfile: &str, size: &str, file_size: &str, input: &str) -> String {
+    let env = [
+        ("HISTFILE", histfile),
+        ("HISTSIZE", size),
+        ("HISTFILESIZE", file_size),
+    ];
+    let output = run_shell_with_env(&["-i", "--norc"], &env, input);
+    String::from_utf8(output.stdout).unwrap().replace("> ", "")
+}
+
+#[test]
+fn repeated_commands_are_kept_once() {
+    let histfile = test_dir().join("duplicate_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    assert_eq!(
+        session(histfile, "echo a\necho a\necho b\necho a\nhistory\n"),
+        "a\na\nb\na\n    1  echo a\n    2  echo b\n    3  echo a\n    4  history\n"
+    );
+    assert_eq!(saved(histfile), "echo a\necho b\necho a\nhistory\n");
+}
+
+#[test]
+fn numbers_stay_stable_when_old_entries_are_dropped() {
+    let histfile = test_dir().join("trimmed_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    assert_eq!(
+        limited_session(
+            histfile,
+            "3",
+            "100",
+            "echo a\necho b\necho c\necho d\nhistory\n!3\n!1\n"
+        ),
+        "a\nb\nc\nd\n    3  echo c\n    4  echo d\n    5  history\necho c\nc\n"
+    );
+    let output = run_shell_with_env(
+        &["-i", "--norc"],
+        &[("HISTFILE", histfile), ("HISTSIZE", "3")],
+        "echo a\necho b\necho c\necho d\n!1\n",
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("ish: !1: event not found"));
+}
+
+#[test]
+fn history_file_is_trimmed_to_histfilesize() {
+    let histfile = test_dir().join("file_size_history");
+    let histfile = histfile.to_str().unwrap();
+    fs::write(histfile, "one\ntwo\nthree\nfour\nfive\n").unwrap();
+    assert_eq!(
+        limited_session(histfile, "2", "3", "history\n"),
+        "    2  five\n    3  history\n"
+    );
+    assert_eq!(saved(histfile), "three\nfour\nfive\nhistory\n");
+}
+
+#[test]
+fn history_references_are_expanded() {
+    let histfile = test_dir().join("expanded_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    assert_eq!(
+        session(histfile, "echo a\necho b\n!!\n!-2 c\necho !1!\nhistory 2\n"),
+        "a\nb\necho b\nb\necho a c\na c\necho echo a!\necho a!\n    4  echo echo a!\n    5  history 2\n"
+    );
+}
+
+#[test]
+fn entries_are_saved_with_the_time_they_were_entered() {
+    let histfile = test_dir().join("timed_history");
+    let histfile = histfile.to_str().unwrap();
+    // a file from before times were saved still loads
+    fs::write(histfile, "old\n#1700000000\necho timed\n").unwrap();
+    let listed = limited_session(histfile, "10", "10", "history -t\n");
+    let lines: Vec<&str> = listed.lines().collect();
+    assert_eq!(lines[0], "    1  ?? old");
+    assert!(lines[1].starts_with("    2  2023-11-1"));
+    assert!(lines[1].ends_with(" echo timed"));
+    assert!(lines[2].starts_with("    3  "));
+    let contents = fs::read_to_string(histfile).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines[..3], ["old", "#1700000000", "echo timed"]);
+    assert!(lines[3][1..].parse::<u64>().unwrap() > 1700000000);
+    assert_eq!(lines[4], "history -t");
+
+    let output = run_shell_with_env(
+        &["-i", "--norc"],
+        &[("HISTFILE", histfile), ("HISTTIMEFORMAT", "[%Y] ")],
+        "history 1\n",
+    );
+    let listed = String::from_utf8(output.stdout).unwrap();
+    assert!(listed.starts_with("> "), "{}", listed);
+    assert!(listed.contains("  [20"), "{}", listed);
+    assert!(listed.contains("] history 1\n"), "{}", listed);
+}
+
+#[test]
+fn history_entries_can_be_deleted() {
+    let histfile = test_dir().join("deleted_history");
+    let histfile = histfile.to_str().unwrap();
+    fs::write(histfile, "one\ntwo\n#1700000000\nsecret\nthree\nfour\n").unwrap();
+    assert_eq!(
+        session(histfile, "history -d 3\nhistory -d 3-4\nhistory\n"),
+        "    1  one\n    2  two\n    3  history -d 3\n    4  history -d 3-4\n    5  history\n"
+    );
+    assert_eq!(
+        saved(histfile),
+        "one\ntwo\nhistory -d 3\nhistory -d 3-4\nhistory\n"
+    );
+    let output = run_shell_with_env(
+        &["-i", "--norc"],
+        &[("HISTFILE", histfile)],
+        "history -d 99\nhistory -d 2-1\nhistory -d x\n",
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: history: 99: history position out of range\n\
+         ish: history: 2-1: history position out of range\n\
+         ish: history: x: numeric argument required\n\
+         exit\n"
+    );
+}
+
+#[test]
+fn history_of_other_sessions_is_merged() {
+    let histfile = test_dir().join("shared_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    let other = script("other_session", "echo theirs\n");
+    let nested = format!(
+        "{} -i --norc < {}",
+        env!("CARGO_BIN_EXE_ish"),
+        other.display()
+    );
+    let input = format!("echo mine\n{}\nhistory -n\nhistory -n\nhistory\n", nested);
+    assert_eq!(
+        session(histfile, &input),
+        format!(
+            "mine\ntheirs\n    1  echo mine\n    2  {}\n    3  history -n\n    4  echo theirs\n    5  history -n\n    6  history\n",
+            nested
+        )
+    );
+}
+
+#[test]
+fn sessions_writing_at_once_keep_every_entry() {
+    let histfile = test_dir().join("concurrent_history");
+    let histfile = histfile.to_str().unwrap();
+    let _ = fs::remove_file(histfile);
+    let sessions: Vec<_> = (0..4)
+        .map(|session| {
+            let input: String = (0..30)
+                .map(|i| format!("true {} {}\n", session, i))
+                .collect();
+            let histfile = histfile.to_string();
+            thread::spawn(move || {
+                run_shell_with_env(&["-i", "--norc"], &[("HISTFILE", &histfile)], &input)
+            })
+        })
+        .collect();
+    for session in sessions {
+        session.join().unwrap();
+    }
+    let saved = saved(histfile);
+    let mut entries: Vec<&str> = saved.lines().collect();
+    entries.sort();
+    entries.dedup();
+    assert_eq!(entries.len(), 120);
+    assert!(entries.iter().all(|entry| entry.starts_with("true ")));
+}
@@ -0,0 +1,267 @@
+#![cfg(not(target_vendor = "apple"))]
+
+mod common;
+
+use std::{fs, thread, time::Duration};
+
+use common::{
+    pty::{wait_for_state, Pty},
+    script, test_dir,
+};
+
+// start `line` in the foreground, returning its process group
+fn start(pty: &mut Pty, line: &str) -> u32 {
+    pty.send(line);
+    pty.send("\n");
+    pty.foreground_job()
+}
+
+// the shell says which job Ctrl-Z stopped, by its number
+fn stopped(pty: &mut Pty, number: usize, pgid: u32) {
+    pty.expect(&format!("[{}] Stopped\n", number));
+    pty.prompt();
+    wait_for_state(pgid, Some('T'));
+}
+
+#[test]
+fn ctrl_c_interrupts_the_foreground_job() {
+    let mut pty = Pty::spawn();
+    let pgid = start(&mut pty, "sleep 30");
+    pty.send("\x03");
+    pty.prompt();
+    wait_for_state(pgid, None);
+    assert_eq!(pty.run("echo $?"), "130\n");
+}
+
+#[test]
+fn ctrl_c_at_the_prompt_throws_the_line_away() {
+    let mut pty = Pty::spawn();
+    pty.send("echo partial");
+    pty.expect("partial");
+    pty.send("\x03");
+    pty.prompt();
+    assert_eq!(pty.run("echo after"), "after\n");
+}
+
+#[test]
+fn ctrl_z_stops_the_job_and_fg_continues_it() {
+    let mut pty = Pty::spawn();
+    let pgid = start(&mut pty, "sleep 30");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, pgid);
+    assert_eq!(pty.run("jobs"), format!("[1] {} Stopped\n", pgid));
+
+    assert_eq!(start(&mut pty, "fg"), pgid);
+    wait_for_state(pgid, Some('S'));
+    pty.send("\x03");
+    pty.prompt();
+    wait_for_state(pgid, None);
+    assert_eq!(pty.run("echo $?"), "130\n");
+    assert_eq!(pty.run("jobs"), "");
+}
+
+#[test]
+fn bg_continues_a_stopped_job() {
+    let mut pty = Pty::spawn();
+    let pgid = start(&mut pty, "sleep 30");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, pgid);
+    assert_eq!(pty.run("bg"), "");
+    wait_for_state(pgid, Some('S'));
+    assert_eq!(pty.run("jobs"), format!("[1] {} Running\n", pgid));
+    pty.run(&format!("kill {}", pgid));
+    wait_for_state(pgid, None);
+}
+
+#[test]
+fn fg_and_bg_take_the_job_to_continue() {
+    let mut pty = Pty::spawn();
+    let first = start(&mut pty, "sleep 30");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, first);
+    let second = start(&mut pty, "sleep 31");
+    pty.send("\x1a");
+    stopped(&mut pty, 2, second);
+
+    assert_eq!(pty.run("bg %1"), "");
+    wait_for_state(first, Some('S'));
+    assert_eq!(
+        pty.run("jobs"),
+        format!("[1] {} Running\n[2] {} Stopped\n", first, second)
+    );
+    assert_eq!(pty.run("fg %3"), "ish: fg: %3: no such job\n");
+    assert_eq!(pty.run("bg %3"), "ish: bg: %3: no such job\n");
+
+    assert_eq!(start(&mut pty, "fg %1"), first);
+    pty.send("\x03");
+    pty.prompt();
+    wait_for_state(first, None);
+    assert_eq!(pty.run("jobs"), format!("[2] {} Stopped\n", second));
+    // stopped again, it keeps its number
+    assert_eq!(start(&mut pty, "fg %2"), second);
+    pty.send("\x1a");
+    stopped(&mut pty, 2, second);
+    pty.run(&format!("kill -9 {}", second));
+    wait_for_state(second, None);
+}
+
+#[test]
+fn a_continued_job_reads_the_terminal_again() {
+    let mut pty = Pty::spawn();
+    let pgid = start(&mut pty, "cat");
+    pty.send("one\n");
+    pty.expect("one\none\n");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, pgid);
+    start(&mut pty, "fg");
+    pty.send("two\n");
+    pty.expect("two\ntwo\n");
+    pty.send("\x04");
+    pty.prompt();
+    assert_eq!(pty.run("echo $?"), "0\n");
+}
+
+#[test]
+fn background_jobs_reading_the_terminal_are_stopped() {
+    let mut pty = Pty::spawn();
+    pty.run("cat &");
+    let jobs = pty.run("jobs");
+    let pgid = jobs.split(' ').nth(1).unwrap().parse().unwrap();
+    wait_for_state(pgid, Some('T'));
+    // the shell keeps the terminal
+    assert_eq!(pty.run("echo still here"), "still here\n");
+    pty.run(&format!("kill -KILL {}", pgid));
+    wait_for_state(pgid, None);
+}
+
+#[test]
+fn wait_sleeps_until_a_job_stops_or_ctrl_c() {
+    let mut pty = Pty::spawn();
+    pty.run("sleep 30 &");
+    let jobs = pty.run("jobs");
+    let pgid: u32 = jobs.split(' ').nth(1).unwrap().parse().unwrap();
+
+    pty.send("wait\n");
+    pty.waiting();
+    pty.send("\x03");
+    pty.prompt();
+    assert_eq!(pty.run("echo $?"), "130\n");
+
+    // there's nothing left to wait for once the job is stopped
+    pty.send("wait\n");
+    pty.waiting();
+    unsafe { libc::kill(pgid as i32, libc::SIGSTOP) };
+    pty.prompt();
+    assert_eq!(pty.run("echo $?"), "0\n");
+    assert_eq!(pty.run("jobs"), format!("[1] {} Stopped\n", pgid));
+    unsafe { libc::kill(pgid as i32, libc::SIGKILL) };
+}
+
+#[test]
+fn exit_ends_the_shell_with_its_status() {
+    let mut pty = Pty::spawn();
+    pty.send("exit 3\n");
+    assert_eq!(pty.wait().code(), Some(3));
+}
+
+#[test]
+fn nohup_keeps_a_command_off_the_terminal() {
+    let mut pty = Pty::spawn();
+    assert_eq!(
+        pty.run("nohup sh -c 'kill -HUP $$; echo out; echo err >&2; cat'"),
+        "nohup: appending output to 'nohup.out'\n"
+    );
+    let output = fs::read_to_string(test_dir().join("nohup.out")).unwrap();
+    assert_eq!(output, "out\nerr\n");
+}
+
+#[test]
+fn nohup_keeps_a_builtin_running_after_a_hangup() {
+    // a nohup.out of its own
+    let dir = test_dir().join("nohup_builtin");
+    fs::create_dir_all(&dir).unwrap();
+    let done = dir.join("done");
+    let sourced = script(
+        "nohup_builtin.ish",
+        &format!("sleep 1\nprintf survived > {}\n", done.display()),
+    );
+    let mut pty = Pty::spawn();
+    pty.run(&format!("cd {}", dir.display()));
+    // a builtin in the background runs in a subshell of its own
+    pty.run(&format!("nohup source {} &", sourced.display()));
+    let jobs = pty.run("jobs");
+    let pgid: u32 = jobs.split(' ').nth(1).unwrap().parse().unwrap();
+    unsafe { libc::kill(-(pgid as i32), libc::SIGHUP) };
+    wait_for_state(pgid, None);
+    assert_eq!(fs::read_to_string(&done).unwrap(), "survived");
+}
+
+// a script has no job control, what it runs is in the foreground with it
+#[test]
+fn commands_of_a_script_read_the_terminal() {
+    let reader = script("reader", "echo ready\ncat\necho status $?\n");
+    let mut pty = Pty::spawn();
+    pty.send(&format!(
+        "{} {}\n",
+        env!("CARGO_BIN_EXE_ish"),
+        reader.display()
+    ));
+    pty.expect("ready\n");
+    pty.send("hello\n");
+    pty.expect("hello\nhello\n");
+    pty.send("\x04");
+    assert_eq!(pty.prompt(), "status 0\n");
+}
+
+#[test]
+fn ctrl_c_interrupts_the_commands_of_a_script() {
+    // what prints ready is the sleep, so it's running by the time the Ctrl-C comes
+    let sleeper = script(
+        "sleeper",
+        "sh -c 'echo ready; exec sleep 30'\necho status $?\n",
+    );
+    let mut pty = Pty::spawn();
+    pty.send(&format!(
+        "{} {}\n",
+        env!("CARGO_BIN_EXE_ish"),
+        sleeper.display()
+    ));
+    pty.expect("ready\n");
+    pty.send("\x03");
+    // after the ^C the terminal echoed
+    assert!(pty.prompt().ends_with("status 130\n"));
+}
+
+#[test]
+fn time_limit_is_on_hold_while_the_job_is_stopped() {
+    let mut pty = Pty::spawn();
+    // sleep keeps counting while it's stopped, so it's done once it's
+    // continued, and only killed if the limit ran out meanwhile
+    let pgid = start(&mut pty, "timeout 2 sleep 1");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, pgid);
+    thread::sleep(Duration::from_secs(3));
+    pty.run("fg");
+    wait_for_state(pgid, None);
+    assert_eq!(pty.run("echo $?"), "0\n");
+
+    // the rest of the limit runs once it's continued
+    let pgid = start(&mut pty, "timeout 1 sleep 30");
+    pty.send("\x1a");
+    stopped(&mut pty, 1, pgid);
+    assert_eq!(start(&mut pty, "fg"), pgid);
+    pty.prompt();
+    wait_for_state(pgid, None);
+    assert_eq!(pty.run("echo $?"), "124\n");
+}
+
+#[test]
+fn output_without_a_newline_gets_the_prompt_on_a_line_of_its_own() {
+    let mut pty = Pty::spawn();
+    pty.resize(20, 10);
+    // a reversed % where the output ends, and enough spaces after it to wrap
+    // to the next row unless it started at the beginning of one
+    let marker = format!("\x1b[7m%\x1b[0m{}\r", " ".repeat(19));
+    assert_eq!(pty.run("printf foo"), format!("foo{}", marker));
+    assert_eq!(pty.run("printf 'bar\\n'"), format!("bar\n{}", marker));
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs;
+
+use common::{run, run_shell, run_shell_with_env, test_dir};
+
+#[test]
+fn jobs_are_numbered_from_one() {
+    let output = run("sleep 1 &\nsleep 1 &\njobs\n");
+    let numbers: Vec<&str> = output
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(numbers, ["[1]", "[2]"]);
+}
+
+#[test]
+fn captured_output_is_shown_when_the_job_finishes() {
+    let output = run("set -o bgcapture\necho hello &\nsleep 0.5\necho after\n");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "[1] Done");
+    assert_eq!(lines[1..], ["hello", "after"]);
+}
+
+#[test]
+fn capture_files_dont_follow_a_planted_link() {
+    let tmp = test_dir().join("capture_tmp");
+    fs::create_dir_all(&tmp).unwrap();
+    let victim = tmp.join("victim");
+    fs::write(&victim, "").unwrap();
+    // a link where the file for job 1 would go if its name could be guessed
+    let input = format!(
+        "ln -s {} \"$TMPDIR/ish-$$-job-1\"\nset -o bgcapture\necho hello &\nsleep 0.5\n",
+        victim.display()
+    );
+    let tmpdir = tmp.to_str().unwrap();
+    let output = run_shell_with_env(&[], &[("TMPDIR", tmpdir)], &input);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[1] Done\nhello\n"
+    );
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "");
+}
+
+#[test]
+fn captured_output_is_shown_on_demand() {
+    assert_eq!(
+        run("set -o bgcapture\nsh -c 'echo partial; echo oops >&2; sleep 1' &\nsleep 0.3\njobs -o %1\n"),
+        "partial\noops\n"
+    );
+    // the job's own redirections still win
+    assert_eq!(
+        run("set -o bgcapture\nsh -c 'echo out; sleep 1' > /dev/null &\nsleep 0.3\njobs -o\n"),
+        ""
+    );
+}
+
+#[test]
+fn jobs_output_errors() {
+    assert_eq!(run("jobs -o %1\necho $?\n"), "1\n");
+    assert_eq!(run("sleep 1 &\njobs -o %1\necho $?\n"), "1\n");
+    assert_eq!(run("jobs -x\necho $?\n"), "2\n");
+}
+
+#[test]
+fn tagged_output_has_the_job_number() {
+    let output = run("set -o bgtag\nsleep 1 &\nsh -c 'echo one; echo two' &\nsleep 0.3\n");
+    let tagged: Vec<&str> = output
+        .lines()
+        .filter(|line| !line.ends_with(" Done"))
+        .collect();
+    assert_eq!(tagged, ["[2] one", "[2] two"]);
+}
+
+#[test]
+fn ttyreset_needs_a_terminal() {
+    let output = run_shell("ttyreset\necho $?\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: ttyreset: no terminal\n"
+    );
+}
+
+#[test]
+fn background_signal_deaths_are_reported() {
+    let output = run("sh -c 'sleep 0.1; kill $$' &\nsleep 0.5\n");
+    assert!(output.contains("[1] killed: Terminated\n"), "{}", output);
+}
+
+#[test]
+fn wait_n_returns_the_status_of_the_first_job_to_finish() {
+    assert_eq!(
+        run("sleep 0.5 &\nsh -c 'sleep 0.1; exit 3' &\nwait -n\necho $?\nwait -n\necho $?\nwait -n\necho $?\n"),
+        "3\n0\n127\n"
+    );
+    assert_eq!(
+        run("sh -c 'sleep 0.1; exit 3' &\nsleep 1 &\nwait -n %1\necho $?\n"),
+        "3\n"
+    );
+}
+
+#[test]
+fn wait_waits_for_every_job() {
+    assert_eq!(
+        run("sleep 0.2 &\nsleep 0.3 &\nwait\necho $?\njobs\n"),
+        "0\n"
+    );
+    assert_eq!(run("wait %2\necho $?\n"), "127\n");
+}
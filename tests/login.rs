@@ -0,0 +1,82 @@
+mod common;
+
+use std::{
+    fs,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use common::{run_shell_with_env, test_dir};
+
+// a home directory of its own for each test, with `files` in it
+fn home(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let home = test_dir().join(name);
+    fs::create_dir_all(&home).unwrap();
+    for (file, contents) in files {
+        fs::write(home.join(file), contents).unwrap();
+    }
+    home
+}
+
+// stdout of a login shell running `input`, after whatever the system's
+// /etc/profile prints
+fn login(home: &Path, input: &str) -> String {
+    let env = [("HOME", home.to_str().unwrap())];
+    let output = run_shell_with_env(&["-l"], &env, input);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn ish_profile_is_sourced() {
+    let home = home(
+        "login_ish_profile",
+        &[
+            (".ish_profile", "echo ish profile\n"),
+            (".profile", "echo sh profile\n"),
+        ],
+    );
+    assert!(login(&home, "echo after\n").ends_with("ish profile\nafter\n"));
+}
+
+#[test]
+fn profile_sets_up_the_environment() {
+    let home = home(
+        "login_profile",
+        &[(
+            ".profile",
+            "if true; then export FROM_PROFILE='one\ntwo'; fi\n",
+        )],
+    );
+    assert!(login(&home, "printenv FROM_PROFILE\n").ends_with("one\ntwo\n"));
+}
+
+#[test]
+fn ish_logout_is_sourced_on_exit() {
+    let home = home("login_logout", &[(".ish_logout", "echo bye\n")]);
+    assert!(login(&home, "echo last\nexit 3\n").ends_with("last\nbye\n"));
+    let output = run_shell_with_env(&[], &[("HOME", home.to_str().unwrap())], "echo last\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "last\n");
+}
+
+#[test]
+fn login_option_is_read_only() {
+    let home = home("login_option", &[]);
+    assert!(login(&home, "set -o | grep login\n").ends_with("login           on\n"));
+    let env = [("HOME", home.to_str().unwrap())];
+    let output = run_shell_with_env(&["-l"], &env, "set +o login\necho $?\n");
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("1\n"));
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with("set: login: read-only option\n"));
+}
+
+#[test]
+fn a_dash_in_front_of_the_name_makes_a_login_shell() {
+    let home = home("login_dash", &[(".ish_profile", "echo ish profile\n")]);
+    let output = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .arg0("-ish")
+        .args(["-c", "echo $0"])
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("ish profile\n-ish\n"));
+}
@@ -0,0 +1,216 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+};
+
+use ish::{
+    expand::{expand_word, Parameters},
+    named_dirs::NamedDirs,
+    parser::{parse, RedirectOp},
+};
+
+// `word` expanded at line 1, after a command that ended with "last"
+fn expanded(word: &str, last_status: i32, positional: &[OsString]) -> Vec<OsString> {
+    let parameters = Parameters {
+        last_status,
+        positional,
+        line_number: 1,
+        last_argument: OsStr::new("last"),
+        extglob: false,
+        nocasematch: false,
+        named_dirs: &NamedDirs::default(),
+    };
+    expand_word(word, &parameters).unwrap()
+}
+
+fn words(line: &str) -> Vec<Vec<Cow<'_, str>>> {
+    let pipeline = parse(line).unwrap();
+    pipeline.commands.into_iter().map(|c| c.words).collect()
+}
+
+#[test]
+fn pipelines_are_split_into_commands() {
+    assert_eq!(
+        words("echo a 'b c' | tr a-z A-Z"),
+        [vec!["echo", "a", "'b c'"], vec!["tr", "a-z", "A-Z"]]
+    );
+    assert!(parse("sleep 1 &").unwrap().background);
+    assert!(!parse("sleep 1").unwrap().background);
+}
+
+#[test]
+fn redirections_are_kept_apart_from_words() {
+    let pipeline = parse("cat <in 2>>log").unwrap();
+    let command = &pipeline.commands[0];
+    assert_eq!(command.words, ["cat"]);
+    let redirects: Vec<_> = command
+        .redirects
+        .iter()
+        .map(|r| (r.fd, r.op, r.target.as_ref()))
+        .collect();
+    assert_eq!(
+        redirects,
+        [(0, RedirectOp::Read, "in"), (2, RedirectOp::Append, "log")]
+    );
+}
+
+#[test]
+fn syntax_errors_are_returned() {
+    assert!(parse("| cat").is_err());
+    assert!(parse("echo >").is_err());
+}
+
+#[test]
+fn words_are_expanded() {
+    let positional: Vec<OsString> = ["ish", "one", "two three"].map(OsString::from).into();
+    assert_eq!(expanded("'$1'", 0, &positional), ["$1"]);
+    assert_eq!(expanded("\"$2\"x", 0, &positional), ["two threex"]);
+    assert_eq!(expanded("$?", 3, &positional), ["3"]);
+    assert_eq!(expanded("$@", 0, &positional), ["one", "two three"]);
+    assert!(expanded("$5", 0, &positional).is_empty());
+    assert_eq!(
+        expanded("$LINENO ${LINENO} $HOME", 0, &positional),
+        ["1 1 $HOME"]
+    );
+}
+
+#[test]
+fn parameters_are_trimmed_and_substituted() {
+    let positional: Vec<OsString> = ["ish", "file.tar.gz", "/usr/local/bin", "a b"]
+        .map(OsString::from)
+        .into();
+    let expand = |word| expanded(word, 0, &positional);
+    assert_eq!(expand("${1%.*}.bak"), ["file.tar.bak"]);
+    assert_eq!(expand("${1%%.*} ${1#*.} ${1##*.}"), ["file tar.gz gz"]);
+    assert_eq!(expand("${2##*/} ${2%/*}"), ["bin /usr/local"]);
+    assert_eq!(expand("${1%\".gz\"} ${1%'*'}"), ["file.tar file.tar.gz"]);
+    assert_eq!(
+        expand("${1/.gz/.xz} ${1//[.a]/-}"),
+        ["file.tar.xz file-t-r-gz"]
+    );
+    assert_eq!(
+        expand("${1/#file/doc} ${1/%gz/$3}"),
+        ["doc.tar.gz file.tar.a b"]
+    );
+    assert_eq!(expand("${#1} ${#@} ${#}"), ["11 3 3"]);
+    assert_eq!(expand("\"${@%/*}\""), ["file.tar.gz", "/usr/local", "a b"]);
+    assert_eq!(expand("${@/ /_}"), ["file.tar.gz", "/usr/local/bin", "a_b"]);
+    assert_eq!(expand("<${*#?}>"), ["<ile.tar.gz usr/local/bin  b>"]);
+}
+
+#[test]
+fn parameters_are_sliced() {
+    let positional: Vec<OsString> = ["ish", "abcdefgh", "3", "héllo"].map(OsString::from).into();
+    let expand = |word| expanded(word, 0, &positional);
+    assert_eq!(expand("${1:2} ${1:2:3} ${1:1:-2}"), ["cdefgh cde bcdef"]);
+    assert_eq!(expand("${1: -3} ${1:(-3):2} ${1:1:$2}"), ["fgh fg bcd"]);
+    assert_eq!(expand("${1:10}|${1: -20:2}"), ["|ab"]);
+    assert_eq!(expand("${3:1:2}"), ["él"]);
+    assert_eq!(expand("${@:2}"), ["3", "héllo"]);
+    assert_eq!(expand("\"${@: -1}\""), ["héllo"]);
+    assert_eq!(expand("${@:0:2}"), ["ish", "abcdefgh"]);
+    assert!(expand("${@:5}").is_empty());
+    assert_eq!(
+        words("echo ${1: -3}|cat"),
+        [vec!["echo", "${1: -3}"], vec!["cat"]]
+    );
+}
+
+#[test]
+fn extglob_groups_match_in_patterns() {
+    let positional: Vec<OsString> = ["ish", "main.c", "notes.md", "ababcab"]
+        .map(OsString::from)
+        .into();
+    let expand = |word, extglob| {
+        let parameters = Parameters {
+            last_status: 0,
+            positional: &positional,
+            line_number: 1,
+            last_argument: OsStr::new("last"),
+            extglob,
+            nocasematch: false,
+            named_dirs: &NamedDirs::default(),
+        };
+        expand_word(word, &parameters).unwrap()
+    };
+    assert_eq!(expand("${1%.@(c|h)} ${2%.@(c|h)}", true), ["main notes.md"]);
+    assert_eq!(expand("${1##!(*.md)}|${2##!(*.md)}", true), ["|d"]);
+    assert_eq!(
+        expand("${3/+(ab)/X} ${3//?(a)b/-} ${3%%*(ab|c)}", true),
+        ["Xcab --c- "]
+    );
+    assert_eq!(expand("${1%.@(c|h)}", false), ["main.c"]);
+    assert_eq!(
+        words("echo ${1%@(.c|.h)}|cat"),
+        [vec!["echo", "${1%@(.c|.h)}"], vec!["cat"]]
+    );
+}
+
+#[test]
+fn nocasematch_substitutes_letters_in_either_case() {
+    let positional: Vec<OsString> = ["ish", "Photo.JPG", "ÉTÉ"].map(OsString::from).into();
+    let parameters = Parameters {
+        last_status: 0,
+        positional: &positional,
+        line_number: 1,
+        last_argument: OsStr::new("last"),
+        extglob: false,
+        nocasematch: true,
+        named_dirs: &NamedDirs::default(),
+    };
+    let expand = |word| expand_word(word, &parameters).unwrap();
+    assert_eq!(
+        expand("${1/%.jpg/.png} ${1//[a-o]/-}"),
+        ["Photo.png P--t-.-P-"]
+    );
+    assert_eq!(expand("${2//é/e} ${1%.jpg}"), ["eTe Photo.JPG"]);
+    assert_eq!(expanded("${1/%.jpg/.png}", 0, &positional), ["Photo.JPG"]);
+}
+
+#[test]
+fn substitutions_ish_doesnt_have_are_errors() {
+    let positional = [OsString::from("ish")];
+    let parameters = Parameters {
+        last_status: 0,
+        positional: &positional,
+        line_number: 1,
+        last_argument: OsStr::new("last"),
+        extglob: false,
+        nocasematch: false,
+        named_dirs: &NamedDirs::default(),
+    };
+    let error = |word| expand_word(word, &parameters).unwrap_err().to_string();
+    assert_eq!(error("[${1:-default}]"), "${1:-default}: bad substitution");
+    assert_eq!(error("${1+x}"), "${1+x}: bad substitution");
+    assert_eq!(error("\"${@:?unset}\""), "${@:?unset}: bad substitution");
+    assert_eq!(error("${#1%x}"), "${#1%x}: bad substitution");
+    assert_eq!(error("${}"), "${}: bad substitution");
+    assert_eq!(error("a${1"), "${1: bad substitution");
+    assert_eq!(
+        error("${1#$(<missing_file)}"),
+        "missing_file: No such file or directory"
+    );
+}
+
+#[test]
+fn file_substitutions_are_one_word() {
+    assert_eq!(
+        words("cat $(< a b)>out|cat"),
+        [vec!["cat", "$(< a b)"], vec!["cat"]]
+    );
+}
+
+#[test]
+fn words_are_slices_of_the_line() {
+    let pipeline = parse("echo plain 'quoted word' a\\\nb \"c\\\nd\" e\\\\").unwrap();
+    let words = &pipeline.commands[0].words;
+    assert_eq!(
+        words,
+        &["echo", "plain", "'quoted word'", "ab", "\"cd\"", "e\\\\"]
+    );
+    let borrowed: Vec<bool> = words
+        .iter()
+        .map(|word| matches!(word, Cow::Borrowed(_)))
+        .collect();
+    assert_eq!(borrowed, [true, true, true, false, false, true]);
+}
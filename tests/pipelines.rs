@@ -0,0 +1,100 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{run, run_shell, run_shell_with_env, test_dir};
+
+#[test]
+fn builtin_output_goes_down_the_pipe() {
+    assert_eq!(
+        run("set -o | grep xtrace | tr a-z A-Z\n"),
+        "XTRACE          OFF\n"
+    );
+    assert_eq!(run("jobs | wc -l\n").trim(), "0");
+}
+
+#[test]
+fn history_can_be_piped() {
+    let histfile = test_dir().join("piped_history");
+    let histfile = histfile.to_str().unwrap();
+    std::fs::write(histfile, "cargo build\nls\ncargo test\n").unwrap();
+    let output = run_shell_with_env(
+        &["-i", "--norc"],
+        &[("HISTFILE", histfile)],
+        "history | grep cargo\n",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().replace("> ", ""),
+        "    1  cargo build\n    3  cargo test\n    4  history | grep cargo\n"
+    );
+}
+
+#[test]
+fn builtin_reads_from_the_pipe() {
+    // the builtin ignores its input, the pipeline still finishes
+    assert_eq!(run("echo hi | set -o suggest\necho $?\n"), "0\n");
+}
+
+#[test]
+fn builtins_in_a_pipeline_run_in_a_subshell() {
+    assert_eq!(run("cd / | cat\npwd\n"), run("pwd\n"));
+    assert_eq!(
+        run("set +o suggest | cat\nset -o | grep ^suggest\n"),
+        "suggest         on\n"
+    );
+    // exit only leaves the subshell
+    assert_eq!(run("exit 3 | cat\necho still here\n"), "still here\n");
+}
+
+#[test]
+fn pipeline_status_comes_from_a_builtin_at_the_end() {
+    assert_eq!(run("true | exit 4\necho $?\n"), "4\n");
+    assert_eq!(run("false | cd /nonexistent\necho $?\n"), "1\n");
+}
+
+#[test]
+fn background_builtins_run_in_a_subshell() {
+    // the monitor may also report the background job finishing
+    let output = run("cd / &\npwd\n");
+    assert!(output.contains(&run("pwd\n")));
+    assert!(!output.lines().any(|line| line == "/"));
+}
+
+// a pipeline with a missing command must not leave the other stages running,
+// or reading the rest of the script from the shell's stdin
+fn failed_pipeline(line: &str) -> (String, String, Duration) {
+    let start = Instant::now();
+    let output = run_shell(&format!("{}\necho $?\necho after\n", line));
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        start.elapsed(),
+    )
+}
+
+#[test]
+fn first_stage_fails_to_start() {
+    let (stdout, stderr, _) = failed_pipeline("nosuchcommand | cat");
+    assert_eq!(stdout, "127\nafter\n");
+    assert_eq!(stderr, "ish: nosuchcommand: command not found\n");
+}
+
+#[test]
+fn middle_stage_fails_to_start() {
+    let (stdout, stderr, elapsed) = failed_pipeline("yes | nosuchcommand | cat");
+    assert_eq!(stdout, "127\nafter\n");
+    assert_eq!(stderr, "ish: nosuchcommand: command not found\n");
+    assert!(elapsed < Duration::from_secs(5));
+}
+
+#[test]
+fn last_stage_fails_to_start() {
+    let (stdout, _, elapsed) = failed_pipeline("sleep 30 | /nonexistent/command");
+    assert_eq!(stdout, "127\nafter\n");
+    assert!(elapsed < Duration::from_secs(5));
+
+    let dir = test_dir();
+    let (stdout, stderr, _) = failed_pipeline(&format!("yes | {}", dir.display()));
+    assert_eq!(stdout, "126\nafter\n");
+    assert_eq!(stderr, format!("ish: {}: Is a directory\n", dir.display()));
+}
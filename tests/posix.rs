@@ -0,0 +1,108 @@
+mod common;
+
+use std::{os::unix::fs::symlink, process::Command};
+
+use common::{run_args, run_shell_with_env, script, test_dir};
+
+// small sh scripts, one command per line, that ish in posix mode runs like dash
+const SCRIPTS: &[(&str, &str)] = &[
+    (
+        "fields",
+        "printf '[%s]' $1 x$2y \"$1\" ${1}z $3 \"\" $4\n\
+         printf '\\n'\n\
+         printf '[%s]' $* \"$*\" $@ \"$@\"\n\
+         printf '\\n'\n",
+    ),
+    (
+        "parameters",
+        "printf '%s\\n' $# \"$0\" ${2}\n\
+         false\n\
+         printf '%s\\n' $?\n\
+         printf '%s\\n' $5 done\n",
+    ),
+    (
+        "quoting",
+        "printf '%s\\n' 'single $1' \"double $1\" \\$1 \"a\\\"b\" 'it'\\''s' \"\\\\$1\"\n\
+         printf '%s\\n' a\\ b \"tab\there\" ''\n",
+    ),
+    (
+        "pipelines",
+        "printf 'b\\na\\n' | sort\n\
+         printf 'one two\\n' | tr a-z A-Z | sed s/O/0/g\n\
+         printf '%s\\n' $1 | wc -w\n",
+    ),
+    (
+        "redirections",
+        "printf 'x\\n' > out\n\
+         printf 'y\\n' >> out\n\
+         cat < out\n\
+         wc -l < out\n\
+         ls /nonexistent 2> /dev/null\n\
+         printf '%s\\n' $?\n\
+         sh -c 'echo to stderr >&2' 2>&1\n\
+         rm out\n",
+    ),
+    (
+        "status",
+        "sh -c 'exit 3'\n\
+         printf '%s\\n' $?\n\
+         /nonexistent 2> /dev/null\n\
+         printf '%s\\n' $?\n\
+         timeout x true 2>&1\n\
+         printf '%s\\n' $?\n\
+         sh -c 'exit 4'\n",
+    ),
+];
+
+#[test]
+fn scripts_run_like_they_do_in_dash() {
+    let dir = test_dir();
+    for (name, contents) in SCRIPTS {
+        let path = script(&format!("posix_{}", name), contents);
+        let args = [path.to_str().unwrap(), " a  b ", "c d", "", "  "];
+        // without dash there's nothing to compare with
+        let Ok(dash) = Command::new("dash").args(args).current_dir(&dir).output() else {
+            return;
+        };
+        let ish = Command::new(env!("CARGO_BIN_EXE_ish"))
+            .arg("--posix")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&ish.stdout),
+            String::from_utf8_lossy(&dash.stdout),
+            "{}",
+            name
+        );
+        assert_eq!(ish.status.code(), dash.status.code(), "{}", name);
+    }
+}
+
+#[test]
+fn running_as_sh_turns_posix_mode_on() {
+    let sh = test_dir().join("sh");
+    let _ = symlink(env!("CARGO_BIN_EXE_ish"), &sh);
+    let output = Command::new(&sh)
+        .args(["-c", "set -o | grep posix"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "posix           on\n"
+    );
+    let output = run_args(&["--posix", "-c", "set -o | grep posix"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "posix           on\n"
+    );
+}
+
+#[test]
+fn posix_mode_has_no_history_expansion() {
+    let histfile = test_dir().join("posix_history");
+    let env = [("HISTFILE", histfile.to_str().unwrap()), ("PS1", "")];
+    let output = run_shell_with_env(&["--posix", "-i", "--norc"], &env, "echo a\necho !!\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a\n!!\n");
+}
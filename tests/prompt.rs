@@ -0,0 +1,182 @@
+mod common;
+
+use std::fs;
+
+use common::{run_shell_with_env, test_dir};
+
+fn prompts(ps1: &str, input: &str) -> String {
+    let output = run_shell_with_env(&["-i"], &[("PS1", ps1)], input);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn ps1_escapes_are_expanded() {
+    assert_eq!(prompts("\\\\ \\n\\q ", ""), "\\ \n\\q ");
+    // # for root
+    assert!(["$ ", "# "].contains(&prompts("\\$ ", "").as_str()));
+    assert_eq!(prompts("\\101\\e\\a\\0 ", ""), "A\x1b\x07\0 ");
+}
+
+#[test]
+fn colors_are_left_out_when_not_on_a_terminal() {
+    assert_eq!(prompts("[\\[\\e[1m\\]x\\[\\e[0m\\]] ", ""), "[x] ");
+}
+
+#[test]
+fn prompt_shows_the_exit_status() {
+    assert_eq!(
+        prompts("\\(x \\?\\|ok\\) ", "false\ntrue\nexit 3\n"),
+        "ok x 1 ok "
+    );
+    assert_eq!(
+        prompts("\\(failed \\)$ ", "ish-no-such-command\n"),
+        "$ failed $ "
+    );
+}
+
+#[test]
+fn ps1_shows_the_current_directory() {
+    let env = [("HOME", "/nonexistent"), ("PS1", "\\w \\W: ")];
+    let output = run_shell_with_env(&["-i"], &env, "cd /usr/bin\ncd /\n");
+    let output = String::from_utf8(output.stdout).unwrap();
+    let cwd = std::env::current_dir().unwrap();
+    let name = cwd.file_name().unwrap().to_str().unwrap();
+    assert_eq!(
+        output,
+        format!("{} {}: /usr/bin bin: / /: ", cwd.display(), name)
+    );
+}
+
+#[test]
+fn ps2_is_shown_for_continuation_lines() {
+    let output = run_shell_with_env(
+        &["-i"],
+        &[("PS1", "$ "), ("PS2", "\\\\.. ")],
+        "echo 'a\nb' |\ncat\n",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "$ \\.. \\.. a\nb\n$ "
+    );
+}
+
+#[test]
+fn home_is_shown_as_tilde() {
+    let home = test_dir().join("prompt_home");
+    fs::create_dir_all(home.join("a/b/c")).unwrap();
+    let home = home.to_str().unwrap();
+    let input = format!("cd {}\ncd a/b/c\ncd /\n", home);
+    let output = run_shell_with_env(&["-i"], &[("HOME", home), ("PS1", "\\w \\W: ")], &input);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("~ ~: ~/a/b/c c: / /: "));
+
+    let env = [("HOME", home), ("PS1", "\\w: "), ("PROMPT_DIRTRIM", "2")];
+    let input = format!("cd {}/a/b\ncd c\ncd /usr/bin\n", home);
+    let output = run_shell_with_env(&["-i"], &env, &input);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("~/a/b: ~/.../b/c: /usr/bin: "));
+}
+
+#[test]
+fn named_directories_are_shown_by_name() {
+    let home = test_dir().join("prompt_named");
+    fs::create_dir_all(home.join("work/big/src")).unwrap();
+    let home = home.to_str().unwrap();
+    let input = "namedir big=~/work/big\ncd ~big/src\ncd ~/work\ncd ~big\n";
+    let env = [("HOME", home), ("PS1", "\\w: ")];
+    let output = run_shell_with_env(&["-i"], &env, input);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with(": ~big/src: ~/work: ~big: "));
+}
+
+#[test]
+fn deleted_directory_is_still_shown() {
+    let gone = test_dir().join("prompt_gone");
+    fs::create_dir_all(&gone).unwrap();
+    let input = format!(
+        "cd {}\nrmdir {}\necho still here\n",
+        gone.display(),
+        gone.display()
+    );
+    let output = run_shell_with_env(&["-i"], &[("PS1", "\\W: ")], &input);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("prompt_gone: prompt_gone: still here\nprompt_gone: "));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "exit\n");
+}
+
+#[test]
+#[cfg(feature = "prompt-git")]
+fn prompt_shows_the_git_branch() {
+    let repo = test_dir().join("prompt_repo");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::create_dir_all(repo.join("src/deep")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/topic\n").unwrap();
+    let input = format!("cd {}/src/deep\ncd /\n", repo.display());
+    let output = prompts("\\{(\\g) \\}$ ", &input);
+    assert!(output.ends_with("(topic) $ $ "));
+
+    fs::write(
+        repo.join(".git/HEAD"),
+        "94b8f6d0c2a7e04c4f1d16c4c2f1e7d7cbd3c6b1\n",
+    )
+    .unwrap();
+    let output = prompts("\\{(\\g) \\}$ ", &input);
+    assert!(output.ends_with("(94b8f6d) $ $ "));
+}
+
+#[test]
+fn prompt_command_runs_before_each_prompt() {
+    let env = [
+        ("PS1", "(\\?)$ "),
+        ("PROMPT_COMMAND", "echo hook $?\nfalse"),
+    ];
+    let output = run_shell_with_env(&["-i"], &env, "false\necho $?\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "hook 0\n(0)$ hook 1\n(1)$ 1\nhook 0\n(0)$ "
+    );
+}
+
+#[test]
+fn broken_prompt_command_is_reported_once() {
+    let env = [("PS1", "$ "), ("PROMPT_COMMAND", "echo 'open")];
+    let output = run_shell_with_env(&["-i"], &env, "echo one\necho two\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "$ one\n$ two\n$ "
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: PROMPT_COMMAND: unexpected EOF while looking for matching `''\nexit\n"
+    );
+}
+
+#[test]
+fn prompt_shows_the_number_of_jobs() {
+    assert_eq!(
+        prompts("\\{[\\j jobs] \\}$ ", "sleep 1 &\ntrue\n"),
+        "$ [1 jobs] $ [1 jobs] $ "
+    );
+}
+
+#[test]
+fn preexec_command_runs_before_each_line() {
+    let env = [("PS1", "$ "), ("PREEXEC_COMMAND", "echo running $1\nfalse")];
+    let output = run_shell_with_env(&["-i"], &env, "echo 'a b'\n\nsh -c 'exit 3'\necho $?\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "$ running echo 'a b'\na b\n$ $ running sh -c 'exit 3'\n$ running echo $?\n3\n$ "
+    );
+}
+
+#[test]
+fn preexec_command_can_exit() {
+    let env = [("PS1", "$ "), ("PREEXEC_COMMAND", "exit 4")];
+    let output = run_shell_with_env(&["-i"], &env, "echo never\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "$ ");
+    assert_eq!(output.status.code(), Some(4));
+}
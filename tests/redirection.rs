@@ -0,0 +1,105 @@
+mod common;
+
+use std::fs;
+
+use common::{errors, run, run_shell, test_dir};
+
+#[test]
+fn output_and_input_redirection() {
+    let file = test_dir().join("redirect_out");
+    let file = file.display();
+    assert_eq!(
+        run(&format!(
+            "echo one > {file}\necho two >>{file}\ncat <{file}\n"
+        )),
+        "one\ntwo\n"
+    );
+    assert_eq!(run(&format!("cat<{file}|wc -l\n")).trim(), "2");
+}
+
+#[test]
+fn stderr_redirection_and_duplication() {
+    let file = test_dir().join("redirect_err");
+    let file = file.display();
+    assert_eq!(run(&format!("ls /nonexistent 2> {file}\necho $?\n")), "2\n");
+    assert!(!fs::read_to_string(test_dir().join("redirect_err"))
+        .unwrap()
+        .is_empty());
+    // 2>&1 after the pipe is set up sends errors down the pipe too
+    assert_eq!(run("ls /nonexistent 2>&1 | wc -l\n").trim(), "1");
+    assert_eq!(errors("ls /nonexistent 2>&-\n"), "");
+}
+
+#[test]
+fn missing_input_file_is_an_error() {
+    let output = run_shell("cat < /nonexistent\necho $?\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: /nonexistent: No such file or directory\n"
+    );
+}
+
+#[test]
+fn unwritable_output_file_is_an_error() {
+    let output = run_shell("echo hi > /nonexistent/file\necho $?\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: /nonexistent/file: No such file or directory\n"
+    );
+}
+
+#[test]
+fn bad_file_descriptor_is_an_error() {
+    assert_eq!(run("echo hi >&7\necho $?\n"), "1\n");
+    assert_eq!(errors("echo hi >&7\n"), "ish: 7: Bad file descriptor\n");
+    assert_eq!(run("echo hi 3>&1 1>&2 2>&3 3>&- | wc -c\n").trim(), "0");
+}
+
+#[test]
+fn failed_stage_gives_the_next_one_empty_input() {
+    assert_eq!(run("cat < /nonexistent | wc -c\n").trim(), "0");
+}
+
+#[test]
+fn builtins_can_be_redirected() {
+    let file = test_dir().join("redirect_builtin");
+    let file = file.display();
+    assert_eq!(
+        run(&format!(
+            "set -o > {file}\necho after\ngrep ^suggest {file}\n"
+        )),
+        "after\nsuggest         on\n"
+    );
+    assert_eq!(errors("cd /nonexistent 2>/dev/null\n"), "");
+    // a redirection without a command just creates the file
+    let empty = test_dir().join("redirect_empty");
+    let _ = fs::remove_file(&empty);
+    run(&format!("> {}\n", empty.display()));
+    assert_eq!(fs::read_to_string(empty).unwrap(), "");
+}
+
+#[test]
+fn quotes_keep_words_together() {
+    assert_eq!(run("echo 'a  b' \"c  $#\" d\\ e\n"), "a  b c  0 d e\n");
+    assert_eq!(run("echo '|' \">\" '#' x#y # comment\n"), "| > # x#y\n");
+    assert_eq!(run("printf '[%s]' '' $9\n"), "[]");
+}
+
+#[test]
+fn syntax_errors() {
+    assert_eq!(
+        errors("echo >\n"),
+        "ish: syntax error near unexpected token `newline'\n"
+    );
+    assert_eq!(
+        errors("| cat\n"),
+        "ish: syntax error near unexpected token `|'\n"
+    );
+    assert_eq!(
+        errors("echo 'oops\n"),
+        "ish: unexpected EOF while looking for matching `''\n"
+    );
+    assert_eq!(run("echo >\necho $?\n"), "2\n");
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::run_shell_with_env;
+
+#[test]
+fn slow_pipelines_report_their_time() {
+    let output = run_shell_with_env(&[], &[("ISH_REPORTTIME", "0.2")], "true\nsleep 0.3 | cat\n");
+    let report = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        report.starts_with("sleep 0.3 | cat  0.00s user "),
+        "{}",
+        report
+    );
+    assert!(report.ends_with(" total\n"), "{}", report);
+    assert_eq!(report.lines().count(), 1);
+}
+
+#[test]
+fn reporting_is_off_by_default() {
+    let output = run_shell_with_env(&[], &[("ISH_REPORTTIME", "0")], "sleep 0.3\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::{os::unix::fs::symlink, process::Command};
+
+use common::{run_shell_with_args, test_dir};
+
+// stdout and stderr of a restricted shell running `input`
+fn restricted(input: &str) -> (String, String) {
+    let output = run_shell_with_args(&["-r"], input);
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn cd_is_refused() {
+    let dir = test_dir();
+    let input = format!("cd {}\necho $?\npwd\n", dir.display());
+    let (stdout, stderr) = restricted(&input);
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(stdout, format!("1\n{}\n", cwd.display()));
+    assert_eq!(stderr, "ish: cd: restricted\n");
+}
+
+#[test]
+fn commands_with_a_slash_are_refused() {
+    let (stdout, stderr) = restricted("/bin/echo hi\necho $?\necho plain\n");
+    assert_eq!(stdout, "1\nplain\n");
+    assert_eq!(
+        stderr,
+        "ish: /bin/echo: restricted: cannot specify `/' in command names\n"
+    );
+}
+
+#[test]
+fn output_redirections_are_refused() {
+    let file = test_dir().join("restricted_output");
+    let input = format!(
+        "echo hi > {0}\necho $?\necho hi >> {0}\necho err 2>&1\ncat < /dev/null\n",
+        file.display()
+    );
+    let (stdout, stderr) = restricted(&input);
+    assert_eq!(stdout, "1\nerr\n");
+    let refused = format!(
+        "ish: {}: restricted: cannot redirect output\n",
+        file.display()
+    );
+    assert_eq!(stderr, refused.repeat(2));
+    assert!(!file.exists());
+}
+
+#[test]
+fn exec_is_refused() {
+    let (stdout, stderr) = restricted("exec echo replaced\necho $?\n");
+    assert_eq!(stdout, "1\n");
+    assert_eq!(stderr, "ish: exec: restricted\n");
+}
+
+#[test]
+fn running_as_rish_is_restricted() {
+    let rish = test_dir().join("rish");
+    let _ = symlink(env!("CARGO_BIN_EXE_ish"), &rish);
+    let output = Command::new(&rish).args(["-c", "cd /"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "ish: cd: restricted\n"
+    );
+    // no option turns it off
+    let (_, stderr) = restricted("set +r\ncd /\n");
+    assert!(stderr.ends_with("ish: cd: restricted\n"), "{}", stderr);
+}
@@ -0,0 +1,332 @@
+mod common;
+
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    process::Command,
+};
+
+use common::{
+    errors, executable, run, run_args, run_shell_with_args, run_shell_with_env, script, test_dir,
+};
+
+#[test]
+fn file_without_shebang_is_run_by_ish() {
+    // `set -o suggest` only exists in ish, /bin/sh would fail on it
+    let plain = executable("plain.sh", b"set +o suggest\nexit 7\n");
+    assert_eq!(run(&format!("{}\necho $?\n", plain.display())), "7\n");
+}
+
+#[test]
+fn binary_for_another_architecture_is_not_run_as_a_script() {
+    let binary = executable("binary", b"\x7fELF\x02\x01\x01\x00\x00\x00");
+    assert_eq!(run(&format!("{}\necho $?\n", binary.display())), "126\n");
+    assert_eq!(
+        errors(&format!("{}\n", binary.display())),
+        format!("ish: {}: Exec format error\n", binary.display())
+    );
+}
+
+#[test]
+fn script_gets_positional_parameters() {
+    let params = script("params.ish", "echo $0 $1 $2 ${2} $#\necho all $@ end\n");
+    let path = params.to_str().unwrap();
+    let output = run_args(&[path, "a", "b"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{} a b b 2\nall a b end\n", path)
+    );
+}
+
+#[test]
+fn script_tolerates_shebang_comments_and_blank_lines() {
+    let commented = script(
+        "commented.ish",
+        "#!/usr/bin/env ish\n# a comment\n\necho one # trailing\necho two#three\n",
+    );
+    let output = run_args(&[commented.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "one\ntwo#three\n"
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn commands_continue_on_the_next_line() {
+    assert_eq!(run("echo 'a\nb'\necho x |\ntr x y\n"), "a\nb\ny\n");
+    assert_eq!(
+        run("echo c\\\nd \\\ne # no 'quote\necho \"f\\\ng\"\n"),
+        "cd e\nfg\n"
+    );
+    assert_eq!(
+        errors("echo 'open\n"),
+        "ish: unexpected EOF while looking for matching `''\n"
+    );
+}
+
+#[test]
+fn script_exits_with_status_of_last_command() {
+    let failing = script("failing.ish", "true\nfalse\n");
+    assert_eq!(
+        run_args(&[failing.to_str().unwrap()]).status.code(),
+        Some(1)
+    );
+    let exiting = script("exiting.ish", "exit 4\necho unreachable\n");
+    let output = run_args(&[exiting.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn script_errors_report_script_and_line() {
+    let broken = script("broken.ish", "true\n\nish-no-such-command\n");
+    let path = broken.to_str().unwrap();
+    let output = run_args(&[path]);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "ish: {}: line 3: ish-no-such-command: command not found\n",
+            path
+        )
+    );
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[test]
+fn lineno_is_the_line_a_command_starts_on() {
+    let lines = script(
+        "lineno.ish",
+        "echo $LINENO\necho \"a\nb\" $LINENO\necho \\\n  ${LINENO}\n\necho $LINENO\nish-no-such-command \\\n  x\n",
+    );
+    let path = lines.to_str().unwrap();
+    let output = run_args(&[path]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1\na\nb 2\n4\n7\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "ish: {}: line 8: ish-no-such-command: command not found\n",
+            path
+        )
+    );
+}
+
+#[test]
+fn missing_script() {
+    let output = run_args(&["/nonexistent/script.ish"]);
+    assert_eq!(output.status.code(), Some(127));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "ish: /nonexistent/script.ish: No such file or directory\n"
+    );
+}
+
+#[test]
+fn command_string() {
+    let output = run_args(&["-c", "echo $1 $2 $#", "name", "a", "b"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a b 2\n");
+    let output = run_args(&["-c", "echo $0", "name"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "name\n");
+    let output = run_args(&["-c", "echo one\necho two"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "one\ntwo\n");
+}
+
+#[test]
+fn command_string_exit_status() {
+    assert_eq!(run_args(&["-c", "false"]).status.code(), Some(1));
+    assert_eq!(run_args(&["-c", "exit 3"]).status.code(), Some(3));
+    assert_eq!(run_args(&["-c", "true"]).status.code(), Some(0));
+    assert_eq!(run_args(&["-c"]).status.code(), Some(2));
+}
+
+#[test]
+fn commands_from_a_pipe_print_no_prompt() {
+    assert_eq!(run("echo one\necho two\n"), "one\ntwo\n");
+}
+
+#[test]
+fn stdin_with_positional_parameters() {
+    let output = run_shell_with_args(&["-s", "a", "b"], "echo $1 $2 $#\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a b 2\n");
+}
+
+#[test]
+fn forced_interactive_prints_prompts() {
+    let output = run_shell_with_args(&["-i"], "echo one\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "> one\n> ");
+}
+
+#[test]
+fn commands_are_looked_up_again_after_they_move() {
+    let first = test_dir().join("path_first");
+    let second = test_dir().join("path_second");
+    for dir in [&first, &second] {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+    }
+    let moving = first.join("moving");
+    fs::write(&moving, "echo first\n").unwrap();
+    fs::set_permissions(&moving, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!("{}:{}:/bin:/usr/bin", first.display(), second.display());
+    let script = format!(
+        "moving\nmv {} {}\nmoving\n",
+        moving.display(),
+        second.join("moving").display()
+    );
+    let output = run_shell_with_env(&[], &[("PATH", &path)], &script);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "first\nfirst\n");
+}
+
+#[test]
+fn arguments_that_arent_utf8_are_kept() {
+    let file = test_dir().join(OsStr::from_bytes(b"not-utf8-\xff"));
+    let output = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .args(["-c", "echo kept > \"$1\"\nprintf %s $1"])
+        .arg("ish")
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, file.as_os_str().as_bytes());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "kept\n");
+}
+
+#[test]
+fn unknown_options_print_the_usage() {
+    let output = run_args(&["--nope"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("ish: --nope: invalid option\nusage: ish "));
+    let output = run_args(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("usage: ish "));
+    let output = run_args(&["--version"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("ish {}\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn scripts_after_a_double_dash_can_start_with_one() {
+    let dir = test_dir().join("dash-script");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("-script"), "echo $0 $1\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ish"))
+        .args(["--", "-script", "arg"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "-script arg\n");
+}
+
+#[test]
+fn xtrace_shows_the_commands() {
+    let output = run_args(&["-x", "-c", "echo 'a b' c\nset +x\necho d"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a b c\nd\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "+ echo 'a b' c\n+ set +x\n"
+    );
+}
+
+#[test]
+fn errexit_stops_at_the_first_failure() {
+    let output = run_args(&["-e", "-c", "echo a\nsh -c 'exit 3'\necho b"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\n");
+    assert_eq!(output.status.code(), Some(3));
+    let output = run_shell_with_args(&[], "false\nset -e\necho a\nfalse\necho b\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn noexec_only_checks_the_syntax() {
+    let checked = script("checked.ish", "echo a >\necho ran\ntouch x\necho \"b\n");
+    let path = checked.to_str().unwrap();
+    let output = run_args(&["-n", path]);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "ish: {}: line 1: syntax error near unexpected token `newline'\n\
+             ish: {}: line 4: unexpected EOF while looking for matching `\"'\n",
+            path, path
+        )
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_shell_with_args(&[], "false\necho a\nset -n\necho b\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn underscore_is_the_last_argument_of_the_command_before() {
+    let deep = test_dir().join("deep/er");
+    let deep = deep.to_str().unwrap();
+    let output = run_args(&[
+        "-c",
+        &format!(
+            "mkdir -p {}\ncd $_\npwd\necho a b | cat\necho $_\nprintenv _",
+            deep
+        ),
+    ]);
+    let printenv = Command::new("sh")
+        .args(["-c", "command -v printenv"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{}\na b\ncat\n{}",
+            deep,
+            String::from_utf8_lossy(&printenv.stdout)
+        )
+    );
+}
+
+#[test]
+fn file_substitution_reads_the_file() {
+    let file = test_dir().join("file_substitution");
+    fs::write(&file, "one two\nthree\n\n\n").unwrap();
+    let path = file.to_str().unwrap();
+    assert_eq!(
+        run(&format!("printf '[%s]' $(<{0}) \"$(< {0} )\"\n", path)),
+        "[one two\nthree][one two\nthree]"
+    );
+    let output = run_shell_with_args(&["--posix"], &format!("printf '[%s]' $(<{})\n", path));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[one][two][three]");
+    assert_eq!(
+        errors("printf '[%s]' $(<missing_file) x\n"),
+        "ish: missing_file: No such file or directory\n"
+    );
+
+    // a file that can't be read fails the command where the script has it
+    let unread = script("unread_file.ish", "printf x $(<missing_file)\necho $?\n");
+    let output = run_args(&[unread.to_str().unwrap()]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "ish: {}: line 1: missing_file: No such file or directory\n",
+            unread.display()
+        )
+    );
+}
+
+#[test]
+fn substitutions_ish_doesnt_have_fail_the_command() {
+    assert_eq!(
+        errors("printf '[%s]' \"${1:-default}\"\n"),
+        "ish: ${1:-default}: bad substitution\n"
+    );
+    assert_eq!(run("printf '[%s]' ${1:=x} x\necho $?\n"), "1\n");
+    // one that isn't closed takes the rest of the line with it
+    assert_eq!(run("printf '[%s]\\n' ${1 | cat\necho $?\n"), "1\n");
+}
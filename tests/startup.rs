@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs;
+
+use common::{run_shell_with_args, run_shell_with_env, script, test_dir};
+
+fn stdout(output: std::process::Output) -> String {
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// a home directory with the given ~/.ishrc
+fn home_with_rc(name: &str, rc: &str) -> String {
+    let home = test_dir().join(name);
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join(".ishrc"), rc).unwrap();
+    home.to_str().unwrap().to_string()
+}
+
+#[test]
+fn interactive_shell_sources_ishrc() {
+    let home = home_with_rc("rc-home", "echo from rc\n");
+    let output = run_shell_with_env(&["-i"], &[("HOME", &home)], "echo hi\n");
+    assert_eq!(stdout(output), "from rc\n> hi\n> ");
+}
+
+#[test]
+fn errors_in_ishrc_are_not_fatal() {
+    let home = home_with_rc("broken-rc-home", "ish-no-such-command\necho after\n");
+    let output = run_shell_with_env(&["-i"], &[("HOME", &home)], "echo hi\n");
+    assert_eq!(
+        String::from_utf8(output.stderr.clone()).unwrap(),
+        format!(
+            "ish: {}/.ishrc: line 1: ish-no-such-command: command not found\nexit\n",
+            home
+        )
+    );
+    assert_eq!(stdout(output), "after\n> hi\n> ");
+}
+
+#[test]
+fn ishrc_is_skipped() {
+    let home = home_with_rc("skipped-rc-home", "echo from rc\n");
+    // with --norc
+    let output = run_shell_with_env(&["-i", "--norc"], &[("HOME", &home)], "echo hi\n");
+    assert_eq!(stdout(output), "> hi\n> ");
+    // and in non-interactive shells
+    let output = run_shell_with_env(&[], &[("HOME", &home)], "echo hi\n");
+    assert_eq!(stdout(output), "hi\n");
+}
+
+#[test]
+fn rcfile_overrides() {
+    let home = home_with_rc("override-rc-home", "echo from rc\n");
+    let alt = script("alt.ishrc", "echo from alt\n");
+    let alt = alt.to_str().unwrap();
+    let output = run_shell_with_env(&["-i", "--rcfile", alt], &[("HOME", &home)], "");
+    assert_eq!(stdout(output), "from alt\n> ");
+    let output = run_shell_with_env(&["-i"], &[("HOME", &home), ("ISH_RC", alt)], "");
+    assert_eq!(stdout(output), "from alt\n> ");
+}
+
+#[test]
+fn source_builtin() {
+    let sourced = script("sourced.ish", "echo sourced $1\nfalse\n");
+    let path = sourced.to_str().unwrap();
+    let output = run_shell_with_env(
+        &["-s", "arg"],
+        &[],
+        &format!("source {}\necho $?\n. {}\n", path, path),
+    );
+    assert_eq!(stdout(output), "sourced arg\n1\nsourced arg\n");
+}
+
+#[test]
+fn ignoreeof_keeps_the_shell_open_for_ten_eofs() {
+    let output = run_shell_with_args(&["-i", "--norc"], "set -o ignoreeof\n");
+    let hint = "Use \"exit\" to leave the shell.\n";
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("{}exit\n", hint.repeat(9))
+    );
+    let output = run_shell_with_args(&["-i", "--norc"], "echo hi\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "exit\n");
+}
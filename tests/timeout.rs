@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{errors, run};
+
+#[test]
+fn command_within_its_time_limit() {
+    assert_eq!(run("timeout 5 echo hi\necho $?\n"), "hi\n0\n");
+    assert_eq!(run("timeout 5s sh -c 'exit 3'\necho $?\n"), "3\n");
+    // a limit of 0 is no limit at all
+    assert_eq!(run("timeout 0 true\necho $?\n"), "0\n");
+}
+
+#[test]
+fn command_over_its_time_limit_is_killed() {
+    let start = Instant::now();
+    assert_eq!(run("timeout 0.2 sleep 10\necho $?\n"), "124\n");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn whole_pipeline_is_limited() {
+    let start = Instant::now();
+    assert_eq!(run("timeout 0.2s sleep 10 | sleep 10\necho $?\n"), "124\n");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn sigkill_after_the_grace_period() {
+    let start = Instant::now();
+    assert_eq!(
+        run("timeout -k 0.2 0.2 sh -c 'trap \"\" TERM; sleep 10'\necho $?\n"),
+        "124\n"
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn bad_time_limits() {
+    assert_eq!(
+        errors("timeout 5x sleep 1\n"),
+        "ish: timeout: 5x: invalid time interval\n"
+    );
+    assert_eq!(errors("timeout 5\n"), "ish: timeout: missing command\n");
+    assert_eq!(run("timeout\necho $?\n"), "125\n");
+}